@@ -1,19 +1,215 @@
+use std::collections::BTreeSet;
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::PathBuf;
 
-use hypescript_vm::ExecutionContext;
+use hypescript_vm::trace::{format_stack, format_vars};
+use hypescript_vm::{ExecutionContext, Termination};
 use structopt::StructOpt;
 
 #[derive(StructOpt)]
 struct Options {
     #[structopt(short, long)]
     trace: bool,
+
+    /// Keep only the given number of most recent steps in the trace (implies --trace)
+    #[structopt(long)]
+    trace_limit: Option<usize>,
+
+    /// Step through the program in an interactive debugger
+    ///
+    /// Debugger commands are read from the file given with --commands if there is one, from stdin
+    /// if the program does not read input, and otherwise from the terminal (on Unix only).
+    #[structopt(short, long)]
+    interactive: bool,
+
+    /// Read debugger commands from the given file (implies --interactive)
+    #[structopt(long)]
+    commands: Option<PathBuf>,
+
+    /// Treat the program as assembly source, rather than bytecode (the default for `.hys` files)
+    #[structopt(short, long)]
+    asm: bool,
+
     path: PathBuf,
 }
 
+const DEBUGGER_HELP: &str = "\
+commands:
+  s, step          execute the next instruction
+  c, continue      run until a breakpoint is reached or the program finishes
+  stack            print the operand stack
+  vars             print the local variables
+  b, break <pc>    set a breakpoint at a program address (decimal, or hex with 0x)
+  q, quit          exit the debugger
+  h, help          print this message";
+
+/// Print the current location of the debugger.
+fn show_location(out: &mut impl Write, context: &ExecutionContext) -> io::Result<()> {
+    let pc = context.program_counter();
+    match context.next_instruction() {
+        Some(instr) => writeln!(out, "pc {pc:#06x}: {instr}"),
+        None => writeln!(out, "pc {pc:#06x}: <no instruction>"),
+    }
+}
+
+/// Report the termination of the program.
+fn show_termination(out: &mut impl Write, termination: Termination) -> io::Result<()> {
+    match termination {
+        Termination::Halted => writeln!(out, "Program halted"),
+        Termination::FellOffEnd => writeln!(out, "Program reached the end"),
+        Termination::HostAborted => writeln!(out, "Program aborted by host"),
+    }
+}
+
+/// Parse a program address, in decimal or hexadecimal.
+fn parse_address(s: &str) -> Option<usize> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        usize::from_str_radix(hex, 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+/// Execute one instruction, reporting errors and termination.
+///
+/// Returns `true` if execution can continue.
+fn debugger_step(out: &mut impl Write, context: &mut ExecutionContext) -> io::Result<bool> {
+    match context.step() {
+        Ok(None) => Ok(true),
+        Ok(Some(termination)) => {
+            show_termination(out, termination)?;
+            Ok(false)
+        }
+        Err(err) => {
+            writeln!(out, "Program halted with {err}")?;
+            Ok(false)
+        }
+    }
+}
+
+/// Run the interactive debugger until the user quits or input ends.
+///
+/// Commands are read from `commands`, and the debugger's own output is written to `out`; the
+/// program's input and output streams are whatever `context` was configured with.
+fn debug(
+    mut context: ExecutionContext,
+    mut commands: impl BufRead,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let mut breakpoints = BTreeSet::new();
+    let mut line = String::new();
+
+    writeln!(out, "{DEBUGGER_HELP}")?;
+    show_location(&mut out, &context)?;
+
+    loop {
+        write!(out, "(hype) ")?;
+        out.flush()?;
+
+        line.clear();
+        match commands.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("Error reading command: {err}");
+                break;
+            }
+        }
+
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (None, _) => {}
+
+            (Some("s" | "step"), None) => {
+                if debugger_step(&mut out, &mut context)? {
+                    show_location(&mut out, &context)?;
+                }
+            }
+
+            (Some("c" | "continue"), None) => {
+                while debugger_step(&mut out, &mut context)? {
+                    if breakpoints.contains(&context.program_counter()) {
+                        writeln!(out, "Breakpoint reached")?;
+                        show_location(&mut out, &context)?;
+                        break;
+                    }
+                }
+            }
+
+            (Some("stack"), None) => {
+                let mut formatted = String::new();
+                format_stack(&mut formatted, context.stack()).unwrap();
+                write!(out, "{formatted}")?;
+            }
+
+            (Some("vars"), None) => {
+                let mut formatted = String::new();
+                format_vars(&mut formatted, context.local_vars()).unwrap();
+                write!(out, "{formatted}")?;
+            }
+
+            (Some("b" | "break"), Some(addr)) => match parse_address(addr) {
+                Some(pc) => {
+                    breakpoints.insert(pc);
+                    writeln!(out, "Breakpoint set at pc {pc:#06x}")?;
+                }
+                None => writeln!(out, "Invalid program address `{addr}`")?,
+            },
+
+            (Some("q" | "quit"), None) => break,
+
+            (Some("h" | "help"), None) => writeln!(out, "{DEBUGGER_HELP}")?,
+
+            _ => writeln!(
+                out,
+                "Unrecognized command; type `help` for a list of commands"
+            )?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Open the stream from which debugger commands are read.
+///
+/// An explicit commands file takes precedence. Otherwise, stdin is used if the program never
+/// reads input; if it does, stdin is left to the program and commands are read from the terminal,
+/// which is only supported on Unix.
+fn open_commands(path: Option<&PathBuf>, program: &[u8]) -> Result<Box<dyn BufRead>, String> {
+    if let Some(path) = path {
+        return File::open(path)
+            .map(|file| Box::new(BufReader::new(file)) as Box<dyn BufRead>)
+            .map_err(|err| format!("Couldn't open {}: {}", path.display(), err));
+    }
+
+    let reads_input = hypescript_bytecode::uses_io(program).map_or(true, |usage| usage.input);
+    if !reads_input {
+        return Ok(Box::new(BufReader::new(io::stdin())));
+    }
+
+    #[cfg(unix)]
+    {
+        File::open("/dev/tty")
+            .map(|tty| Box::new(BufReader::new(tty)) as Box<dyn BufRead>)
+            .map_err(|err| format!("Couldn't open terminal for debugger commands: {}", err))
+    }
+
+    #[cfg(not(unix))]
+    {
+        Err("Program reads from stdin; pass debugger commands with --commands".to_string())
+    }
+}
+
 fn main() {
-    let Options { trace, path } = Options::from_args();
+    let Options {
+        trace,
+        trace_limit,
+        interactive,
+        commands,
+        asm,
+        path,
+    } = Options::from_args();
 
     let mut file = match File::open(&path) {
         Ok(file) => file,
@@ -29,13 +225,50 @@ fn main() {
         std::process::exit(1);
     }
 
+    if asm || path.extension().is_some_and(|ext| ext == "hys") {
+        let source = match String::from_utf8(program) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("Error reading {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        };
+
+        program = match hypescript_bytecode::asm::assemble(&source) {
+            Ok(instrs) => hypescript_bytecode::instructions_to_vec(&instrs),
+            Err(err) => {
+                eprintln!("Error assembling {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        };
+    }
+
     let input_stream = BufReader::new(std::io::stdin());
     let output_stream = std::io::stdout();
     let context = ExecutionContext::new(&program)
         .with_input_stream(input_stream)
         .with_output_stream(output_stream);
 
-    let context = if trace { context.with_trace() } else { context };
+    if interactive || commands.is_some() {
+        let commands = match open_commands(commands.as_ref(), &program) {
+            Ok(commands) => commands,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        };
+        if let Err(err) = debug(context, commands, io::stdout()) {
+            eprintln!("Error writing debugger output: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let trace = trace || trace_limit.is_some();
+    let context = match trace_limit {
+        Some(limit) => context.with_bounded_trace(limit),
+        None => context.with_trace_enabled(trace),
+    };
 
     match context.run() {
         Ok(summary) => {
@@ -50,3 +283,38 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn scripted_debugger() {
+        // Divides by zero at pc 4
+        let instrs = hypescript_bytecode::asm::assemble("push8 6\npush8 0\ndiv\nhalt\n").unwrap();
+        let program = hypescript_bytecode::instructions_to_vec(&instrs);
+        let context = ExecutionContext::new(&program).with_captured_output();
+
+        let commands = Cursor::new("break 4\ncontinue\nstack\nstep\nbogus\nstack\nquit\nstack\n");
+        let mut out = Vec::new();
+        debug(context, commands, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let responses: Vec<&str> = out.split("(hype) ").skip(1).collect();
+        assert_eq!(responses.len(), 7);
+        assert_eq!(responses[0], "Breakpoint set at pc 0x0004\n");
+        assert_eq!(responses[1], "Breakpoint reached\npc 0x0004: div\n");
+        assert_eq!(responses[2].lines().count(), 2);
+        assert!(responses[3].starts_with("Program halted with runtime error at pc 4 (div)"));
+        assert!(responses[3].contains("divide by zero"));
+
+        // The debugger keeps accepting commands after the error, until told to quit
+        // (the failed division has already popped its operands, and the final `stack` is never
+        // read)
+        assert!(responses[4].starts_with("Unrecognized command"));
+        assert_eq!(responses[5], "");
+        assert_eq!(responses[6], "");
+    }
+}
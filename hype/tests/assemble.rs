@@ -0,0 +1,53 @@
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn run_assembly() {
+    let dir = std::env::temp_dir().join(format!("hype-assemble-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("countdown.hys");
+    fs::write(
+        &input,
+        "\
+// Count down from 3
+    push8 3
+
+loop:
+    dup0
+    print
+    push8 1
+    sub
+    dup0
+    push8s @loop    // jump back while nonzero
+    jcond
+
+    pop
+    halt
+",
+    )
+    .unwrap();
+
+    let result = Command::new(env!("CARGO_BIN_EXE_hype"))
+        .arg(&input)
+        .output()
+        .unwrap();
+
+    assert!(result.status.success());
+    assert_eq!(String::from_utf8(result.stdout).unwrap(), "3\n2\n1\n");
+
+    // Assembly errors are reported with their line
+    let input = dir.join("broken.hys");
+    fs::write(&input, "push8 1\npush8 @nowhere\njump\n").unwrap();
+
+    let result = Command::new(env!("CARGO_BIN_EXE_hype"))
+        .arg(&input)
+        .output()
+        .unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8(result.stderr)
+        .unwrap()
+        .contains("line 2: Undefined label `nowhere`"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -3,10 +3,23 @@ use std::fs::File;
 use std::io::Read;
 use std::path::PathBuf;
 
+use hypescript_lang::parse::LexerOptions;
 use structopt::StructOpt;
 
 #[derive(StructOpt)]
 struct Options {
+    /// Allow block comments to nest
+    #[structopt(long)]
+    nested_comments: bool,
+
+    /// Print the number of emitted instructions and their encoded size
+    #[structopt(long)]
+    stats: bool,
+
+    /// Warn if the program can finish by running off its end, rather than at a `halt`
+    #[structopt(long)]
+    warn_fallthrough: bool,
+
     input_file: PathBuf,
     output_file: Option<PathBuf>,
 }
@@ -21,6 +34,22 @@ impl Options {
     }
 }
 
+/// Convert a character offset into the source to a one-based line and column.
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut column = 1;
+    for c in source.chars().take(offset) {
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+
+    (line, column)
+}
+
 fn run() -> Result<(), String> {
     let options = Options::from_args();
 
@@ -30,18 +59,49 @@ fn run() -> Result<(), String> {
         .read_to_string(&mut input)
         .map_err(|e| e.to_string())?;
 
-    let ast = hypescript_lang::parse::parse(&input).map_err(|errs| {
+    let lexer_options = LexerOptions {
+        nested_comments: options.nested_comments,
+    };
+    let ast =
+        hypescript_lang::parse::parse_with_options(&input, lexer_options).map_err(|errs| {
+            let mut err = String::new();
+            for e in errs {
+                writeln!(&mut err, "{e}").unwrap();
+            }
+            err
+        })?;
+
+    let (_, warnings) = hypescript_lang::types::typecheck_with_warnings(&ast).map_err(|errs| {
         let mut err = String::new();
         for e in errs {
             writeln!(&mut err, "{e}").unwrap();
         }
         err
     })?;
-
-    hypescript_lang::types::typecheck(&ast).map_err(|e| e.to_string())?;
+    for warning in warnings {
+        let (line, column) = line_column(&input, warning.span().start);
+        eprintln!("warning: {line}:{column}: {warning}");
+    }
 
     let instructions = hypescript_lang::codegen::translate(&ast).map_err(|e| e.to_string())?;
 
+    if options.stats {
+        println!(
+            "{} instructions, {} bytes",
+            instructions.len(),
+            hypescript_bytecode::Instruction::combined_len(&instructions)
+        );
+    }
+
+    if options.warn_fallthrough {
+        let bytes = hypescript_bytecode::instructions_to_vec(&instructions);
+        let ends_cleanly =
+            hypescript_bytecode::analysis::ends_cleanly(&bytes).map_err(|e| e.to_string())?;
+        if !ends_cleanly {
+            eprintln!("warning: program does not end with a halt, and may fall off the end");
+        }
+    }
+
     let mut output = File::create(options.output_file()).map_err(|e| e.to_string())?;
     hypescript_bytecode::write_instructions(&mut output, &instructions)
         .map_err(|e| e.to_string())?;
@@ -0,0 +1,27 @@
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn stats_line() {
+    let dir = std::env::temp_dir().join(format!("hypec-stats-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("stats.hyp");
+    let output = dir.join("stats.hyc");
+    fs::write(&input, "print 1 + 2;\n").unwrap();
+
+    let result = Command::new(env!("CARGO_BIN_EXE_hypec"))
+        .arg("--stats")
+        .arg(&input)
+        .arg(&output)
+        .output()
+        .unwrap();
+
+    assert!(result.status.success());
+    assert_eq!(
+        String::from_utf8(result.stdout).unwrap(),
+        "6 instructions, 9 bytes\n"
+    );
+    assert_eq!(fs::metadata(&output).unwrap().len(), 9);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,33 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn compile_stderr(dir: &Path, source: &str) -> String {
+    let input = dir.join("fallthrough.hyp");
+    let output = dir.join("fallthrough.hyc");
+    fs::write(&input, source).unwrap();
+
+    let result = Command::new(env!("CARGO_BIN_EXE_hypec"))
+        .arg("--warn-fallthrough")
+        .arg(&input)
+        .arg(&output)
+        .output()
+        .unwrap();
+
+    assert!(result.status.success());
+    String::from_utf8(result.stderr).unwrap()
+}
+
+#[test]
+fn warn_fallthrough() {
+    let dir = std::env::temp_dir().join(format!("hypec-fallthrough-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        compile_stderr(&dir, "print 1;\n"),
+        "warning: program does not end with a halt, and may fall off the end\n"
+    );
+    assert_eq!(compile_stderr(&dir, "print 1;\nhalt;\n"), "");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
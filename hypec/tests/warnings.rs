@@ -0,0 +1,24 @@
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn warning_locations() {
+    let dir = std::env::temp_dir().join(format!("hypec-warnings-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("warnings.hyp");
+    let output = dir.join("warnings.hyc");
+    fs::write(&input, "x = 1;\nif true {\n  print 2;\n}\n").unwrap();
+
+    let result = Command::new(env!("CARGO_BIN_EXE_hypec"))
+        .arg(&input)
+        .arg(&output)
+        .output()
+        .unwrap();
+
+    assert!(result.status.success());
+    let stderr = String::from_utf8(result.stderr).unwrap();
+    assert!(stderr.contains("warning: 1:1: Variable `x` is assigned but never read\n"));
+    assert!(stderr.contains("warning: 2:4: `if` condition is always true\n"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,545 @@
+//! Static analysis of bytecode programs.
+//!
+//! This module provides conservative checks that can detect some runtime errors before a program
+//! is executed.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::{DecodeError, Instruction, Opcode};
+
+/// The number of times the stack depth bound at a single instruction may grow before it is taken
+/// to be unbounded.
+///
+/// This guarantees that the analysis terminates on programs with loops that grow the stack.
+const WIDENING_THRESHOLD: usize = 4;
+
+/// Error returned by the analysis functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum AnalysisError {
+    /// The program could not be fully decoded.
+    #[error("{error} at offset {offset:#06x}")]
+    Decode { offset: usize, error: DecodeError },
+
+    /// An instruction is executed with too few values on the stack on every path that reaches it.
+    #[error(
+        "Stack underflow at offset {offset:#06x}: instruction requires {required} stack values, \
+        but at most {available} are available"
+    )]
+    StackUnderflow {
+        offset: usize,
+        required: usize,
+        available: usize,
+    },
+}
+
+/// The effect of an instruction on the depth of the operand stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackEffect {
+    /// The number of values that must be on the stack for the instruction to execute.
+    pub required: usize,
+
+    /// The number of values popped from the stack.
+    pub pops: usize,
+
+    /// The number of values pushed to the stack.
+    pub pushes: usize,
+}
+
+/// Get the effect of an instruction on the depth of the operand stack.
+pub fn stack_effect(instr: Instruction) -> StackEffect {
+    use Opcode::*;
+
+    let (required, pops, pushes) = match instr.opcode {
+        VarSt => (2, 2, 0),
+        VarLd => (1, 1, 1),
+        VarRes | VarDisc => (1, 1, 0),
+        NumVars => (0, 0, 1),
+        Push8 | Push8S | Push16 | Push16S | Push32 | Push32S | Push64 => (0, 0, 1),
+        Dup0 => (1, 0, 1),
+        Dup1 => (2, 0, 1),
+        Dup2 => (3, 0, 1),
+        Dup3 => (4, 0, 1),
+        DupN => (instr.literal as usize + 1, 0, 1),
+        Pop => (1, 1, 0),
+        Swap => (2, 2, 2),
+        Add | Sub | Mul | Mod | Div | DivS | Pow => (2, 2, 1),
+        AddCarry | SubBorrow => (2, 2, 2),
+        FAdd | FSub | FMul | FDiv | FGt | FLt | FEq => (2, 2, 1),
+        LAnd | LOr => (2, 2, 1),
+        Gt | GtS | Lt | LtS | Ge | GeS | Le | LeS | Eq | Ne => (2, 2, 1),
+        And | Or | Xor => (2, 2, 1),
+        BitTest | BitSet | BitClear => (2, 2, 1),
+        Not | Inv => (1, 1, 1),
+        Jump => (1, 1, 0),
+        JCond => (2, 2, 0),
+        Select | ModPow => (3, 3, 1),
+        PrintPeek | PrintPeekS => (1, 0, 0),
+        PrintStack => (0, 0, 0),
+        Read | ReadS | ReadBool => (0, 0, 1),
+        ReadN => (0, 0, instr.literal as usize),
+        Print | PrintS | PrintF | Assert => (1, 1, 0),
+        Halt => (0, 0, 0),
+    };
+
+    StackEffect {
+        required,
+        pops,
+        pushes,
+    }
+}
+
+/// Abstract machine state at an instruction boundary, summarizing every path that reaches it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct State {
+    /// The largest possible stack depth, or `None` if it is unbounded.
+    max_depth: Option<usize>,
+
+    /// The value on the top of the stack, if it is the same constant on every path.
+    top: Option<u64>,
+}
+
+impl State {
+    const UNKNOWN: Self = Self {
+        max_depth: None,
+        top: None,
+    };
+
+    fn join(self, other: Self) -> Self {
+        Self {
+            max_depth: self.max_depth.zip(other.max_depth).map(|(a, b)| a.max(b)),
+            top: if self.top == other.top {
+                self.top
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// Check a program for instructions that will underflow the operand stack whenever they are
+/// executed.
+///
+/// This computes an upper bound on the stack depth before each instruction, over all paths from
+/// the start of the program, and reports the first instruction (by program offset) whose bound is
+/// smaller than the number of stack values it requires. Since the bound is an upper bound, any
+/// reported instruction is guaranteed to underflow if it is ever executed; conversely, an `Ok`
+/// result does not guarantee that the program never underflows.
+///
+/// The analysis makes the following conservative assumptions:
+///
+/// - The target of a `jump` or `jcond` is only known if the offset on top of the stack is the same
+///   constant on every path reaching it (typically, because it is pushed by the immediately
+///   preceding instruction).
+/// - A jump whose target is unknown, or whose target is not the start of an instruction, may reach
+///   any instruction with any stack depth.
+/// - A jump whose target is outside the program halts it.
+/// - Conditional jumps may or may not be taken, regardless of their condition.
+/// - Any path may reach an instruction, even if it fails with another kind of runtime error along
+///   the way (e.g. a division by zero).
+///
+/// # Errors
+///
+/// Returns [`AnalysisError::Decode`] if the program cannot be fully decoded, and
+/// [`AnalysisError::StackUnderflow`] if an instruction is found that always underflows.
+pub fn check_stack_underflow(bytes: &[u8]) -> Result<(), AnalysisError> {
+    let instrs = decode_all(bytes)?;
+    let mut analysis = DepthAnalysis::default();
+
+    if !instrs.is_empty() {
+        let entry = State {
+            max_depth: Some(0),
+            top: None,
+        };
+        analysis.propagate(0, entry);
+    }
+
+    // Whether a jump with an unknown target has been reached
+    let mut flooded = false;
+
+    while let Some(offset) = analysis.worklist.pop_front() {
+        let instr = instrs[&offset];
+        let state = analysis.states[&offset];
+        let effect = stack_effect(instr);
+
+        let max_depth = match state.max_depth {
+            // This instruction always underflows, so execution never continues past it
+            Some(depth) if depth < effect.required => continue,
+            Some(depth) => Some(depth - effect.pops + effect.pushes),
+            None => None,
+        };
+
+        let top = instr.opcode.is_push().then_some(instr.literal);
+        let next_state = State { max_depth, top };
+        let next = offset + 1 + instr.opcode.literal_len();
+
+        let mut successors = Vec::with_capacity(2);
+        let mut unknown_target = false;
+        match instr.opcode {
+            Opcode::Halt => {}
+
+            Opcode::Jump | Opcode::JCond => {
+                if instr.opcode == Opcode::JCond {
+                    successors.push(next);
+                }
+
+                let target = state
+                    .top
+                    .and_then(|n| (offset + 1).checked_add_signed(n as i64 as isize));
+                match target {
+                    Some(target) if target >= bytes.len() => {}
+                    Some(target) if instrs.contains_key(&target) => successors.push(target),
+                    _ => unknown_target = true,
+                }
+            }
+
+            _ => successors.push(next),
+        }
+
+        // Falling off the end of the program halts it
+        for succ in successors.into_iter().filter(|&succ| succ < bytes.len()) {
+            analysis.propagate(succ, next_state);
+        }
+
+        if unknown_target && !flooded {
+            flooded = true;
+            for &offset in instrs.keys() {
+                analysis.propagate(offset, State::UNKNOWN);
+            }
+        }
+    }
+
+    for (&offset, state) in &analysis.states {
+        let required = stack_effect(instrs[&offset]).required;
+        if let Some(available) = state.max_depth.filter(|&depth| depth < required) {
+            return Err(AnalysisError::StackUnderflow {
+                offset,
+                required,
+                available,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Pair each `jump` or `jcond` instruction in a program with its resolved absolute target.
+///
+/// Each entry gives the program offset of a jump instruction, along with the program offset it
+/// jumps to if its relative offset is pushed by the immediately preceding instruction, or `None`
+/// otherwise. Entries are in program order.
+///
+/// # Errors
+///
+/// Returns [`DecodeError::UnrecognizedOpcode`] or [`DecodeError::IncompleteLiteral`] if the
+/// program cannot be fully decoded.
+pub fn jump_targets(bytes: &[u8]) -> Result<Vec<(usize, Option<usize>)>, DecodeError> {
+    let mut targets = Vec::new();
+    let mut offset = 0;
+    let mut prev: Option<Instruction> = None;
+
+    while offset < bytes.len() {
+        let (instr, next) = Instruction::decode_at(bytes, offset)?;
+        if matches!(instr.opcode, Opcode::Jump | Opcode::JCond) {
+            targets.push((offset, jump_target(offset, instr, prev)));
+        }
+
+        prev = Some(instr);
+        offset = next;
+    }
+
+    Ok(targets)
+}
+
+/// Check whether a program's last instruction is a `halt` or an unconditional `jump`.
+///
+/// A program that does not end cleanly may terminate by executing its last instruction and
+/// falling off the end of the program. This is legal, but often unintended. Note that this check
+/// only inspects the last instruction in a linear decoding of the program; it says nothing about
+/// whether the program halts on every path. An empty program does not end cleanly.
+///
+/// # Errors
+///
+/// Returns [`DecodeError::UnrecognizedOpcode`] or [`DecodeError::IncompleteLiteral`] if the
+/// program cannot be fully decoded.
+pub fn ends_cleanly(bytes: &[u8]) -> Result<bool, DecodeError> {
+    let mut last = None;
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let (instr, next) = Instruction::decode_at(bytes, offset)?;
+        last = Some(instr.opcode);
+        offset = next;
+    }
+
+    Ok(matches!(last, Some(Opcode::Halt | Opcode::Jump)))
+}
+
+/// Resolve the absolute target of a jump instruction at the given offset, if its relative offset
+/// is given by the immediately preceding instruction.
+pub(crate) fn jump_target(
+    offset: usize,
+    instr: Instruction,
+    prev: Option<Instruction>,
+) -> Option<usize> {
+    if !matches!(instr.opcode, Opcode::Jump | Opcode::JCond) {
+        return None;
+    }
+
+    let prev = prev?;
+    if !prev.opcode.is_push() {
+        return None;
+    }
+
+    // The program counter is incremented past the jump instruction after the jump is taken
+    (offset + 1).checked_add_signed(prev.literal as i64 as isize)
+}
+
+/// Working state of the stack depth analysis.
+#[derive(Debug, Default)]
+struct DepthAnalysis {
+    /// The abstract state before each instruction reached so far.
+    states: BTreeMap<usize, State>,
+
+    /// The number of times the depth bound before each instruction has grown.
+    widenings: HashMap<usize, usize>,
+
+    /// Instructions whose state has changed since they were last visited.
+    worklist: VecDeque<usize>,
+}
+
+impl DepthAnalysis {
+    /// Merge a new incoming state into the state before the instruction at the given offset.
+    fn propagate(&mut self, offset: usize, incoming: State) {
+        let new = match self.states.get(&offset) {
+            None => incoming,
+            Some(&old) => {
+                let mut new = old.join(incoming);
+                if old.max_depth.is_some() && new.max_depth > old.max_depth {
+                    let count = self.widenings.entry(offset).or_default();
+                    *count += 1;
+                    if *count > WIDENING_THRESHOLD {
+                        new.max_depth = None;
+                    }
+                }
+
+                if new == old {
+                    return;
+                }
+                new
+            }
+        };
+
+        self.states.insert(offset, new);
+        self.worklist.push_back(offset);
+    }
+}
+
+/// Decode every instruction in a program, keyed by program offset.
+fn decode_all(bytes: &[u8]) -> Result<BTreeMap<usize, Instruction>, AnalysisError> {
+    let mut instrs = BTreeMap::new();
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let (instr, next) = Instruction::decode_at(bytes, offset)
+            .map_err(|error| AnalysisError::Decode { offset, error })?;
+        instrs.insert(offset, instr);
+        offset = next;
+    }
+
+    Ok(instrs)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::consts::*;
+
+    use super::*;
+
+    fn underflow(offset: usize, required: usize, available: usize) -> Result<(), AnalysisError> {
+        Err(AnalysisError::StackUnderflow {
+            offset,
+            required,
+            available,
+        })
+    }
+
+    #[test]
+    fn straight_line() {
+        assert_eq!(check_stack_underflow(&[]), Ok(()));
+        assert_eq!(
+            check_stack_underflow(&[PUSH8, 1, PUSH8, 2, ADD, PRINT]),
+            Ok(())
+        );
+
+        assert_eq!(check_stack_underflow(&[ADD]), underflow(0, 2, 0));
+        assert_eq!(check_stack_underflow(&[PUSH8, 1, ADD]), underflow(2, 2, 1));
+        assert_eq!(
+            check_stack_underflow(&[PUSH8, 1, DUP0, DUPN, 1, DUP3]),
+            underflow(5, 4, 3)
+        );
+
+        // Only the first underflow is reported
+        assert_eq!(
+            check_stack_underflow(&[READ, PUSH8, 1, JCOND, POP, POP]),
+            underflow(4, 1, 0)
+        );
+
+        // Instructions after a halt are never executed
+        assert_eq!(check_stack_underflow(&[HALT, POP]), Ok(()));
+    }
+
+    #[test]
+    fn branches() {
+        // One path pushes a value, and the other doesn't; the pop may succeed
+        assert_eq!(
+            check_stack_underflow(&[READ, PUSH8, 2, JCOND, PUSH8, 5, POP]),
+            Ok(())
+        );
+
+        // Only one path pushes a value, but there are two pops
+        assert_eq!(
+            check_stack_underflow(&[READ, PUSH8, 1, JCOND, NUMVARS, POP, POP]),
+            underflow(6, 1, 0)
+        );
+
+        // Jumping over the only push
+        assert_eq!(
+            check_stack_underflow(&[PUSH8, 2, JUMP, PUSH8, 5, POP]),
+            underflow(5, 1, 0)
+        );
+
+        // Jumping out of the program halts it
+        assert_eq!(check_stack_underflow(&[PUSH8, 10, JUMP, POP]), Ok(()));
+    }
+
+    #[rustfmt::skip]
+    const COUNTER: &[u8] = &[
+        PUSH8, 0,
+        PUSH8, 5,
+        JUMP,
+
+        PUSH8, 1,
+        ADD,
+        DUP0,
+        PRINT,
+
+        DUP0,
+        PUSH8, 10,
+        LT,
+        PUSH8S, (-12i8) as u8,
+        JCOND,
+
+        POP,
+    ];
+
+    #[test]
+    fn loops() {
+        assert_eq!(check_stack_underflow(COUNTER), Ok(()));
+
+        // The loop grows the stack on each iteration, so its depth is unbounded
+        assert_eq!(
+            check_stack_underflow(&[READ, READ, PUSH8S, (-5i8) as u8, JCOND, POP, POP]),
+            Ok(())
+        );
+
+        // The loop underflows on its second iteration, but the pop succeeds on the path from the
+        // entry point, so this can't be reported
+        assert_eq!(
+            check_stack_underflow(&[PUSH8, 1, POP, PUSH8S, (-4i8) as u8, JUMP]),
+            Ok(())
+        );
+
+        // Every path into the loop body underflows
+        #[rustfmt::skip]
+        let program = [
+            PUSH8, 1,
+            JUMP,
+
+            PRINT,
+            READ,
+            PUSH8S, (-5i8) as u8,
+            JCOND,
+
+            POP,
+        ];
+        assert_eq!(check_stack_underflow(&program), underflow(3, 1, 0));
+    }
+
+    #[test]
+    fn unknown_jumps() {
+        // The jump offset is computed, so the pop may be reachable with any stack depth
+        assert_eq!(
+            check_stack_underflow(&[PUSH8, 2, PUSH8, 1, ADD, JUMP, POP]),
+            Ok(())
+        );
+
+        // Jumping into the middle of an instruction is treated as an unknown jump
+        assert_eq!(
+            check_stack_underflow(&[PUSH8, 1, JUMP, PUSH8, POP, POP]),
+            Ok(())
+        );
+
+        // An unknown jump that is never reached doesn't affect the analysis
+        assert_eq!(
+            check_stack_underflow(&[POP, PUSH8, 2, PUSH8, 1, ADD, JUMP]),
+            underflow(0, 1, 0)
+        );
+    }
+
+    #[test]
+    fn decode_error() {
+        assert_eq!(
+            check_stack_underflow(&[PUSH8, 1, 0x20]),
+            Err(AnalysisError::Decode {
+                offset: 2,
+                error: DecodeError::UnrecognizedOpcode
+            })
+        );
+    }
+
+    #[test]
+    fn jump_targets_counter() {
+        assert_eq!(
+            jump_targets(COUNTER),
+            Ok(vec![(4, Some(10)), (16, Some(5))])
+        );
+
+        // Offsets not pushed by the immediately preceding instruction are unresolved
+        assert_eq!(
+            jump_targets(&[READ, JUMP, PUSH8, 1, DUP0, JCOND]),
+            Ok(vec![(1, None), (5, None)])
+        );
+
+        assert_eq!(
+            jump_targets(&[PUSH8, 2, PUSH16, 1]),
+            Err(DecodeError::IncompleteLiteral)
+        );
+    }
+
+    #[test]
+    fn ends_cleanly_halt() {
+        assert_eq!(
+            ends_cleanly(&[PUSH8, 1, PUSH8, 2, ADD, PRINT, HALT]),
+            Ok(true)
+        );
+        assert_eq!(
+            ends_cleanly(&[PUSH8, 1, PRINT, PUSH8S, (-5i8) as u8, JUMP]),
+            Ok(true)
+        );
+
+        // Falls off the end after the add
+        assert_eq!(ends_cleanly(&[PUSH8, 1, PUSH8, 2, ADD]), Ok(false));
+
+        // A conditional jump may fall through
+        assert_eq!(
+            ends_cleanly(&[READ, PUSH8S, (-3i8) as u8, JCOND]),
+            Ok(false)
+        );
+        assert_eq!(ends_cleanly(&[]), Ok(false));
+
+        assert_eq!(
+            ends_cleanly(&[HALT, PUSH16, 1]),
+            Err(DecodeError::IncompleteLiteral)
+        );
+    }
+}
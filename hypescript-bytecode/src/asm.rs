@@ -0,0 +1,478 @@
+//! Assembly of bytecode programs
+//!
+//! This module provides [`assemble`], which assembles a complete program from source text, as
+//! found in `.hys` files.
+//!
+//! With the `macros` feature enabled, it also provides the `hyasm!` macro, which builds a list of
+//! instructions from assembly-style mnemonics. It is primarily intended for writing test programs
+//! without hand-encoding opcode bytes and literals.
+//!
+//! # Assembly source format
+//!
+//! Each line of an assembly source contains at most one instruction, written as its mnemonic
+//! followed by its literal (if any). A literal may be written in decimal or in hexadecimal with a
+//! `0x` prefix, and may be negated with a leading `-`, which gives its two's complement form.
+//! Everything following `//` on a line is a comment, and blank lines are ignored.
+//!
+//! A line may begin with a label definition, an identifier followed by a colon, which names the
+//! program offset of the following instruction. A literal of the form `@label` is replaced with
+//! the relative offset from the end of the instruction *following* the one it appears in to the
+//! label; i.e., it is the offset to push immediately before a `jump` or `jcond` that should jump
+//! to the label.
+//!
+//! ```text
+//! // Print 3, 2, 1
+//!     push8 3
+//! loop:
+//!     dup0
+//!     print
+//!     push8 1
+//!     sub
+//!     dup0
+//!     push8s @loop
+//!     jcond
+//! ```
+
+use std::collections::HashMap;
+
+use crate::{Instruction, InstructionError, Opcode};
+
+/// Assemble a list of [`Instruction`]s from mnemonics.
+///
+/// Each instruction is written as its mnemonic, optionally followed by a literal, and terminated
+/// by a semicolon. Literals may be any integer expression; a negative integer literal is
+/// converted to its two's complement form, for use with the sign-extending push instructions.
+///
+/// This macro is only available with the `macros` feature enabled.
+///
+/// ```
+/// # use hypescript_bytecode::{hyasm, Instruction, Opcode};
+/// let instrs = hyasm! {
+///     push8 3;
+///     dup0;
+///     push8s -1;
+///     add;
+///     print;
+/// };
+///
+/// assert_eq!(instrs[2], Instruction::new(Opcode::Push8S, -1i64 as u64));
+/// ```
+///
+/// # Panics
+///
+/// The resulting expression panics if a mnemonic is unrecognized, or if a literal is missing,
+/// unexpected, or out of range for its instruction (as checked by [`Instruction::try_new`]).
+#[cfg(feature = "macros")]
+#[macro_export]
+macro_rules! hyasm {
+    (@instrs [$($out:expr,)*]) => {
+        ::std::vec![$($out,)*]
+    };
+
+    (@instrs [$($out:expr,)*] $mnem:tt; $($rest:tt)*) => {
+        $crate::hyasm!(@instrs [
+            $($out,)*
+            $crate::asm::assemble_instruction(stringify!($mnem), None),
+        ] $($rest)*)
+    };
+
+    (@instrs [$($out:expr,)*] $mnem:tt - $lit:literal; $($rest:tt)*) => {
+        $crate::hyasm!(@instrs [
+            $($out,)*
+            $crate::asm::assemble_instruction(stringify!($mnem), Some((-($lit as i64)) as u64)),
+        ] $($rest)*)
+    };
+
+    (@instrs [$($out:expr,)*] $mnem:tt $lit:expr; $($rest:tt)*) => {
+        $crate::hyasm!(@instrs [
+            $($out,)*
+            $crate::asm::assemble_instruction(stringify!($mnem), Some(($lit) as u64)),
+        ] $($rest)*)
+    };
+
+    ($($body:tt)*) => {
+        $crate::hyasm!(@instrs [] $($body)*)
+    };
+}
+
+/// Assemble a single instruction from its mnemonic and literal.
+///
+/// This is an implementation detail of [`hyasm!`](crate::hyasm).
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub fn assemble_instruction(mnemonic: &str, literal: Option<u64>) -> Instruction {
+    let opcode = Opcode::from_mnemonic(mnemonic)
+        .unwrap_or_else(|| panic!("unrecognized mnemonic `{mnemonic}`"));
+
+    if opcode.literal_len() > 0 && literal.is_none() {
+        panic!("missing literal for `{mnemonic}`");
+    }
+
+    Instruction::try_new(opcode, literal.unwrap_or(0))
+        .unwrap_or_else(|err| panic!("invalid literal for `{mnemonic}`: {err}"))
+}
+
+/// Error returned by [`assemble`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("line {line}: {kind}")]
+pub struct AssemblyError {
+    /// The line of the source on which the error occurred, counting from 1.
+    pub line: usize,
+
+    /// The kind of error.
+    pub kind: AssemblyErrorKind,
+}
+
+/// Categories of assembly error.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AssemblyErrorKind {
+    #[error("Unrecognized mnemonic `{0}`")]
+    UnrecognizedMnemonic(String),
+
+    #[error("Missing literal")]
+    MissingLiteral,
+
+    #[error("Malformed literal `{0}`")]
+    MalformedLiteral(String),
+
+    #[error("Unexpected `{0}` after instruction")]
+    TrailingInput(String),
+
+    #[error("Invalid label name `{0}`")]
+    InvalidLabel(String),
+
+    #[error("Label `{0}` is defined more than once")]
+    DuplicateLabel(String),
+
+    #[error("Undefined label `{0}`")]
+    UndefinedLabel(String),
+
+    #[error(transparent)]
+    Instruction(#[from] InstructionError),
+}
+
+/// A literal as written in assembly source.
+enum Literal<'a> {
+    Value(u64),
+    Label(&'a str),
+}
+
+/// An instruction parsed from assembly source, whose literal may not yet be resolved.
+struct SourceInstruction<'a> {
+    line: usize,
+    offset: usize,
+    opcode: Opcode,
+    literal: Option<Literal<'a>>,
+}
+
+/// Assemble a program from source text.
+///
+/// See the [module documentation](self) for the source format. The program is laid out
+/// contiguously from program offset 0, as by [`write_instructions`](crate::write_instructions).
+///
+/// # Errors
+///
+/// Returns an [`AssemblyError`] for the first line found to be invalid. Literals are checked as
+/// for [`Instruction::try_new`], including those given by label references.
+pub fn assemble(source: &str) -> Result<Vec<Instruction>, AssemblyError> {
+    let mut labels = HashMap::new();
+    let mut instrs = Vec::new();
+    let mut offset = 0;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let error = |kind| AssemblyError { line, kind };
+
+        let mut text = text.split_once("//").map_or(text, |(code, _)| code).trim();
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(error(AssemblyErrorKind::InvalidLabel(label.into())));
+            }
+            if labels.insert(label, offset).is_some() {
+                return Err(error(AssemblyErrorKind::DuplicateLabel(label.into())));
+            }
+            text = rest.trim();
+        }
+
+        let mut words = text.split_whitespace();
+        let Some(mnemonic) = words.next() else {
+            continue;
+        };
+
+        let opcode = Opcode::from_mnemonic(mnemonic)
+            .ok_or_else(|| error(AssemblyErrorKind::UnrecognizedMnemonic(mnemonic.into())))?;
+        let literal = words
+            .next()
+            .map(|word| {
+                parse_literal(word)
+                    .ok_or_else(|| error(AssemblyErrorKind::MalformedLiteral(word.into())))
+            })
+            .transpose()?;
+        if let Some(word) = words.next() {
+            return Err(error(AssemblyErrorKind::TrailingInput(word.into())));
+        }
+        if opcode.literal_len() > 0 && literal.is_none() {
+            return Err(error(AssemblyErrorKind::MissingLiteral));
+        }
+
+        instrs.push(SourceInstruction {
+            line,
+            offset,
+            opcode,
+            literal,
+        });
+        offset += 1 + opcode.literal_len();
+    }
+
+    instrs
+        .into_iter()
+        .map(|instr| {
+            let error = |kind| AssemblyError {
+                line: instr.line,
+                kind,
+            };
+
+            let literal = match instr.literal {
+                None => 0,
+                Some(Literal::Value(value)) => value,
+                Some(Literal::Label(label)) => {
+                    let target = *labels
+                        .get(label)
+                        .ok_or_else(|| error(AssemblyErrorKind::UndefinedLabel(label.into())))?;
+
+                    // The program counter is incremented past the jump instruction after the jump
+                    // is taken
+                    let jump_end = instr.offset + 1 + instr.opcode.literal_len() + 1;
+                    (target as u64).wrapping_sub(jump_end as u64)
+                }
+            };
+
+            Instruction::try_new(instr.opcode, literal).map_err(|err| error(err.into()))
+        })
+        .collect()
+}
+
+/// Check whether a string is a valid label name.
+fn is_label(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parse a literal in assembly source.
+fn parse_literal(word: &str) -> Option<Literal<'_>> {
+    if let Some(label) = word.strip_prefix('@') {
+        return is_label(label).then_some(Literal::Label(label));
+    }
+
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, word),
+    };
+
+    let magnitude = match digits.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+
+    if negative {
+        (magnitude <= 1 << 63).then(|| Literal::Value(magnitude.wrapping_neg()))
+    } else {
+        Some(Literal::Value(magnitude))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::consts::*;
+    use crate::instructions_to_vec;
+
+    use super::*;
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn counter() {
+        let instrs = hyasm! {
+            push8 0;
+            push8 5;
+            jump;
+
+            push8 1;
+            add;
+            dup0;
+            print;
+
+            dup0;
+            push8 10;
+            lt;
+            push8s -12;
+            jcond;
+
+            pop;
+        };
+
+        #[rustfmt::skip]
+        assert_eq!(
+            instructions_to_vec(&instrs),
+            [
+                PUSH8, 0,
+                PUSH8, 5,
+                JUMP,
+
+                PUSH8, 1,
+                ADD,
+                DUP0,
+                PRINT,
+
+                DUP0,
+                PUSH8, 10,
+                LT,
+                PUSH8S, (-12i8) as u8,
+                JCOND,
+
+                POP,
+            ]
+        );
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn literal_expressions() {
+        let instrs = hyasm! {
+            push64 0xffff_ffff_ffff_ffff;
+            push16 2 * 300;
+            mod;
+        };
+        assert_eq!(
+            instructions_to_vec(&instrs),
+            [&[PUSH64][..], &[0xff; 8], &[PUSH16, 0x02, 0x58], &[MOD]].concat()
+        );
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    #[should_panic(expected = "unrecognized mnemonic `frob`")]
+    fn unrecognized_mnemonic() {
+        hyasm! { frob; };
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    #[should_panic(expected = "missing literal for `push8`")]
+    fn missing_literal() {
+        hyasm! { push8; };
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    #[should_panic(expected = "invalid literal for `push8`")]
+    fn literal_out_of_range() {
+        hyasm! { push8 256; };
+    }
+
+    #[test]
+    fn assemble_source() {
+        let source = "
+            // The counter program, with a label for each jump target
+                push8 0
+                push8 @check   // skip the body on entry
+                jump
+
+            body:
+                push8 1
+                add
+                dup0
+                print
+
+            check: dup0
+                push8 10
+                lt
+                push8s @body
+                jcond
+
+                pop
+        ";
+
+        let instrs = assemble(source).unwrap();
+
+        #[rustfmt::skip]
+        assert_eq!(
+            instructions_to_vec(&instrs),
+            [
+                PUSH8, 0,
+                PUSH8, 5,
+                JUMP,
+
+                PUSH8, 1,
+                ADD,
+                DUP0,
+                PRINT,
+
+                DUP0,
+                PUSH8, 10,
+                LT,
+                PUSH8S, (-12i8) as u8,
+                JCOND,
+
+                POP,
+            ]
+        );
+    }
+
+    #[test]
+    fn assemble_literals() {
+        let instrs =
+            assemble("push8 0x7f\npush16s -0x10\npush64 18446744073709551615\npush8s -128")
+                .unwrap();
+        assert_eq!(
+            instrs,
+            [
+                Instruction::new(Opcode::Push8, 0x7f),
+                Instruction::new(Opcode::Push16S, -16i64 as u64),
+                Instruction::new(Opcode::Push64, u64::MAX),
+                Instruction::new(Opcode::Push8S, -128i64 as u64),
+            ]
+        );
+
+        // A label at the end of the program
+        let instrs = assemble("push8 @end\njump\nhalt\nend:").unwrap();
+        assert_eq!(instrs[0], Instruction::new(Opcode::Push8, 1));
+    }
+
+    #[test]
+    fn assemble_errors() {
+        let err = |line, kind| Err(AssemblyError { line, kind });
+        use AssemblyErrorKind::*;
+
+        assert_eq!(
+            assemble("push8 1\n\nfrob"),
+            err(3, UnrecognizedMnemonic("frob".into()))
+        );
+        assert_eq!(assemble("push8"), err(1, MissingLiteral));
+        assert_eq!(assemble("push8 1z"), err(1, MalformedLiteral("1z".into())));
+        assert_eq!(assemble("push8 1 2"), err(1, TrailingInput("2".into())));
+        assert_eq!(
+            assemble("push8 256"),
+            err(1, Instruction(InstructionError::LiteralOutOfRange))
+        );
+        assert_eq!(
+            assemble("add 1"),
+            err(1, Instruction(InstructionError::UnexpectedLiteral))
+        );
+        assert_eq!(
+            assemble("push8 @nowhere\njump"),
+            err(1, UndefinedLabel("nowhere".into()))
+        );
+        assert_eq!(
+            assemble("a:\nadd\na: add"),
+            err(3, DuplicateLabel("a".into()))
+        );
+        assert_eq!(assemble("1a: add"), err(1, InvalidLabel("1a".into())));
+
+        // A backward label reference does not fit in an unsigned push
+        assert_eq!(
+            assemble("top:\npush8 @top\njump"),
+            err(2, Instruction(InstructionError::LiteralOutOfRange))
+        );
+    }
+}
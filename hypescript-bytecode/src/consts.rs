@@ -20,12 +20,26 @@ pub const DUP2: u8 = 0x32;
 pub const DUP3: u8 = 0x33;
 pub const POP: u8 = 0x34;
 pub const SWAP: u8 = 0x35;
+pub const DUPN: u8 = 0x36;
 pub const ADD: u8 = 0x38;
 pub const SUB: u8 = 0x39;
 pub const MUL: u8 = 0x3a;
 pub const MOD: u8 = 0x3b;
 pub const DIV: u8 = 0x3c;
 pub const DIVS: u8 = 0x3d;
+pub const POW: u8 = 0x3e;
+pub const MODPOW: u8 = 0x3f;
+pub const FADD: u8 = 0x40;
+pub const FSUB: u8 = 0x41;
+pub const FMUL: u8 = 0x42;
+pub const FDIV: u8 = 0x43;
+pub const FGT: u8 = 0x44;
+pub const FLT: u8 = 0x45;
+pub const FEQ: u8 = 0x46;
+pub const LAND: u8 = 0x48;
+pub const LOR: u8 = 0x49;
+pub const ADDC: u8 = 0x4c;
+pub const SUBB: u8 = 0x4d;
 pub const GT: u8 = 0x50;
 pub const GTS: u8 = 0x51;
 pub const LT: u8 = 0x52;
@@ -40,10 +54,22 @@ pub const OR: u8 = 0x5a;
 pub const XOR: u8 = 0x5b;
 pub const NOT: u8 = 0x5c;
 pub const INV: u8 = 0x5d;
+pub const NE: u8 = 0x5e;
 pub const JUMP: u8 = 0x60;
 pub const JCOND: u8 = 0x61;
+pub const SELECT: u8 = 0x62;
+pub const BITTEST: u8 = 0x68;
+pub const BITSET: u8 = 0x6a;
+pub const BITCLEAR: u8 = 0x6c;
+pub const PRINTF: u8 = 0xf0;
+pub const READN: u8 = 0xf2;
+pub const READBOOL: u8 = 0xf4;
+pub const PRINTSTACK: u8 = 0xf6;
+pub const PRINTPEEK: u8 = 0xf8;
+pub const PRINTPEEKS: u8 = 0xf9;
 pub const READ: u8 = 0xfa;
 pub const READS: u8 = 0xfb;
 pub const PRINT: u8 = 0xfc;
 pub const PRINTS: u8 = 0xfd;
+pub const ASSERT: u8 = 0xfe;
 pub const HALT: u8 = 0xff;
@@ -0,0 +1,218 @@
+//! Bytecode disassembly
+//!
+//! This module provides functions for decoding HypeScript bytecode into structured instruction
+//! lists, and for rendering those lists as human-readable assembly listings.
+
+use std::fmt::{self, Display, Formatter, Write};
+
+use crate::analysis::jump_target;
+use crate::{DecodeError, Instruction};
+
+/// Error returned by the disassembly functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("{error} at offset {offset:#06x}")]
+pub struct DisassemblyError {
+    /// The program offset at which decoding failed.
+    pub offset: usize,
+
+    /// The decoding error.
+    pub error: DecodeError,
+}
+
+/// A single disassembled instruction.
+///
+/// The `Display` implementation renders the line as it appears in a plain listing, e.g.
+/// `0x0004  jump`. The alternate form (`{:#}`) additionally annotates resolved jump targets, as in
+/// an annotated listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisassemblyLine {
+    /// The program offset of the instruction.
+    pub offset: usize,
+
+    /// The decoded instruction.
+    pub instruction: Instruction,
+
+    /// The absolute program offset targeted by a `jump` or `jcond` instruction, if its relative
+    /// offset is given by an immediately preceding push. Always `None` for other instructions.
+    pub target: Option<usize>,
+}
+
+impl Display for DisassemblyLine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let offset = self.offset;
+        match self.target {
+            Some(target) if f.alternate() => write!(
+                f,
+                "{offset:#06x}  {:<24}; -> {target:#06x}",
+                self.instruction.to_string()
+            ),
+            _ => write!(f, "{offset:#06x}  {}", self.instruction),
+        }
+    }
+}
+
+/// Disassemble a bytecode program into a list of instructions with their offsets.
+///
+/// Jump targets are resolved as described for [`DisassemblyLine::target`]. See [`listing`] and
+/// [`annotated_listing`] for rendering the result as text.
+pub fn disassemble(bytes: &[u8]) -> Result<Vec<DisassemblyLine>, DisassemblyError> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    let mut prev: Option<Instruction> = None;
+
+    while offset < bytes.len() {
+        let (instruction, next) = Instruction::decode_at(bytes, offset)
+            .map_err(|error| DisassemblyError { offset, error })?;
+
+        lines.push(DisassemblyLine {
+            offset,
+            instruction,
+            target: jump_target(offset, instruction, prev),
+        });
+
+        prev = Some(instruction);
+        offset = next;
+    }
+
+    Ok(lines)
+}
+
+/// Disassemble a bytecode program into a listing, annotating resolved jump targets.
+///
+/// This is equivalent to calling [`annotated_listing`] on the result of [`disassemble`].
+pub fn disassemble_annotated(bytes: &[u8]) -> Result<String, DisassemblyError> {
+    disassemble(bytes).map(|lines| annotated_listing(&lines))
+}
+
+/// Render disassembled instructions as a listing, one instruction per line.
+///
+/// Each line contains the program offset of the instruction, followed by the instruction
+/// mnemonic and literal (if any).
+pub fn listing(lines: &[DisassemblyLine]) -> String {
+    let mut listing = String::new();
+    for line in lines {
+        writeln!(listing, "{line}").unwrap();
+    }
+    listing
+}
+
+/// Render disassembled instructions as a listing, annotating resolved jump targets.
+///
+/// This is identical to [`listing`], except that each `jump` or `jcond` instruction with a
+/// resolved target is annotated with a comment giving the absolute program offset of its target,
+/// e.g. `jcond ; -> 0x002a`. Jumps whose offsets are not given by an immediately preceding push
+/// are left unannotated.
+pub fn annotated_listing(lines: &[DisassemblyLine]) -> String {
+    let mut listing = String::new();
+    for line in lines {
+        writeln!(listing, "{line:#}").unwrap();
+    }
+    listing
+}
+
+#[cfg(test)]
+mod test {
+    use crate::consts::*;
+    use crate::Opcode;
+
+    use super::*;
+
+    #[rustfmt::skip]
+    const COUNTER: &[u8] = &[
+        PUSH8, 0,
+        PUSH8, 5,
+        JUMP,
+
+        PUSH8, 1,
+        ADD,
+        DUP0,
+        PRINT,
+
+        DUP0,
+        PUSH8, 10,
+        LT,
+        PUSH8S, (-12i8) as u8,
+        JCOND,
+
+        POP,
+    ];
+
+    #[test]
+    fn structured() {
+        let lines = disassemble(COUNTER).unwrap();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(
+            lines[..3],
+            [
+                DisassemblyLine {
+                    offset: 0,
+                    instruction: Instruction::new(Opcode::Push8, 0),
+                    target: None,
+                },
+                DisassemblyLine {
+                    offset: 2,
+                    instruction: Instruction::new(Opcode::Push8, 5),
+                    target: None,
+                },
+                DisassemblyLine {
+                    offset: 4,
+                    instruction: Instruction::from(Opcode::Jump),
+                    target: Some(0x0a),
+                },
+            ]
+        );
+        assert_eq!(
+            lines[11],
+            DisassemblyLine {
+                offset: 0x10,
+                instruction: Instruction::from(Opcode::JCond),
+                target: Some(0x05),
+            }
+        );
+        assert!(lines
+            .iter()
+            .filter(|line| line.instruction.opcode != Opcode::Jump
+                && line.instruction.opcode != Opcode::JCond)
+            .all(|line| line.target.is_none()));
+    }
+
+    #[test]
+    fn plain() {
+        let listing = listing(&disassemble(&COUNTER[..7]).unwrap());
+        assert_eq!(
+            listing,
+            "0x0000  push8 0x00\n0x0002  push8 0x05\n0x0004  jump\n0x0005  push8 0x01\n"
+        );
+    }
+
+    #[test]
+    fn annotated_jumps() {
+        let listing = disassemble_annotated(COUNTER).unwrap();
+        let lines: Vec<_> = listing.lines().collect();
+
+        assert_eq!(lines.len(), 13);
+        assert_eq!(lines[2], "0x0004  jump                    ; -> 0x000a");
+        assert_eq!(lines[10], "0x000e  push8s -12");
+        assert_eq!(lines[11], "0x0010  jcond                   ; -> 0x0005");
+        assert_eq!(lines[12], "0x0011  pop");
+    }
+
+    #[test]
+    fn unresolved_jump() {
+        // Offset comes from a computation rather than a constant
+        let listing = disassemble_annotated(&[PUSH8, 2, PUSH8, 1, ADD, JUMP]).unwrap();
+        assert_eq!(listing.lines().last(), Some("0x0005  jump"));
+    }
+
+    #[test]
+    fn decode_error() {
+        let err = disassemble_annotated(&[PUSH8, 2, PUSH16, 1]).unwrap_err();
+        assert_eq!(
+            err,
+            DisassemblyError {
+                offset: 2,
+                error: DecodeError::IncompleteLiteral
+            }
+        );
+    }
+}
@@ -2,9 +2,14 @@
 //!
 //! This crate provides types and functions for working with HypeScript bytecode. This includes
 //! writing and parsing bytecode, and querying information about opcodes, but not execution; see
-//! the `nilscript-vm` crate for an execution engine.
+//! the `hypescript-vm` crate for an execution engine.
 
+pub mod analysis;
+pub mod asm;
 pub mod consts;
+pub mod disasm;
+pub mod program;
+pub mod slot_types;
 
 use consts::*;
 use hypescript_util::array_from_slice;
@@ -39,12 +44,26 @@ pub enum Opcode {
     Dup3 = DUP3,
     Pop = POP,
     Swap = SWAP,
+    DupN = DUPN,
     Add = ADD,
     Sub = SUB,
     Mul = MUL,
     Mod = MOD,
     Div = DIV,
     DivS = DIVS,
+    Pow = POW,
+    ModPow = MODPOW,
+    FAdd = FADD,
+    FSub = FSUB,
+    FMul = FMUL,
+    FDiv = FDIV,
+    FGt = FGT,
+    FLt = FLT,
+    FEq = FEQ,
+    LAnd = LAND,
+    LOr = LOR,
+    AddCarry = ADDC,
+    SubBorrow = SUBB,
     Gt = GT,
     GtS = GTS,
     Lt = LT,
@@ -59,16 +78,105 @@ pub enum Opcode {
     Xor = XOR,
     Not = NOT,
     Inv = INV,
+    Ne = NE,
     Jump = JUMP,
     JCond = JCOND,
+    Select = SELECT,
+    BitTest = BITTEST,
+    BitSet = BITSET,
+    BitClear = BITCLEAR,
+    PrintF = PRINTF,
+    ReadN = READN,
+    ReadBool = READBOOL,
+    PrintStack = PRINTSTACK,
+    PrintPeek = PRINTPEEK,
+    PrintPeekS = PRINTPEEKS,
     Read = READ,
     ReadS = READS,
     Print = PRINT,
     PrintS = PRINTS,
+    Assert = ASSERT,
     Halt = HALT,
 }
 
 impl Opcode {
+    /// Get a list of all opcodes, in numeric order.
+    pub fn all() -> &'static [Self] {
+        &[
+            Self::VarSt,
+            Self::VarLd,
+            Self::VarRes,
+            Self::VarDisc,
+            Self::NumVars,
+            Self::Push8,
+            Self::Push8S,
+            Self::Push16,
+            Self::Push16S,
+            Self::Push32,
+            Self::Push32S,
+            Self::Push64,
+            Self::Dup0,
+            Self::Dup1,
+            Self::Dup2,
+            Self::Dup3,
+            Self::Pop,
+            Self::Swap,
+            Self::DupN,
+            Self::Add,
+            Self::Sub,
+            Self::Mul,
+            Self::Mod,
+            Self::Div,
+            Self::DivS,
+            Self::Pow,
+            Self::ModPow,
+            Self::FAdd,
+            Self::FSub,
+            Self::FMul,
+            Self::FDiv,
+            Self::FGt,
+            Self::FLt,
+            Self::FEq,
+            Self::LAnd,
+            Self::LOr,
+            Self::AddCarry,
+            Self::SubBorrow,
+            Self::Gt,
+            Self::GtS,
+            Self::Lt,
+            Self::LtS,
+            Self::Ge,
+            Self::GeS,
+            Self::Le,
+            Self::LeS,
+            Self::Eq,
+            Self::And,
+            Self::Or,
+            Self::Xor,
+            Self::Not,
+            Self::Inv,
+            Self::Ne,
+            Self::Jump,
+            Self::JCond,
+            Self::Select,
+            Self::BitTest,
+            Self::BitSet,
+            Self::BitClear,
+            Self::PrintF,
+            Self::ReadN,
+            Self::ReadBool,
+            Self::PrintStack,
+            Self::PrintPeek,
+            Self::PrintPeekS,
+            Self::Read,
+            Self::ReadS,
+            Self::Print,
+            Self::PrintS,
+            Self::Assert,
+            Self::Halt,
+        ]
+    }
+
     /// Convert an opcode encoded as a `u8` into an `Opcode`.
     ///
     /// Returns `None` if the given byte is not recognized as an opcode.
@@ -92,12 +200,26 @@ impl Opcode {
             DUP3 => Some(Self::Dup3),
             POP => Some(Self::Pop),
             SWAP => Some(Self::Swap),
+            DUPN => Some(Self::DupN),
             ADD => Some(Self::Add),
             SUB => Some(Self::Sub),
             MUL => Some(Self::Mul),
             MOD => Some(Self::Mod),
             DIV => Some(Self::Div),
             DIVS => Some(Self::DivS),
+            POW => Some(Self::Pow),
+            MODPOW => Some(Self::ModPow),
+            FADD => Some(Self::FAdd),
+            FSUB => Some(Self::FSub),
+            FMUL => Some(Self::FMul),
+            FDIV => Some(Self::FDiv),
+            FGT => Some(Self::FGt),
+            FLT => Some(Self::FLt),
+            FEQ => Some(Self::FEq),
+            LAND => Some(Self::LAnd),
+            LOR => Some(Self::LOr),
+            ADDC => Some(Self::AddCarry),
+            SUBB => Some(Self::SubBorrow),
             GT => Some(Self::Gt),
             GTS => Some(Self::GtS),
             LT => Some(Self::Lt),
@@ -112,12 +234,24 @@ impl Opcode {
             XOR => Some(Self::Xor),
             NOT => Some(Self::Not),
             INV => Some(Self::Inv),
+            NE => Some(Self::Ne),
             JUMP => Some(Self::Jump),
             JCOND => Some(Self::JCond),
+            SELECT => Some(Self::Select),
+            BITTEST => Some(Self::BitTest),
+            BITSET => Some(Self::BitSet),
+            BITCLEAR => Some(Self::BitClear),
+            PRINTF => Some(Self::PrintF),
+            READN => Some(Self::ReadN),
+            READBOOL => Some(Self::ReadBool),
+            PRINTSTACK => Some(Self::PrintStack),
+            PRINTPEEK => Some(Self::PrintPeek),
+            PRINTPEEKS => Some(Self::PrintPeekS),
             READ => Some(Self::Read),
             READS => Some(Self::ReadS),
             PRINT => Some(Self::Print),
             PRINTS => Some(Self::PrintS),
+            ASSERT => Some(Self::Assert),
             HALT => Some(Self::Halt),
             _ => None,
         }
@@ -149,12 +283,26 @@ impl Opcode {
             "dup3" => Some(Self::Dup3),
             "pop" => Some(Self::Pop),
             "swap" => Some(Self::Swap),
+            "dupn" => Some(Self::DupN),
             "add" => Some(Self::Add),
             "sub" => Some(Self::Sub),
             "mul" => Some(Self::Mul),
             "mod" => Some(Self::Mod),
             "div" => Some(Self::Div),
             "divs" => Some(Self::DivS),
+            "pow" => Some(Self::Pow),
+            "modpow" => Some(Self::ModPow),
+            "fadd" => Some(Self::FAdd),
+            "fsub" => Some(Self::FSub),
+            "fmul" => Some(Self::FMul),
+            "fdiv" => Some(Self::FDiv),
+            "fgt" => Some(Self::FGt),
+            "flt" => Some(Self::FLt),
+            "feq" => Some(Self::FEq),
+            "land" => Some(Self::LAnd),
+            "lor" => Some(Self::LOr),
+            "addc" => Some(Self::AddCarry),
+            "subb" => Some(Self::SubBorrow),
             "gt" => Some(Self::Gt),
             "gts" => Some(Self::GtS),
             "lt" => Some(Self::Lt),
@@ -169,12 +317,24 @@ impl Opcode {
             "xor" => Some(Self::Xor),
             "not" => Some(Self::Not),
             "inv" => Some(Self::Inv),
+            "ne" => Some(Self::Ne),
             "jump" => Some(Self::Jump),
             "jcond" => Some(Self::JCond),
+            "select" => Some(Self::Select),
+            "bittest" => Some(Self::BitTest),
+            "bitset" => Some(Self::BitSet),
+            "bitclear" => Some(Self::BitClear),
+            "printf" => Some(Self::PrintF),
+            "readn" => Some(Self::ReadN),
+            "readbool" => Some(Self::ReadBool),
+            "printstack" => Some(Self::PrintStack),
+            "printpeek" => Some(Self::PrintPeek),
+            "printpeeks" => Some(Self::PrintPeekS),
             "read" => Some(Self::Read),
             "reads" => Some(Self::ReadS),
             "print" => Some(Self::Print),
             "prints" => Some(Self::PrintS),
+            "assert" => Some(Self::Assert),
             "halt" => Some(Self::Halt),
             _ => None,
         }
@@ -201,12 +361,26 @@ impl Opcode {
             Self::Dup3 => "dup3",
             Self::Pop => "pop",
             Self::Swap => "swap",
+            Self::DupN => "dupn",
             Self::Add => "add",
             Self::Sub => "sub",
             Self::Mul => "mul",
             Self::Mod => "mod",
             Self::Div => "div",
             Self::DivS => "divs",
+            Self::Pow => "pow",
+            Self::ModPow => "modpow",
+            Self::FAdd => "fadd",
+            Self::FSub => "fsub",
+            Self::FMul => "fmul",
+            Self::FDiv => "fdiv",
+            Self::FGt => "fgt",
+            Self::FLt => "flt",
+            Self::FEq => "feq",
+            Self::LAnd => "land",
+            Self::LOr => "lor",
+            Self::AddCarry => "addc",
+            Self::SubBorrow => "subb",
             Self::Gt => "gt",
             Self::GtS => "gts",
             Self::Lt => "lt",
@@ -221,12 +395,24 @@ impl Opcode {
             Self::Xor => "xor",
             Self::Not => "not",
             Self::Inv => "inv",
+            Self::Ne => "ne",
             Self::Jump => "jump",
             Self::JCond => "jcond",
+            Self::Select => "select",
+            Self::BitTest => "bittest",
+            Self::BitSet => "bitset",
+            Self::BitClear => "bitclear",
+            Self::PrintF => "printf",
+            Self::ReadN => "readn",
+            Self::ReadBool => "readbool",
+            Self::PrintStack => "printstack",
+            Self::PrintPeek => "printpeek",
+            Self::PrintPeekS => "printpeeks",
             Self::Read => "read",
             Self::ReadS => "reads",
             Self::Print => "print",
             Self::PrintS => "prints",
+            Self::Assert => "assert",
             Self::Halt => "halt",
         }
     }
@@ -236,13 +422,63 @@ impl Opcode {
     /// This will be 0, 1, 2, 4, or 8.
     pub fn literal_len(self) -> usize {
         match self {
-            Opcode::Push8 | Opcode::Push8S => 1,
+            Opcode::Push8 | Opcode::Push8S | Opcode::DupN | Opcode::ReadN => 1,
             Opcode::Push16 | Opcode::Push16S => 2,
             Opcode::Push32 | Opcode::Push32S => 4,
             Opcode::Push64 => 8,
             _ => 0,
         }
     }
+
+    /// Get the signedness of the inline literal expected by this opcode.
+    ///
+    /// Signed literals are sign-extended to 64 bits when decoded, and unsigned literals are
+    /// zero-extended. Returns `None` if the opcode takes no inline literal.
+    pub fn literal_signedness(self) -> Option<Signedness> {
+        match self {
+            Opcode::Push8S | Opcode::Push16S | Opcode::Push32S => Some(Signedness::Signed),
+            Opcode::Push8
+            | Opcode::Push16
+            | Opcode::Push32
+            | Opcode::Push64
+            | Opcode::DupN
+            | Opcode::ReadN => Some(Signedness::Unsigned),
+            _ => None,
+        }
+    }
+
+    /// Check whether this opcode is one of the literal push opcodes.
+    pub fn is_push(self) -> bool {
+        matches!(
+            self,
+            Opcode::Push8
+                | Opcode::Push8S
+                | Opcode::Push16
+                | Opcode::Push16S
+                | Opcode::Push32
+                | Opcode::Push32S
+                | Opcode::Push64
+        )
+    }
+
+    /// The default cost of executing this opcode, for use in gas-limited execution.
+    ///
+    /// Most opcodes cost 1. Pushes cost 2, since their inline literal must also be decoded, and
+    /// `varres` costs 8, since it may allocate host memory.
+    pub fn cost(self) -> u32 {
+        match self {
+            Opcode::VarRes => 8,
+            opcode if opcode.is_push() => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// The signedness of an inline literal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Signedness {
+    Unsigned,
+    Signed,
 }
 
 impl From<Opcode> for u8 {
@@ -287,6 +523,15 @@ pub struct Instruction {
 impl Display for Instruction {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.opcode.mnemonic())?;
+        if self.opcode.literal_signedness() == Some(Signedness::Signed) {
+            return match self.opcode.literal_len() {
+                1 => write!(f, " {}", self.literal as i8),
+                2 => write!(f, " {}", self.literal as i16),
+                4 => write!(f, " {}", self.literal as i32),
+                _ => unreachable!(),
+            };
+        }
+
         match self.opcode.literal_len() {
             0 => Ok(()),
             1 => write!(f, " 0x{:02x}", self.literal as u8),
@@ -314,6 +559,35 @@ impl Instruction {
         Self { opcode, literal }
     }
 
+    /// Create a new `Instruction`, checking that the literal is valid for the opcode.
+    ///
+    /// Unlike [`Instruction::new`], which accepts any literal and leaves it to be ignored or
+    /// truncated during encoding, this requires that the instruction would decode back to exactly
+    /// the same literal after being encoded.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InstructionError::UnexpectedLiteral`] if the opcode expects no literal and
+    /// `literal` is nonzero, and [`InstructionError::LiteralOutOfRange`] if `literal` cannot be
+    /// represented in the opcode's literal width with the opcode's zero- or sign-extension. (E.g.
+    /// `push8` accepts 0 through 255, while `push8s` accepts the sign-extended forms of -128
+    /// through 127.)
+    pub fn try_new(opcode: Opcode, literal: u64) -> Result<Self, InstructionError> {
+        let lit_len = opcode.literal_len();
+        if lit_len == 0 {
+            if literal != 0 {
+                return Err(InstructionError::UnexpectedLiteral);
+            }
+        } else {
+            let bytes = literal.to_be_bytes();
+            if Self::extend_literal(opcode, &bytes[8 - lit_len..]) != literal {
+                return Err(InstructionError::LiteralOutOfRange);
+            }
+        }
+
+        Ok(Self::new(opcode, literal))
+    }
+
     /// Decode a single instruction from a stream.
     ///
     /// This function makes very small reads. It is recommended to use it on buffered streams to
@@ -329,28 +603,56 @@ impl Instruction {
         let mut buf = [0; 8];
         stream.read_exact(&mut buf[..1])?;
         let opcode = Opcode::from_u8(buf[0])
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, DecodeError::UnrecognizedOpcode))?;
+            .ok_or_else(|| io::Error::other(DecodeError::UnrecognizedOpcode))?;
 
         let lit_len = opcode.literal_len();
-        let literal = if lit_len > 0 {
-            stream.read_exact(&mut buf[..lit_len])?;
-            match opcode {
-                Opcode::Push8 => buf[0] as u64,
-                Opcode::Push8S => buf[0] as i8 as u64,
-                Opcode::Push16 => u16::from_be_bytes(array_from_slice(&buf[..2])) as u64,
-                Opcode::Push16S => i16::from_be_bytes(array_from_slice(&buf[..2])) as u64,
-                Opcode::Push32 => u32::from_be_bytes(array_from_slice(&buf[..4])) as u64,
-                Opcode::Push32S => i32::from_be_bytes(array_from_slice(&buf[..4])) as u64,
-                Opcode::Push64 => u64::from_be_bytes(buf),
-                _ => unreachable!(),
-            }
-        } else {
-            0
-        };
+        stream.read_exact(&mut buf[..lit_len])?;
+        let literal = Self::extend_literal(opcode, &buf[..lit_len]);
 
         Ok(Instruction { opcode, literal })
     }
 
+    /// Decode a single instruction from a byte slice, starting at the given offset.
+    ///
+    /// On success, returns the decoded instruction along with the offset immediately following
+    /// it, i.e. the offset of the next instruction.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DecodeError::UnexpectedEnd`] if `offset` is not less than the length of `bytes`,
+    /// [`DecodeError::UnrecognizedOpcode`] if the byte at `offset` is not a recognized opcode, and
+    /// [`DecodeError::IncompleteLiteral`] if the slice ends before the opcode's full inline
+    /// literal.
+    pub fn decode_at(bytes: &[u8], offset: usize) -> Result<(Self, usize), DecodeError> {
+        let byte = *bytes.get(offset).ok_or(DecodeError::UnexpectedEnd)?;
+        let opcode = Opcode::from_u8(byte).ok_or(DecodeError::UnrecognizedOpcode)?;
+
+        let lit_start = offset + 1;
+        let next = lit_start + opcode.literal_len();
+        let lit_bytes = bytes
+            .get(lit_start..next)
+            .ok_or(DecodeError::IncompleteLiteral)?;
+        let literal = Self::extend_literal(opcode, lit_bytes);
+
+        Ok((Instruction { opcode, literal }, next))
+    }
+
+    /// Zero- or sign-extend the big-endian inline literal bytes of the given opcode to 64 bits.
+    ///
+    /// `bytes` must be exactly `opcode.literal_len()` bytes long.
+    fn extend_literal(opcode: Opcode, bytes: &[u8]) -> u64 {
+        match opcode {
+            Opcode::Push8 | Opcode::DupN | Opcode::ReadN => bytes[0] as u64,
+            Opcode::Push8S => bytes[0] as i8 as u64,
+            Opcode::Push16 => u16::from_be_bytes(array_from_slice(bytes)) as u64,
+            Opcode::Push16S => i16::from_be_bytes(array_from_slice(bytes)) as u64,
+            Opcode::Push32 => u32::from_be_bytes(array_from_slice(bytes)) as u64,
+            Opcode::Push32S => i32::from_be_bytes(array_from_slice(bytes)) as u64,
+            Opcode::Push64 => u64::from_be_bytes(array_from_slice(bytes)),
+            _ => 0,
+        }
+    }
+
     /// Encode an instruction into a stream.
     ///
     /// This function makes very small writes. It is recommended to use it on buffered streams to
@@ -371,16 +673,52 @@ impl Instruction {
         Ok(())
     }
 
+    /// Encode an instruction into a stream, checking that its literal is valid for its opcode.
+    ///
+    /// Unlike [`Instruction::encode_to_stream`], which truncates the literal to the opcode's
+    /// literal width, this requires that the instruction would decode back to exactly the same
+    /// literal, as for [`Instruction::try_new`]. (E.g. `push8s` with a literal of 300 or 200 is
+    /// rejected, as neither is the sign extension of a single byte.) Nothing is written to the
+    /// stream if the literal is invalid.
+    ///
+    /// # Errors
+    ///
+    /// If the literal is invalid, this function will return an error with error kind
+    /// `InvalidInput`, whose wrapped error is downcastable to [`InstructionError`].
+    ///
+    /// Any errors returned from the stream will be returned unmodified.
+    pub fn encode_checked<W: io::Write>(&self, stream: &mut W) -> io::Result<()> {
+        Self::try_new(self.opcode, self.literal)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        self.encode_to_stream(stream)
+    }
+
     /// Get the number of bytes in the encoded form of this instruction.
     pub fn encoded_len(&self) -> usize {
         1 + self.opcode.literal_len()
     }
 
     /// Get the combined encoded length of a series of instructions.
+    ///
+    /// # Panics
+    ///
+    /// Each instruction encodes to at most 9 bytes, so the sum can exceed `usize::MAX` on targets
+    /// with small pointer widths. This function panics on overflow in debug builds, and silently
+    /// wraps in release builds; use [`Instruction::checked_combined_len`] where the instruction
+    /// list may be very large.
     pub fn combined_len(instructions: &[Self]) -> usize {
         instructions.iter().map(|inst| inst.encoded_len()).sum()
     }
 
+    /// Get the combined encoded length of a series of instructions, checking for overflow.
+    ///
+    /// Returns `None` if the length does not fit in a `usize`.
+    pub fn checked_combined_len(instructions: &[Self]) -> Option<usize> {
+        instructions
+            .iter()
+            .try_fold(0usize, |len, inst| len.checked_add(inst.encoded_len()))
+    }
+
     /// Construct an unsigned push instruction of optimal size for the value.
     pub fn optimal_push(value: u64) -> Self {
         let opcode = if value <= u8::MAX as u64 {
@@ -410,6 +748,21 @@ impl Instruction {
 
         Self::new(opcode, value as u64)
     }
+
+    /// Construct a push instruction of optimal size for the value, signed or unsigned.
+    ///
+    /// This chooses the narrowest push opcode, from either the signed or unsigned family, that
+    /// faithfully represents the value once extended to 64 bits. Non-negative values always use
+    /// an unsigned push, since for any such value the narrowest unsigned encoding is never wider
+    /// than the narrowest signed one (e.g. 128 through 255 fit in `push8` but not `push8s`).
+    /// Negative values use a signed push.
+    pub fn optimal_push_int(value: i64) -> Self {
+        if value >= 0 {
+            Self::optimal_push(value as u64)
+        } else {
+            Self::optimal_pushs(value)
+        }
+    }
 }
 
 pub fn write_instructions<W: io::Write>(stream: &mut W, instrs: &[Instruction]) -> io::Result<()> {
@@ -426,11 +779,89 @@ pub fn instructions_to_vec(instrs: &[Instruction]) -> Vec<u8> {
     bytes
 }
 
+/// The kinds of IO that a program may perform, as reported by [`uses_io`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct IoUsage {
+    /// Whether the program contains any `read`, `reads`, `readn`, or `readbool` instructions.
+    pub input: bool,
+
+    /// Whether the program contains any `print`, `prints`, `printf`, `printstack`, `printpeek`, or
+    /// `printpeeks` instructions.
+    pub output: bool,
+}
+
+/// Statically determine whether a program contains any input or output instructions.
+///
+/// The program is decoded linearly from offset 0, and every decoded instruction is inspected,
+/// whether or not it is reachable. Note that this does not account for jumps into the middle of
+/// an instruction, which could execute inline literal bytes as opcodes; a host that needs a hard
+/// guarantee should simply not configure the corresponding stream.
+///
+/// # Errors
+///
+/// Returns [`DecodeError::UnrecognizedOpcode`] or [`DecodeError::IncompleteLiteral`] if the
+/// program cannot be fully decoded.
+pub fn uses_io(bytes: &[u8]) -> Result<IoUsage, DecodeError> {
+    let mut usage = IoUsage::default();
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let (instr, next) = Instruction::decode_at(bytes, offset)?;
+        match instr.opcode {
+            Opcode::Read | Opcode::ReadS | Opcode::ReadN | Opcode::ReadBool => usage.input = true,
+            Opcode::Print
+            | Opcode::PrintS
+            | Opcode::PrintF
+            | Opcode::PrintStack
+            | Opcode::PrintPeek
+            | Opcode::PrintPeekS => usage.output = true,
+            _ => {}
+        }
+        offset = next;
+    }
+
+    Ok(usage)
+}
+
+/// Count the instructions in a program, decoding linearly from offset 0.
+///
+/// # Errors
+///
+/// Returns [`DecodeError::UnrecognizedOpcode`] or [`DecodeError::IncompleteLiteral`] if the
+/// program cannot be fully decoded.
+pub fn instruction_count(bytes: &[u8]) -> Result<usize, DecodeError> {
+    let mut count = 0;
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        (_, offset) = Instruction::decode_at(bytes, offset)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Error returned by [`Instruction::try_new`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum InstructionError {
+    #[error("Literal given for an opcode that takes no literal")]
+    UnexpectedLiteral,
+
+    #[error("Literal does not fit in the opcode's literal width")]
+    LiteralOutOfRange,
+}
+
 /// Error returned by [`Instruction`] encoding and decoding.
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
 pub enum DecodeError {
     #[error("Unrecognized opcode")]
     UnrecognizedOpcode,
+
+    #[error("Unexpected end of input")]
+    UnexpectedEnd,
+
+    #[error("Incomplete literal")]
+    IncompleteLiteral,
 }
 
 #[cfg(test)]
@@ -444,6 +875,7 @@ mod test {
             (Instruction::new(Opcode::Div, 14), &[DIV]), // Lits are ignored for standalone opcodes
             (Instruction::new(Opcode::Push8, 0), &[PUSH8, 0]),
             (Instruction::new(Opcode::Push8, 155), &[PUSH8, 155]),
+            (Instruction::new(Opcode::DupN, 0x104), &[DUPN, 4]),
             (
                 Instruction::new(Opcode::Push16, 0x12345), // Lits are truncated for shorter lits
                 &[PUSH16, 0x23, 0x45],
@@ -473,6 +905,99 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn encode_checked() -> io::Result<()> {
+        let valid: &[(_, &[u8])] = &[
+            (Instruction::new(Opcode::Gt, 0), &[GT]),
+            (Instruction::new(Opcode::Push8, 255), &[PUSH8, 255]),
+            (Instruction::new(Opcode::Push8S, 127), &[PUSH8S, 0x7f]),
+            (
+                Instruction::new(Opcode::Push8S, -128i64 as u64),
+                &[PUSH8S, 0x80],
+            ),
+            (
+                Instruction::new(Opcode::Push16, 0xffff),
+                &[PUSH16, 0xff, 0xff],
+            ),
+            (
+                Instruction::new(Opcode::Push16S, -2i64 as u64),
+                &[PUSH16S, 0xff, 0xfe],
+            ),
+            (
+                Instruction::new(Opcode::Push32S, i32::MIN as i64 as u64),
+                &[PUSH32S, 0x80, 0, 0, 0],
+            ),
+            (
+                Instruction::new(Opcode::Push64, u64::MAX),
+                &[PUSH64, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+        ];
+
+        let mut stream = Vec::new();
+        for (instr, bytes) in valid {
+            stream.clear();
+
+            instr.encode_checked(&mut stream)?;
+            assert_eq!(&stream, bytes);
+        }
+
+        let invalid = [
+            (
+                Instruction::new(Opcode::Push8S, 300),
+                InstructionError::LiteralOutOfRange,
+            ),
+            (
+                Instruction::new(Opcode::Push8S, 200),
+                InstructionError::LiteralOutOfRange,
+            ),
+            (
+                Instruction::new(Opcode::Push8S, -129i64 as u64),
+                InstructionError::LiteralOutOfRange,
+            ),
+            (
+                Instruction::new(Opcode::Push8, 256),
+                InstructionError::LiteralOutOfRange,
+            ),
+            (
+                Instruction::new(Opcode::Push8, -1i64 as u64),
+                InstructionError::LiteralOutOfRange,
+            ),
+            (
+                Instruction::new(Opcode::Push16S, 0x8000),
+                InstructionError::LiteralOutOfRange,
+            ),
+            (
+                Instruction::new(Opcode::Push32, 1 << 32),
+                InstructionError::LiteralOutOfRange,
+            ),
+            (
+                Instruction::new(Opcode::Div, 14),
+                InstructionError::UnexpectedLiteral,
+            ),
+        ];
+
+        for (instr, expected) in invalid {
+            stream.clear();
+
+            let err = instr.encode_checked(&mut stream).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(
+                err.into_inner()
+                    .and_then(|err| err.downcast::<InstructionError>().ok())
+                    .map(|err| *err),
+                Some(expected),
+                "{instr:?}"
+            );
+            assert!(stream.is_empty());
+
+            // The lenient encoding still truncates
+            instr.encode_to_stream(&mut stream)?;
+            assert_eq!(stream.len(), instr.encoded_len());
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn decode_successful() -> io::Result<()> {
         let pairs: &[(_, &[u8])] = &[
@@ -482,6 +1007,8 @@ mod test {
             (Instruction::new(Opcode::Push8, 0), &[PUSH8, 0]),
             // Only extracts first instruction in presence of literal:
             (Instruction::new(Opcode::Push8, 155), &[PUSH8, 155, 3, 4, 5]),
+            (Instruction::new(Opcode::DupN, 0xfe), &[DUPN, 0xfe]), // Depths are zero-extended
+            (Instruction::new(Opcode::ReadN, 0x83), &[READN, 0x83]), // As are counts
             (
                 Instruction::new(Opcode::Push16, 0x2345),
                 &[PUSH16, 0x23, 0x45],
@@ -541,4 +1068,282 @@ mod test {
             .expect("Downcast failed");
         assert!(matches!(*err, DecodeError::UnrecognizedOpcode));
     }
+
+    #[test]
+    fn try_new() {
+        use InstructionError::*;
+
+        let cases = &[
+            (Opcode::Add, 0, Ok(())),
+            (Opcode::Add, 1, Err(UnexpectedLiteral)),
+            (Opcode::Push8, 255, Ok(())),
+            (Opcode::Push8, 256, Err(LiteralOutOfRange)),
+            (Opcode::Push8S, -128_i64 as u64, Ok(())),
+            (Opcode::Push8S, -129_i64 as u64, Err(LiteralOutOfRange)),
+            (Opcode::Push8S, 0x80, Err(LiteralOutOfRange)), // Would decode as -128
+            (Opcode::Push16S, 0x7fff, Ok(())),
+            (Opcode::Push16S, 0x8000, Err(LiteralOutOfRange)),
+            (Opcode::Push32, u32::MAX as u64, Ok(())),
+            (Opcode::Push32, u32::MAX as u64 + 1, Err(LiteralOutOfRange)),
+            (Opcode::Push32S, i32::MIN as i64 as u64, Ok(())),
+            (
+                Opcode::Push32S,
+                i32::MIN as i64 as u64 - 1,
+                Err(LiteralOutOfRange),
+            ),
+            (Opcode::Push64, u64::MAX, Ok(())),
+            (Opcode::DupN, 4, Ok(())),
+            (Opcode::DupN, 0x100, Err(LiteralOutOfRange)),
+        ];
+
+        for &(opcode, literal, expected) in cases {
+            assert_eq!(
+                Instruction::try_new(opcode, literal),
+                expected.map(|()| Instruction::new(opcode, literal)),
+                "{opcode:?} {literal:#x}"
+            );
+        }
+    }
+
+    #[test]
+    fn decode_at() {
+        #[rustfmt::skip]
+        let bytes = &[
+            PUSH8, 4,
+            ADD,
+            PUSH16S, 0xff, 0xfe,
+            PUSH32, 0, 1,
+        ];
+
+        assert_eq!(
+            Instruction::decode_at(bytes, 0),
+            Ok((Instruction::new(Opcode::Push8, 4), 2))
+        );
+        assert_eq!(
+            Instruction::decode_at(bytes, 2),
+            Ok((Instruction::from(Opcode::Add), 3))
+        );
+        assert_eq!(
+            Instruction::decode_at(bytes, 3),
+            Ok((Instruction::new(Opcode::Push16S, -2_i64 as u64), 6))
+        );
+
+        // Truncated literal at the end of the slice
+        assert_eq!(
+            Instruction::decode_at(bytes, 6),
+            Err(DecodeError::IncompleteLiteral)
+        );
+
+        // Offset in the middle of a literal
+        assert_eq!(
+            Instruction::decode_at(bytes, 7),
+            Err(DecodeError::UnrecognizedOpcode)
+        );
+
+        // Offset out of bounds
+        assert_eq!(
+            Instruction::decode_at(bytes, 9),
+            Err(DecodeError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Instruction::decode_at(bytes, 100),
+            Err(DecodeError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn optimal_push_int() {
+        let pairs = &[
+            (0, Opcode::Push8),
+            (127, Opcode::Push8),
+            (128, Opcode::Push8), // Unsigned preferred where signed would need more bytes
+            (255, Opcode::Push8),
+            (256, Opcode::Push16),
+            (-1, Opcode::Push8S),
+            (-128, Opcode::Push8S),
+            (-129, Opcode::Push16S),
+            (u32::MAX as i64, Opcode::Push32),
+            (i32::MIN as i64, Opcode::Push32S),
+            (i32::MIN as i64 - 1, Opcode::Push64),
+            (i64::MAX, Opcode::Push64),
+        ];
+
+        for &(value, opcode) in pairs {
+            let instr = Instruction::optimal_push_int(value);
+            assert_eq!(
+                instr,
+                Instruction::new(opcode, value as u64),
+                "value = {value}"
+            );
+
+            // The encoded form must decode back to the same 64-bit value
+            let bytes = instructions_to_vec(&[instr]);
+            let decoded = Instruction::decode_from_stream(&mut bytes.as_slice()).unwrap();
+            assert_eq!(decoded.literal, value as u64, "value = {value}");
+        }
+    }
+
+    #[test]
+    fn io_usage() {
+        let io = |input, output| IoUsage { input, output };
+
+        assert_eq!(uses_io(&[]), Ok(io(false, false)));
+        assert_eq!(
+            uses_io(&[PUSH8, 3, PUSH8, 4, ADD, HALT]),
+            Ok(io(false, false))
+        );
+        assert_eq!(uses_io(&[READ, POP]), Ok(io(true, false)));
+        assert_eq!(uses_io(&[READN, 2, POP, POP]), Ok(io(true, false)));
+        assert_eq!(uses_io(&[PUSH8, 1, PRINTS]), Ok(io(false, true)));
+        assert_eq!(uses_io(&[READS, PRINTPEEK, POP]), Ok(io(true, true)));
+        assert_eq!(uses_io(&[PRINTSTACK]), Ok(io(false, true)));
+
+        // IO opcode bytes within inline literals are not instructions
+        assert_eq!(
+            uses_io(&[PUSH8, READ, PUSH16, PRINT, PRINTS, DUPN, READS]),
+            Ok(io(false, false))
+        );
+
+        // Unreachable instructions are still counted
+        assert_eq!(uses_io(&[HALT, PRINT]), Ok(io(false, true)));
+
+        assert_eq!(
+            uses_io(&[PUSH8, 1, 0x20, PRINT]),
+            Err(DecodeError::UnrecognizedOpcode)
+        );
+        assert_eq!(
+            uses_io(&[READ, PUSH16, 1]),
+            Err(DecodeError::IncompleteLiteral)
+        );
+    }
+
+    #[test]
+    fn display() {
+        let display = |opcode, literal| Instruction::new(opcode, literal).to_string();
+
+        assert_eq!(display(Opcode::Add, 0), "add");
+        assert_eq!(display(Opcode::Push8, 0xff), "push8 0xff");
+        assert_eq!(display(Opcode::Push64, 1), "push64 0x0000000000000001");
+        assert_eq!(display(Opcode::DupN, 3), "dupn 0x03");
+
+        assert_eq!(display(Opcode::Push8S, -1_i64 as u64), "push8s -1");
+        assert_eq!(display(Opcode::Push8S, 0x7f), "push8s 127");
+        assert_eq!(display(Opcode::Push16S, -300_i64 as u64), "push16s -300");
+        assert_eq!(
+            display(Opcode::Push32S, i32::MIN as i64 as u64),
+            "push32s -2147483648"
+        );
+        assert_eq!(Instruction::optimal_pushs(-5).to_string(), "push8s -5");
+    }
+
+    #[test]
+    fn literal_signedness() {
+        assert_eq!(
+            Opcode::Push16S.literal_signedness(),
+            Some(Signedness::Signed)
+        );
+        assert_eq!(
+            Opcode::Push64.literal_signedness(),
+            Some(Signedness::Unsigned)
+        );
+        assert_eq!(
+            Opcode::DupN.literal_signedness(),
+            Some(Signedness::Unsigned)
+        );
+        assert_eq!(Opcode::Jump.literal_signedness(), None);
+    }
+
+    #[test]
+    fn instruction_count() {
+        // Example 1 from the challenge document
+        #[rustfmt::skip]
+        let example1 = &[
+            PUSH8, 2,
+            VARRES,
+            PUSH8, 5,
+            PUSH8, 0,
+            VARST,
+            PUSH8, 4,
+            PUSH8, 0,
+            VARLD,
+            ADD,
+            PUSH8, 1,
+            VARST,
+            PUSH8, 0,
+            VARLD,
+            PRINT,
+            PUSH8, 1,
+            VARLD,
+            PRINT,
+        ];
+
+        assert_eq!(super::instruction_count(example1), Ok(17));
+        assert_eq!(super::instruction_count(&[]), Ok(0));
+        assert_eq!(
+            super::instruction_count(&[PUSH8, 2, PUSH16, 1]),
+            Err(DecodeError::IncompleteLiteral)
+        );
+    }
+
+    #[test]
+    fn cost() {
+        assert_eq!(Opcode::Pop.cost(), 1);
+        assert_eq!(Opcode::Add.cost(), 1);
+        assert_eq!(Opcode::Push8.cost(), 2);
+        assert_eq!(Opcode::Push64.cost(), 2);
+        assert_eq!(Opcode::VarRes.cost(), 8);
+        assert!(Opcode::all().iter().all(|op| op.cost() > 0));
+    }
+
+    #[test]
+    fn combined_len() {
+        let instrs = [
+            Instruction::new(Opcode::Push8, 1),
+            Instruction::new(Opcode::Push64, 2),
+            Instruction::from(Opcode::Add),
+        ];
+
+        assert_eq!(Instruction::combined_len(&instrs), 12);
+        assert_eq!(Instruction::checked_combined_len(&instrs), Some(12));
+        assert_eq!(Instruction::checked_combined_len(&[]), Some(0));
+    }
+
+    #[test]
+    fn opcode_tables() {
+        let all = Opcode::all();
+
+        // Every recognized byte is listed, in order
+        let recognized: Vec<_> = (0..=u8::MAX).filter_map(Opcode::from_u8).collect();
+        assert_eq!(all, recognized);
+
+        for &opcode in all {
+            assert_eq!(Opcode::from_u8(u8::from(opcode)), Some(opcode));
+            assert_eq!(Opcode::from_mnemonic(opcode.mnemonic()), Some(opcode));
+
+            let expected_len = match opcode {
+                Opcode::Push8 | Opcode::Push8S => 1,
+                Opcode::Push16 | Opcode::Push16S => 2,
+                Opcode::Push32 | Opcode::Push32S => 4,
+                Opcode::Push64 => 8,
+                Opcode::DupN | Opcode::ReadN => 1,
+                _ => 0,
+            };
+            assert_eq!(opcode.literal_len(), expected_len, "{}", opcode.mnemonic());
+            assert_eq!(
+                opcode.literal_signedness().is_some(),
+                expected_len > 0,
+                "{}",
+                opcode.mnemonic()
+            );
+            assert_eq!(
+                opcode.is_push(),
+                opcode.mnemonic().starts_with("push"),
+                "{}",
+                opcode.mnemonic()
+            );
+        }
+
+        let mnemonics: std::collections::HashSet<_> = all.iter().map(|op| op.mnemonic()).collect();
+        assert_eq!(mnemonics.len(), all.len());
+    }
 }
@@ -0,0 +1,165 @@
+//! Validated, pre-decoded bytecode programs.
+
+use crate::{DecodeError, Instruction};
+
+/// Error returned when constructing a [`Program`] from bytes that cannot be fully decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("{error} at offset {offset:#06x}")]
+pub struct ProgramError {
+    /// The program offset at which decoding failed.
+    pub offset: usize,
+
+    /// The decoding error.
+    pub error: DecodeError,
+}
+
+/// A bytecode program, along with its instructions decoded linearly from offset 0.
+///
+/// Constructing a `Program` with [`Program::try_from`] guarantees that the whole byte sequence
+/// decodes into complete instructions. Note that the VM may still jump into the middle of an
+/// instruction at runtime, so the raw bytes remain available through [`Program::bytes`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program {
+    bytes: Vec<u8>,
+    instructions: Vec<Instruction>,
+    offsets: Vec<usize>,
+}
+
+impl Program {
+    /// The raw bytes of the program.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The decoded instructions of the program, in order.
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// The number of instructions in the program.
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// Whether the program contains no instructions.
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    /// The program offset of the instruction at the given index.
+    pub fn index_to_offset(&self, index: usize) -> Option<usize> {
+        self.offsets.get(index).copied()
+    }
+
+    /// The index of the instruction starting at the given program offset.
+    ///
+    /// Returns `None` if `offset` is past the end of the program, or falls inside an instruction's
+    /// inline literal.
+    pub fn offset_to_index(&self, offset: usize) -> Option<usize> {
+        self.offsets.binary_search(&offset).ok()
+    }
+}
+
+impl TryFrom<&[u8]> for Program {
+    type Error = ProgramError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut instructions = Vec::new();
+        let mut offsets = Vec::new();
+        let mut offset = 0;
+
+        while offset < bytes.len() {
+            let (instr, next) = Instruction::decode_at(bytes, offset)
+                .map_err(|error| ProgramError { offset, error })?;
+            instructions.push(instr);
+            offsets.push(offset);
+            offset = next;
+        }
+
+        Ok(Self {
+            bytes: bytes.to_vec(),
+            instructions,
+            offsets,
+        })
+    }
+}
+
+impl AsRef<[u8]> for Program {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::consts::*;
+    use crate::Opcode;
+
+    use super::*;
+
+    #[rustfmt::skip]
+    const COUNTDOWN: &[u8] = &[
+        PUSH8, 3,
+
+        DUP0,
+        PRINT,
+        PUSH8, 1,
+        SUB,
+        DUP0,
+        PUSH8S, (-9i8) as u8,
+        JCOND,
+
+        HALT,
+    ];
+
+    #[test]
+    fn decode() {
+        let program = Program::try_from(COUNTDOWN).unwrap();
+        assert_eq!(program.len(), 9);
+        assert!(!program.is_empty());
+        assert_eq!(program.bytes(), COUNTDOWN);
+        assert_eq!(
+            program.instructions()[..3],
+            [
+                Instruction::new(Opcode::Push8, 3),
+                Instruction::from(Opcode::Dup0),
+                Instruction::from(Opcode::Print),
+            ]
+        );
+        assert_eq!(
+            program.instructions()[6],
+            Instruction::new(Opcode::Push8S, -9i64 as u64)
+        );
+
+        assert!(Program::try_from(&[][..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn offsets() {
+        let program = Program::try_from(COUNTDOWN).unwrap();
+        assert_eq!(program.offset_to_index(0), Some(0));
+        assert_eq!(program.offset_to_index(1), None);
+        assert_eq!(program.offset_to_index(2), Some(1));
+        assert_eq!(program.offset_to_index(10), Some(7));
+        assert_eq!(program.offset_to_index(11), Some(8));
+        assert_eq!(program.offset_to_index(12), None);
+
+        for index in 0..program.len() {
+            let offset = program.index_to_offset(index).unwrap();
+            assert_eq!(program.offset_to_index(offset), Some(index));
+        }
+        assert_eq!(program.index_to_offset(9), None);
+    }
+
+    #[test]
+    fn decode_error() {
+        let err = Program::try_from(&[PUSH8, 2, 0x07][..]).unwrap_err();
+        assert_eq!(
+            err,
+            ProgramError {
+                offset: 2,
+                error: DecodeError::UnrecognizedOpcode
+            }
+        );
+    }
+}
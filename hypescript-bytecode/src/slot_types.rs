@@ -0,0 +1,50 @@
+//! Static type annotations for operand stack slots.
+//!
+//! The VM itself is untyped; every stack value is simply 64 bits. A compiler that knows the types
+//! of the values its code places on the stack can describe them with a [`SlotTypeMap`], which
+//! tools such as trace formatters can use to render values more readably, and which the VM can
+//! check its stack against to catch miscompilations.
+
+use std::collections::BTreeMap;
+
+use crate::Instruction;
+
+/// The static type of a value in an operand stack slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SlotType {
+    Int,
+    Bool,
+}
+
+/// A map from program addresses to the types of the operand stack slots before the instruction at
+/// that address is executed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SlotTypeMap {
+    by_pc: BTreeMap<usize, Vec<SlotType>>,
+}
+
+impl SlotTypeMap {
+    /// Build a map from a list of instructions, and a parallel list giving the types of the stack
+    /// slots before each instruction, bottom of the stack first.
+    ///
+    /// The instructions are assumed to be laid out contiguously from program address 0, as by
+    /// [`write_instructions`](crate::write_instructions).
+    pub fn from_instructions(instrs: &[Instruction], types: Vec<Vec<SlotType>>) -> Self {
+        let mut by_pc = BTreeMap::new();
+        let mut pc = 0;
+        for (instr, types) in instrs.iter().zip(types) {
+            by_pc.insert(pc, types);
+            pc += instr.encoded_len();
+        }
+
+        Self { by_pc }
+    }
+
+    /// Get the types of the stack slots before the instruction at the given program address,
+    /// bottom of the stack first.
+    ///
+    /// Returns `None` if no types are known for that address.
+    pub fn get(&self, pc: usize) -> Option<&[SlotType]> {
+        self.by_pc.get(&pc).map(Vec::as_slice)
+    }
+}
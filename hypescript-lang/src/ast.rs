@@ -1,6 +1,7 @@
 //! The HypeScript abstract syntax tree structure
 
 use std::fmt::{self, Display, Formatter};
+use std::ops::{Deref, Range};
 use std::str::FromStr;
 
 /// Binary operators
@@ -11,6 +12,7 @@ pub enum BinopSym {
     Mul,
     Div,
     Mod,
+    Pow,
     Greater,
     Less,
     GreaterEq,
@@ -34,6 +36,7 @@ impl FromStr for BinopSym {
             "*" => Ok(Self::Mul),
             "/" => Ok(Self::Div),
             "%" => Ok(Self::Mod),
+            "**" => Ok(Self::Pow),
             ">" => Ok(Self::Greater),
             "<" => Ok(Self::Less),
             ">=" => Ok(Self::GreaterEq),
@@ -58,6 +61,7 @@ impl Display for BinopSym {
             Self::Mul => write!(f, "*"),
             Self::Div => write!(f, "/"),
             Self::Mod => write!(f, "%"),
+            Self::Pow => write!(f, "**"),
             Self::Greater => write!(f, ">"),
             Self::Less => write!(f, "<"),
             Self::GreaterEq => write!(f, ">="),
@@ -93,7 +97,53 @@ impl Display for UnopSym {
 #[error("failed to parse operator")]
 pub struct ParseOperatorError;
 
+/// A range of character offsets into the source text.
+pub type Span = Range<usize>;
+
+/// A node along with the [`Span`] of the source text it was parsed from.
+///
+/// Nodes constructed directly, rather than by the parser, have an empty span at offset 0. Spans
+/// are ignored when comparing for equality, so a parsed tree compares equal to the same tree
+/// constructed directly.
+#[derive(Debug, Clone)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    /// Attach a span to a node.
+    pub fn new(node: T, span: Span) -> Self {
+        Self { node, span }
+    }
+}
+
+impl<T> From<T> for Spanned<T> {
+    fn from(node: T) -> Self {
+        Self::new(node, Span::default())
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
+impl<T: Eq> Eq for Spanned<T> {}
+
 /// The abstract syntax tree.
+///
+/// Expressions nested within other nodes, and the variable names of assignments, are [`Spanned`]
+/// with their location in the source text.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Ast {
     /// A block of statements
@@ -105,15 +155,21 @@ pub enum Ast {
     /// Unsigned int literal
     Int(u64),
 
+    /// Signed int literal, written with a leading minus sign
+    SInt(i64),
+
     /// Boolean literal
     Boolean(bool),
 
     /// Assignment to a declared variable
-    Assign { var: String, value: Box<Ast> },
+    Assign {
+        var: Spanned<String>,
+        value: Box<Spanned<Ast>>,
+    },
 
     /// If statement, with optional else clauses
     IfCond {
-        cond: Box<Ast>,
+        cond: Box<Spanned<Ast>>,
         body: Vec<Ast>,
         else_body: Vec<Ast>,
     },
@@ -121,15 +177,24 @@ pub enum Ast {
     /// Binary operation
     Binop {
         sym: BinopSym,
-        lhs: Box<Ast>,
-        rhs: Box<Ast>,
+        lhs: Box<Spanned<Ast>>,
+        rhs: Box<Spanned<Ast>>,
     },
 
     /// Unary operation
-    Unop { sym: UnopSym, operand: Box<Ast> },
+    Unop {
+        sym: UnopSym,
+        operand: Box<Spanned<Ast>>,
+    },
 
     /// Print statement
-    Print(Box<Ast>),
+    Print(Box<Spanned<Ast>>),
+
+    /// Assert statement
+    Assert(Box<Spanned<Ast>>),
+
+    /// Halt statement
+    Halt,
 }
 
 impl Ast {
@@ -141,15 +206,15 @@ impl Ast {
     /// Create a variable assignment node.
     pub fn assign(var: impl Into<String>, value: Self) -> Self {
         Self::Assign {
-            var: var.into(),
-            value: Box::new(value),
+            var: Spanned::from(var.into()),
+            value: Box::new(Spanned::from(value)),
         }
     }
 
     /// Create an if-else node.
     pub fn if_cond(cond: Self, body: Vec<Self>, else_body: Vec<Self>) -> Self {
         Self::IfCond {
-            cond: Box::new(cond),
+            cond: Box::new(Spanned::from(cond)),
             body,
             else_body,
         }
@@ -159,8 +224,8 @@ impl Ast {
     pub fn binop(sym: BinopSym, lhs: Self, rhs: Self) -> Self {
         Self::Binop {
             sym,
-            lhs: Box::new(lhs),
-            rhs: Box::new(rhs),
+            lhs: Box::new(Spanned::from(lhs)),
+            rhs: Box::new(Spanned::from(rhs)),
         }
     }
 
@@ -168,13 +233,18 @@ impl Ast {
     pub fn unop(sym: UnopSym, operand: Self) -> Self {
         Self::Unop {
             sym,
-            operand: Box::new(operand),
+            operand: Box::new(Spanned::from(operand)),
         }
     }
 
     /// Create a print node.
     pub fn print(val: Self) -> Self {
-        Self::Print(Box::new(val))
+        Self::Print(Box::new(Spanned::from(val)))
+    }
+
+    /// Create an assert node.
+    pub fn assert(cond: Self) -> Self {
+        Self::Assert(Box::new(Spanned::from(cond)))
     }
 }
 
@@ -197,6 +267,7 @@ impl Ast {
         mul Mul "multiplication",
         div Div "division",
         mod_ Mod "modulo",
+        pow Pow "exponentiation",
         greater Greater "greater-than comparison",
         less Less "less-than comparison",
         greater_eq GreaterEq "greater-or-equal comparison",
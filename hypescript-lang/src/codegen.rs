@@ -1,5 +1,8 @@
 //! Code generation from ASTs.
 
+use std::collections::HashMap;
+
+use hypescript_bytecode::slot_types::{SlotType, SlotTypeMap};
 use hypescript_bytecode::{Instruction, Opcode};
 
 use crate::ast::{Ast, BinopSym, UnopSym};
@@ -9,16 +12,63 @@ use crate::ast::{Ast, BinopSym, UnopSym};
 pub enum CodegenError {
     #[error("Undeclared variable `{0}`")]
     UndeclaredVariable(String),
+
+    #[error("Code body too large to jump over")]
+    BodyTooLarge,
+
+    #[error("Jump offset {0} does not fit in a jump literal")]
+    JumpTooFar(i64),
 }
 
+/// Optional opcodes that codegen may emit.
+///
+/// Each flag enables a convenience opcode that was added to the VM after its original core
+/// instruction set. When a flag is disabled, codegen emits an equivalent sequence of core
+/// instructions instead. `pow` has no equivalent sequence without loops, and is always emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodegenFeatures {
+    /// Emit `ne` for `!=`, rather than `eq` followed by `not`.
+    pub ne: bool,
+
+    /// Emit `land` and `lor` for `&&` and `||`, rather than the bitwise `and` and `or`.
+    ///
+    /// The two are equivalent on the canonical booleans produced by well-typed programs.
+    pub logical_connectives: bool,
+}
+
+impl CodegenFeatures {
+    /// Features that restrict codegen to the core instruction set.
+    pub fn core() -> Self {
+        Self {
+            ne: false,
+            logical_connectives: false,
+        }
+    }
+}
+
+impl Default for CodegenFeatures {
+    fn default() -> Self {
+        Self {
+            ne: true,
+            logical_connectives: true,
+        }
+    }
+}
+
+/// The widest literal, in bytes, that may encode a jump offset.
+const MAX_JUMP_LITERAL_LEN: usize = 8;
+
 /// Variable binding context for codegen.
 ///
 /// This struct tracks existing declared variables, as well as the maximum number of variables in
-/// scope at any point in the program.
+/// scope at any point in the program. It also records the type of each literal value pushed by the
+/// program, in the order they are emitted, for use by [`translate_with_slot_types`].
 #[derive(Debug, Clone, Default)]
 struct Context {
     vars: Vec<String>,
     max_vars: usize,
+    literal_types: Vec<SlotType>,
+    features: CodegenFeatures,
 }
 
 impl Context {
@@ -46,22 +96,55 @@ impl Context {
 
     /// Perform an action in a new program scope.
     ///
-    /// This will clone the current context, and pass the clone to the given closure. Thus, any
-    /// variables added to the context within the closure will be deallocated once this function
-    /// returns.
+    /// Any variables added to the context within the given closure will be deallocated once this
+    /// function returns. The variable list is truncated back to its length on entry rather than
+    /// cloned, so entering a scope takes constant time regardless of the number of variables
+    /// already in scope.
     fn in_new_scope<F, T>(&mut self, op: F) -> T
     where
         F: FnOnce(&mut Context) -> T,
     {
-        let mut inner_ctx = self.clone();
-        let res = op(&mut inner_ctx);
-        self.max_vars = self.max_vars.max(inner_ctx.max_vars);
+        let scope_start = self.vars.len();
+        let res = op(self);
+        self.vars.truncate(scope_start);
         res
     }
 }
 
 /// Translate an AST into a vec of instructions.
 pub fn translate(program: &[Ast]) -> Result<Vec<Instruction>, CodegenError> {
+    translate_with_features(program, CodegenFeatures::default())
+}
+
+/// Translate an AST into a vec of instructions, emitting only the optional opcodes enabled in
+/// `features`.
+pub fn translate_with_features(
+    program: &[Ast],
+    features: CodegenFeatures,
+) -> Result<Vec<Instruction>, CodegenError> {
+    translate_program(program, features).map(|(instructions, _)| instructions)
+}
+
+/// Translate an AST into a vec of instructions, along with a map of the types of the operand stack
+/// slots at each instruction.
+///
+/// The type map is only a debugging aid (e.g. for rendering booleans in traces, or for verifying
+/// the generated code at runtime with the VM's slot type verification), and has no effect on the
+/// generated code.
+pub fn translate_with_slot_types(
+    program: &[Ast],
+) -> Result<(Vec<Instruction>, SlotTypeMap), CodegenError> {
+    let (instructions, ctx) = translate_program(program, CodegenFeatures::default())?;
+    let types = infer_slot_types(&instructions, &ctx.literal_types);
+    let map = SlotTypeMap::from_instructions(&instructions, types);
+    Ok((instructions, map))
+}
+
+/// Translate a whole program, returning the final codegen context along with the instructions.
+fn translate_program(
+    program: &[Ast],
+    features: CodegenFeatures,
+) -> Result<(Vec<Instruction>, Context), CodegenError> {
     // Set up the preamble; we will change exactly how many variables to reserve after the rest of
     // the program is translated
     let mut instructions = vec![
@@ -69,13 +152,154 @@ pub fn translate(program: &[Ast]) -> Result<Vec<Instruction>, CodegenError> {
         Instruction::from(Opcode::VarRes),
     ];
 
-    let mut ctx = Context::default();
+    let mut ctx = Context {
+        features,
+        ..Context::default()
+    };
 
     translate_sequence(&mut ctx, &mut instructions, program)?;
 
     // Update the preamble
     instructions[0] = Instruction::optimal_push(ctx.max_vars as u64);
-    Ok(instructions)
+    Ok((instructions, ctx))
+}
+
+/// Determine the types of the stack slots before each of the given instructions.
+///
+/// This relies on the patterns in which codegen emits instructions: every push immediately
+/// followed by a variable access or jump is a variable index or jump offset, and all other pushes
+/// are literal values, whose types are given by `literal_types` in program order. All jumps are
+/// forward jumps.
+fn infer_slot_types(instrs: &[Instruction], literal_types: &[SlotType]) -> Vec<Vec<SlotType>> {
+    let mut literal_types = literal_types.iter().copied();
+    let mut types = Vec::with_capacity(instrs.len());
+
+    let mut stack = Vec::new();
+    let mut vars = Vec::new();
+
+    // The machine state at the targets of jumps already seen
+    let mut jump_targets: HashMap<usize, (Vec<SlotType>, Vec<SlotType>)> = HashMap::new();
+    let mut reachable = true;
+
+    let mut pc = 0;
+    for (i, instr) in instrs.iter().enumerate() {
+        // Code following an unconditional jump is only reached by jumping to it
+        if let Some(state) = jump_targets.remove(&pc) {
+            if !reachable {
+                (stack, vars) = state;
+            }
+        }
+        reachable = true;
+
+        types.push(stack.clone());
+
+        let prev_literal = i.checked_sub(1).map_or(0, |prev| instrs[prev].literal);
+        match instr.opcode {
+            Opcode::Push8
+            | Opcode::Push8S
+            | Opcode::Push16
+            | Opcode::Push16S
+            | Opcode::Push32
+            | Opcode::Push32S
+            | Opcode::Push64 => {
+                let is_operand = matches!(
+                    instrs.get(i + 1).map(|next| next.opcode),
+                    Some(
+                        Opcode::VarSt
+                            | Opcode::VarLd
+                            | Opcode::VarRes
+                            | Opcode::Jump
+                            | Opcode::JCond
+                    )
+                );
+
+                if is_operand {
+                    stack.push(SlotType::Int);
+                } else {
+                    stack.push(literal_types.next().unwrap_or(SlotType::Int));
+                }
+            }
+
+            Opcode::VarRes => {
+                stack.pop();
+                vars.resize(vars.len() + prev_literal as usize, SlotType::Int);
+            }
+
+            Opcode::VarSt => {
+                stack.pop();
+                let ty = stack.pop().unwrap_or(SlotType::Int);
+                if let Some(var) = vars.get_mut(prev_literal as usize) {
+                    *var = ty;
+                }
+            }
+
+            Opcode::VarLd => {
+                stack.pop();
+                let ty = vars.get(prev_literal as usize).copied();
+                stack.push(ty.unwrap_or(SlotType::Int));
+            }
+
+            Opcode::Jump | Opcode::JCond => {
+                stack.pop();
+                if instr.opcode == Opcode::JCond {
+                    stack.pop();
+                } else {
+                    reachable = false;
+                }
+
+                let target = (pc + 1).wrapping_add_signed(prev_literal as i64 as isize);
+                jump_targets.insert(target, (stack.clone(), vars.clone()));
+            }
+
+            Opcode::Select => {
+                let else_ty = stack.pop();
+                let then_ty = stack.pop();
+                stack.pop();
+                stack.push(match (then_ty, else_ty) {
+                    (Some(then_ty), Some(else_ty)) if then_ty == else_ty => then_ty,
+                    _ => SlotType::Int,
+                });
+            }
+
+            Opcode::AddCarry | Opcode::SubBorrow => {
+                stack.truncate(stack.len().saturating_sub(2));
+                stack.extend([SlotType::Int, SlotType::Bool]);
+            }
+
+            // Code following a halt is only reached by jumping to it
+            Opcode::Halt => reachable = false,
+
+            opcode => {
+                let (pops, result) = match opcode {
+                    Opcode::Print | Opcode::PrintF | Opcode::Assert => (1, None),
+                    Opcode::PrintStack => (0, None),
+                    Opcode::Not => (1, Some(SlotType::Bool)),
+                    Opcode::Inv => (1, Some(SlotType::Int)),
+                    Opcode::ModPow => (3, Some(SlotType::Int)),
+                    Opcode::Gt
+                    | Opcode::Lt
+                    | Opcode::Ge
+                    | Opcode::Le
+                    | Opcode::Eq
+                    | Opcode::Ne
+                    | Opcode::FGt
+                    | Opcode::FLt
+                    | Opcode::FEq
+                    | Opcode::BitTest
+                    | Opcode::LAnd
+                    | Opcode::LOr => (2, Some(SlotType::Bool)),
+                    _ => (2, Some(SlotType::Int)),
+                };
+
+                stack.truncate(stack.len().saturating_sub(pops));
+                stack.extend(result);
+            }
+        }
+
+        pc += instr.encoded_len();
+    }
+
+    types
 }
 
 /// Translate a sequence of instructions.
@@ -91,6 +315,28 @@ fn translate_sequence(
     Ok(())
 }
 
+/// Compute the offset of a forward jump over the given instructions.
+fn jump_distance(body: &[Instruction]) -> Result<i64, CodegenError> {
+    len_to_jump_distance(Instruction::checked_combined_len(body))
+}
+
+/// Convert the encoded length of a body of code, if it did not overflow, to a jump offset.
+fn len_to_jump_distance(len: Option<usize>) -> Result<i64, CodegenError> {
+    len.and_then(|len| i64::try_from(len).ok())
+        .ok_or(CodegenError::BodyTooLarge)
+}
+
+/// Construct an instruction pushing a jump offset, with a literal at most `max_literal_len` bytes
+/// wide.
+fn push_jump_offset(offset: i64, max_literal_len: usize) -> Result<Instruction, CodegenError> {
+    let push = Instruction::optimal_pushs(offset);
+    if push.opcode.literal_len() > max_literal_len {
+        return Err(CodegenError::JumpTooFar(offset));
+    }
+
+    Instruction::try_new(push.opcode, push.literal).map_err(|_| CodegenError::JumpTooFar(offset))
+}
+
 /// Translate a single AST node.
 fn translate_one(
     ctx: &mut Context,
@@ -113,11 +359,19 @@ fn translate_one(
 
         Ast::Int(val) => {
             instructions.push(Instruction::optimal_push(*val));
+            ctx.literal_types.push(SlotType::Int);
+            Ok(())
+        }
+
+        Ast::SInt(val) => {
+            instructions.push(Instruction::optimal_pushs(*val));
+            ctx.literal_types.push(SlotType::Int);
             Ok(())
         }
 
         Ast::Boolean(val) => {
             instructions.push(Instruction::optimal_push(*val as u64));
+            ctx.literal_types.push(SlotType::Bool);
             Ok(())
         }
 
@@ -147,22 +401,22 @@ fn translate_one(
             let mut else_instrs = Vec::new();
             ctx.in_new_scope(|ctx| translate_sequence(ctx, &mut else_instrs, else_body))?;
 
-            let else_body_len = Instruction::combined_len(&else_instrs);
+            let else_body_len = jump_distance(&else_instrs)?;
 
             // If there is a non-empty else clause, append instructions to the if clause to jump
             // over it.
             if else_body_len > 0 {
                 if_instrs.extend_from_slice(&[
-                    Instruction::optimal_pushs(else_body_len as i64),
+                    push_jump_offset(else_body_len, MAX_JUMP_LITERAL_LEN)?,
                     Instruction::from(Opcode::Jump),
                 ]);
             }
 
-            let if_body_len = Instruction::combined_len(&if_instrs);
+            let if_body_len = jump_distance(&if_instrs)?;
 
             instructions.extend_from_slice(&[
                 Instruction::from(Opcode::Not),
-                Instruction::optimal_pushs(if_body_len as i64),
+                push_jump_offset(if_body_len, MAX_JUMP_LITERAL_LEN)?,
                 Instruction::from(Opcode::JCond),
             ]);
 
@@ -175,7 +429,7 @@ fn translate_one(
         Ast::Binop { sym, lhs, rhs } => {
             translate_one(ctx, instructions, lhs)?;
             translate_one(ctx, instructions, rhs)?;
-            append_binop_instrs(instructions, *sym);
+            append_binop_instrs(instructions, *sym, ctx.features);
             Ok(())
         }
 
@@ -190,31 +444,49 @@ fn translate_one(
             instructions.push(Instruction::from(Opcode::Print));
             Ok(())
         }
+
+        Ast::Assert(cond) => {
+            translate_one(ctx, instructions, cond)?;
+            instructions.push(Instruction::from(Opcode::Assert));
+            Ok(())
+        }
+
+        Ast::Halt => {
+            instructions.push(Instruction::from(Opcode::Halt));
+            Ok(())
+        }
     }
 }
 
 /// Append instructions to the given vec implementing the given binop.
 ///
-/// Each binary operator in the language has a single corresponding opcode, except for `!=`, which
-/// requires two.
-fn append_binop_instrs(instrs: &mut Vec<Instruction>, op: BinopSym) {
+/// Each binary operator in the language has a single corresponding opcode, though some are only
+/// emitted if enabled in `features`.
+fn append_binop_instrs(instrs: &mut Vec<Instruction>, op: BinopSym, features: CodegenFeatures) {
     match op {
         BinopSym::Plus => instrs.push(Instruction::from(Opcode::Add)),
         BinopSym::Minus => instrs.push(Instruction::from(Opcode::Sub)),
         BinopSym::Mul => instrs.push(Instruction::from(Opcode::Mul)),
         BinopSym::Div => instrs.push(Instruction::from(Opcode::Div)),
         BinopSym::Mod => instrs.push(Instruction::from(Opcode::Mod)),
+        BinopSym::Pow => instrs.push(Instruction::from(Opcode::Pow)),
         BinopSym::Greater => instrs.push(Instruction::from(Opcode::Gt)),
         BinopSym::Less => instrs.push(Instruction::from(Opcode::Lt)),
         BinopSym::GreaterEq => instrs.push(Instruction::from(Opcode::Ge)),
         BinopSym::LessEq => instrs.push(Instruction::from(Opcode::Le)),
         BinopSym::Eq => instrs.push(Instruction::from(Opcode::Eq)),
-        BinopSym::NEq => instrs.extend_from_slice(&[
-            Instruction::from(Opcode::Eq),
-            Instruction::from(Opcode::Not),
-        ]),
-        BinopSym::BitAnd | BinopSym::LogAnd => instrs.push(Instruction::from(Opcode::And)),
-        BinopSym::BitOr | BinopSym::LogOr => instrs.push(Instruction::from(Opcode::Or)),
+        BinopSym::NEq if features.ne => instrs.push(Instruction::from(Opcode::Ne)),
+        BinopSym::NEq => instrs.extend([Opcode::Eq, Opcode::Not].map(Instruction::from)),
+        BinopSym::BitAnd => instrs.push(Instruction::from(Opcode::And)),
+        BinopSym::BitOr => instrs.push(Instruction::from(Opcode::Or)),
+        BinopSym::LogAnd if features.logical_connectives => {
+            instrs.push(Instruction::from(Opcode::LAnd))
+        }
+        BinopSym::LogOr if features.logical_connectives => {
+            instrs.push(Instruction::from(Opcode::LOr))
+        }
+        BinopSym::LogAnd => instrs.push(Instruction::from(Opcode::And)),
+        BinopSym::LogOr => instrs.push(Instruction::from(Opcode::Or)),
         BinopSym::BitXor => instrs.push(Instruction::from(Opcode::Xor)),
     };
 }
@@ -532,6 +804,55 @@ mod test {
         assert!(output.is_empty());
     }
 
+    #[test]
+    fn jump_distance_overflow() {
+        let body = [
+            Instruction::optimal_push(300),
+            Instruction::from(Opcode::Jump),
+        ];
+        assert_eq!(jump_distance(&body).unwrap(), 4);
+        assert_eq!(jump_distance(&[]).unwrap(), 0);
+
+        // No real body is large enough to overflow, so the length is given directly
+        assert_eq!(
+            len_to_jump_distance(Some(i64::MAX as usize)).unwrap(),
+            i64::MAX
+        );
+        assert!(matches!(
+            len_to_jump_distance(Some(i64::MAX as usize + 1)),
+            Err(CodegenError::BodyTooLarge)
+        ));
+        assert!(matches!(
+            len_to_jump_distance(None),
+            Err(CodegenError::BodyTooLarge)
+        ));
+    }
+
+    #[test]
+    fn jump_too_far() {
+        assert_eq!(
+            push_jump_offset(127, 1).unwrap(),
+            Instruction::new(Opcode::Push8S, 127)
+        );
+        assert_eq!(
+            push_jump_offset(-300, 2).unwrap(),
+            Instruction::new(Opcode::Push16S, -300_i64 as u64)
+        );
+        assert_eq!(
+            push_jump_offset(i64::MAX, MAX_JUMP_LITERAL_LEN).unwrap(),
+            Instruction::new(Opcode::Push64, i64::MAX as u64)
+        );
+
+        assert!(matches!(
+            push_jump_offset(128, 1),
+            Err(CodegenError::JumpTooFar(128))
+        ));
+        assert!(matches!(
+            push_jump_offset(1 << 31, 4),
+            Err(CodegenError::JumpTooFar(offset)) if offset == 1 << 31
+        ));
+    }
+
     #[test]
     fn var_out_of_scope() {
         // a = 4
@@ -564,6 +885,412 @@ mod test {
         assert!(matches!(err, CodegenError::UndeclaredVariable(name) if name == "b"));
     }
 
+    #[test]
+    fn not_equal_single_instruction() {
+        // a = 4
+        // b = 5
+        // print a != b
+
+        let program = &[
+            Ast::assign("a", Ast::Int(4)),
+            Ast::assign("b", Ast::Int(5)),
+            Ast::print(Ast::binop(BinopSym::NEq, Ast::var("a"), Ast::var("b"))),
+        ];
+
+        let instructions = translate(program).expect("Failed to translate AST");
+
+        assert_eq!(
+            &instructions[instructions.len() - 6..],
+            &[
+                Instruction::new(Push8, 0),
+                Instruction::from(VarLd),
+                Instruction::new(Push8, 1),
+                Instruction::from(VarLd),
+                Instruction::from(Ne),
+                Instruction::from(Print),
+            ]
+        );
+
+        let bytes = instructions_to_vec(&instructions);
+        let mut output = Vec::<u8>::new();
+        let _summary = ExecutionContext::new(&bytes)
+            .with_output_stream(&mut output)
+            .run()
+            .expect("Runtime error");
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output, "1\n");
+    }
+
+    #[test]
+    fn core_features() {
+        let program = crate::parse::parse("a = 4; b = 5; print a != b; print a < b && a > b;")
+            .expect("Parsing failed");
+
+        let core = translate_with_features(&program, CodegenFeatures::core())
+            .expect("Failed to translate AST");
+        let full = translate_with_features(&program, CodegenFeatures::default())
+            .expect("Failed to translate AST");
+        assert_eq!(full, translate(&program).unwrap());
+
+        // a != b
+        assert_eq!(
+            &core[12..15],
+            &[
+                Instruction::from(Eq),
+                Instruction::from(Not),
+                Instruction::from(Print)
+            ]
+        );
+        assert_eq!(
+            &full[12..14],
+            &[Instruction::from(Ne), Instruction::from(Print)]
+        );
+
+        // a < b && a > b
+        assert_eq!(
+            &core[core.len() - 2..],
+            &[Instruction::from(And), Instruction::from(Print)]
+        );
+        assert_eq!(
+            &full[full.len() - 2..],
+            &[Instruction::from(LAnd), Instruction::from(Print)]
+        );
+
+        for instructions in [core, full] {
+            let summary = ExecutionContext::from_instructions(&instructions)
+                .with_captured_output()
+                .run()
+                .expect("VM encountered a runtime error");
+            assert_eq!(summary.output.as_deref(), Some("1\n0\n"));
+        }
+    }
+
+    #[test]
+    fn exponentiation() {
+        // print 2 ** 10
+        // print 2 ** 3 ** 2
+
+        let program = &[
+            Ast::print(Ast::pow(Ast::Int(2), Ast::Int(10))),
+            Ast::print(Ast::pow(Ast::Int(2), Ast::pow(Ast::Int(3), Ast::Int(2)))),
+        ];
+
+        let instructions = translate(program).expect("Failed to translate AST");
+        let bytes = instructions_to_vec(&instructions);
+        let mut output = Vec::<u8>::new();
+        let _summary = ExecutionContext::new(&bytes)
+            .with_output_stream(&mut output)
+            .run()
+            .expect("Runtime error");
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output, "1024\n512\n");
+    }
+
+    #[test]
+    fn assert() {
+        let program = crate::parse::parse("a = 3; assert a == 3; print a; assert a > 5; print 0;")
+            .expect("Parsing failed");
+
+        let instructions = translate(&program).expect("Failed to translate AST");
+        let bytes = instructions_to_vec(&instructions);
+        let mut output = Vec::<u8>::new();
+        let err = ExecutionContext::new(&bytes)
+            .with_output_stream(&mut output)
+            .run()
+            .expect_err("Failing assertion did not halt execution");
+
+        assert_eq!(err.kind, hypescript_vm::error::ErrorKind::AssertionFailed);
+        assert_eq!(
+            bytes[err.program_counter],
+            hypescript_bytecode::consts::ASSERT
+        );
+
+        // The first assertion passes, and the second halts execution before the final print
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output, "3\n");
+    }
+
+    #[test]
+    fn run_instructions() {
+        let program = crate::parse::parse("a = 6; b = if a > 4 { a * 7 } else { 0 }; print b;")
+            .expect("Parsing failed");
+
+        let instructions = translate(&program).expect("Failed to translate AST");
+        let summary = ExecutionContext::from_instructions(&instructions)
+            .with_captured_output()
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.output.as_deref(), Some("42\n"));
+        assert_eq!(
+            summary.program_counter,
+            Instruction::combined_len(&instructions)
+        );
+    }
+
+    #[test]
+    fn halt() {
+        let program = crate::parse::parse("a = 3; if a > 2 { print 1; halt; } print a;")
+            .expect("Parsing failed");
+
+        let instructions = translate(&program).expect("Failed to translate AST");
+        let bytes = instructions_to_vec(&instructions);
+        let summary = ExecutionContext::new(&bytes)
+            .with_captured_output()
+            .run()
+            .expect("VM encountered a runtime error");
+
+        // The halt stops execution before the final print
+        assert_eq!(summary.output.as_deref(), Some("1\n"));
+        assert_eq!(
+            bytes[summary.program_counter],
+            hypescript_bytecode::consts::HALT
+        );
+
+        // When the condition is false, the halt is skipped
+        let program = crate::parse::parse("a = 1; if a > 2 { print 1; halt; } print a;")
+            .expect("Parsing failed");
+        let bytes = instructions_to_vec(&translate(&program).expect("Failed to translate AST"));
+        let summary = ExecutionContext::new(&bytes)
+            .with_captured_output()
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.output.as_deref(), Some("1\n"));
+    }
+
+    #[test]
+    fn signed_literal() {
+        let program = crate::parse::parse("a = -5; print a + 7;").expect("Parsing failed");
+
+        let instructions = translate(&program).expect("Failed to translate AST");
+        assert!(instructions.contains(&Instruction::new(Opcode::Push8S, -5i64 as u64)));
+
+        let bytes = instructions_to_vec(&instructions);
+        let summary = ExecutionContext::new(&bytes)
+            .with_captured_output()
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.output.as_deref(), Some("2\n"));
+    }
+
+    #[test]
+    fn print_multiple_values() {
+        let program = crate::parse::parse("print 1, 2, 3; a = 5; print a * 2, a > 3;")
+            .expect("Parsing failed");
+
+        let instructions = translate(&program).expect("Failed to translate AST");
+        let bytes = instructions_to_vec(&instructions);
+        let mut output = Vec::<u8>::new();
+        let _summary = ExecutionContext::new(&bytes)
+            .with_output_stream(&mut output)
+            .run()
+            .expect("Runtime error");
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output, "1\n2\n3\n10\n1\n");
+    }
+
+    #[test]
+    fn slot_types() {
+        use hypescript_bytecode::slot_types::SlotType::*;
+        use hypescript_vm::trace::{format_trace, format_trace_typed, TraceFormat};
+
+        let program = crate::parse::parse(
+            "a = 3 > 2; b = if a { 1 } else { 2 }; c = !a || true; d = c; print b + 1;",
+        )
+        .expect("Parsing failed");
+
+        let (instructions, types) =
+            translate_with_slot_types(&program).expect("Failed to translate AST");
+        assert_eq!(instructions, translate(&program).unwrap());
+
+        let bytes = instructions_to_vec(&instructions);
+        let summary = ExecutionContext::new(&bytes)
+            .with_output_stream(Vec::new())
+            .with_trace()
+            .run()
+            .expect("Runtime error");
+        let trace = summary.trace.unwrap();
+
+        // Every executed instruction has a type for each stack slot
+        for snapshot in &trace {
+            let slot_types = types.get(snapshot.program_counter).unwrap();
+            assert_eq!(slot_types.len(), snapshot.stack.len(), "{snapshot}");
+        }
+
+        // a = 3 > 2
+        assert_eq!(trace[5].next_instruction, Instruction::new(Push8, 0));
+        assert_eq!(types.get(trace[5].program_counter), Some(&[Bool][..]));
+
+        // Find the step storing to the given variable
+        let store = |idx| {
+            let pos = trace.windows(2).position(|w| {
+                w[0].next_instruction == Instruction::new(Push8, idx)
+                    && w[1].next_instruction == Instruction::from(VarSt)
+            });
+            trace[pos.unwrap()].program_counter
+        };
+
+        // b = if a { 1 } else { 2 }
+        assert_eq!(types.get(store(1)), Some(&[Int][..]));
+
+        // d = c, where c is a boolean variable
+        assert_eq!(types.get(store(3)), Some(&[Bool][..]));
+
+        let mut typed = String::new();
+        format_trace_typed(&mut typed, &trace, &types, TraceFormat::Full).unwrap();
+        assert!(typed.contains("\t\ttrue\n"));
+
+        let mut typed_decimal = String::new();
+        format_trace_typed(&mut typed_decimal, &trace, &types, TraceFormat::DecimalOnly).unwrap();
+        assert!(typed_decimal.contains(": true\n"));
+        assert!(!typed_decimal.contains("\t\ttrue\n"));
+        assert_eq!(typed_decimal.lines().count(), typed.lines().count());
+
+        let mut untyped = String::new();
+        format_trace(&mut untyped, &trace).unwrap();
+        assert!(!untyped.contains("true"));
+        assert_eq!(typed.lines().count(), untyped.lines().count());
+    }
+
+    #[test]
+    fn slot_type_verification() {
+        use hypescript_vm::error::ErrorKind;
+
+        let program = crate::parse::parse("a = 3 > 2; if a { print 1; } assert(!a || a);")
+            .expect("Parsing failed");
+
+        let (instructions, types) =
+            translate_with_slot_types(&program).expect("Failed to translate AST");
+        let bytes = instructions_to_vec(&instructions);
+        ExecutionContext::new(&bytes)
+            .with_output_stream(Vec::new())
+            .with_slot_type_verification(types)
+            .run()
+            .expect("Verification failed for correct program");
+
+        // Simulate a miscompilation negating the if condition bitwise rather than logically
+        let (mut instructions, ctx) =
+            translate_program(&program, CodegenFeatures::default()).unwrap();
+        let not = instructions
+            .iter()
+            .position(|instr| instr.opcode == Opcode::Not)
+            .unwrap();
+        instructions[not] = Instruction::from(Opcode::Inv);
+
+        let types = infer_slot_types(&instructions, &ctx.literal_types);
+        let types = SlotTypeMap::from_instructions(&instructions, types);
+        let bytes = instructions_to_vec(&instructions);
+        let err = ExecutionContext::new(&bytes)
+            .with_output_stream(Vec::new())
+            .with_slot_type_verification(types)
+            .run()
+            .expect_err("Verification succeeded for type-confused program");
+        assert_eq!(err.kind, ErrorKind::SlotTypeMismatch);
+        assert_eq!(err.instr, Some(Instruction::from(Opcode::JCond)));
+    }
+
+    #[test]
+    fn logical_connectives() {
+        // print (1 < 2) && (3 > 4) || true
+
+        let program = &[Ast::print(Ast::log_or(
+            Ast::log_and(
+                Ast::less(Ast::Int(1), Ast::Int(2)),
+                Ast::greater(Ast::Int(3), Ast::Int(4)),
+            ),
+            Ast::Boolean(true),
+        ))];
+
+        let instructions = translate(program).expect("Failed to translate AST");
+
+        let expected = &[
+            Instruction::new(Push8, 0),
+            Instruction::from(VarRes),
+            Instruction::new(Push8, 1),
+            Instruction::new(Push8, 2),
+            Instruction::from(Lt),
+            Instruction::new(Push8, 3),
+            Instruction::new(Push8, 4),
+            Instruction::from(Gt),
+            Instruction::from(LAnd),
+            Instruction::new(Push8, 1),
+            Instruction::from(LOr),
+            Instruction::from(Print),
+        ];
+
+        assert_eq!(expected, instructions.as_slice());
+
+        let bytes = instructions_to_vec(&instructions);
+        let mut output = Vec::<u8>::new();
+        let _summary = ExecutionContext::new(&bytes)
+            .with_output_stream(&mut output)
+            .run()
+            .expect("Runtime error");
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output, "1\n");
+    }
+
+    #[test]
+    fn deep_nesting() {
+        // v0 = 0
+        // {
+        //     v1 = v0 + 1
+        //     {
+        //         ...
+        //         print vN
+        //     }
+        // }
+        // {
+        //     w = 1
+        //     print w
+        // }
+        const DEPTH: usize = 300;
+
+        let mut inner = vec![Ast::print(Ast::var(format!("v{DEPTH}")))];
+        for i in (1..=DEPTH).rev() {
+            let assign = Ast::assign(
+                format!("v{i}"),
+                Ast::plus(Ast::var(format!("v{}", i - 1)), Ast::Int(1)),
+            );
+            inner.insert(0, assign);
+            inner = vec![Ast::Block(inner)];
+        }
+
+        let mut program = vec![Ast::assign("v0", Ast::Int(0))];
+        program.extend(inner);
+        program.push(Ast::Block(vec![
+            Ast::assign("w", Ast::Int(1)),
+            Ast::print(Ast::var("w")),
+        ]));
+
+        let instructions = translate(&program).expect("Codegen failed");
+        assert_eq!(
+            &instructions[0..2],
+            &[
+                Instruction::new(Push16, DEPTH as u64 + 1),
+                Instruction::from(VarRes)
+            ]
+        );
+
+        // The sibling scope reuses the first slot freed by the nested scopes
+        let w_store = instructions.len() - 5;
+        assert_eq!(
+            &instructions[w_store..w_store + 2],
+            &[Instruction::new(Push8, 1), Instruction::from(VarSt)]
+        );
+
+        let bytecode = instructions_to_vec(&instructions);
+        let mut output = Vec::<u8>::new();
+        ExecutionContext::new(&bytecode)
+            .with_output_stream(&mut output)
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(String::from_utf8(output).unwrap(), format!("{DEPTH}\n1\n"));
+    }
+
     #[test]
     fn minimal_vars_reserved() {
         // Three distinct variable names, but only a maximum of two in scope at any point. Preamble
@@ -1,10 +1,14 @@
 //! A parser for HypeScript.
 
+// Chumsky's `Simple` error type is large, and is what all of our parser closures must return.
+#![allow(clippy::result_large_err)]
+
 use std::fmt::{self, Display, Formatter};
+use std::num::{IntErrorKind, ParseIntError};
 
 use chumsky::prelude::*;
 
-use crate::ast::{Ast, BinopSym, UnopSym};
+use crate::ast::{Ast, BinopSym, Span, Spanned, UnopSym};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Tok {
@@ -13,6 +17,7 @@ pub enum Tok {
     Ident(String),
     HexInt(String),
     DecInt(String),
+    Char(char),
     Binop(BinopSym),
     Unop(UnopSym),
     Punct(Punct),
@@ -26,6 +31,7 @@ impl Display for Tok {
             Tok::Ident(id) => write!(f, "{id}"),
             Tok::HexInt(n) => write!(f, "{n}"),
             Tok::DecInt(n) => write!(f, "{n}"),
+            Tok::Char(c) => write!(f, "{c:?}"),
             Tok::Binop(op) => write!(f, "{op}"),
             Tok::Unop(op) => write!(f, "{op}"),
             Tok::Punct(punct) => write!(f, "{punct}"),
@@ -38,6 +44,8 @@ pub enum Kw {
     If,
     Else,
     Print,
+    Assert,
+    Halt,
 }
 
 impl Display for Kw {
@@ -46,6 +54,8 @@ impl Display for Kw {
             Kw::If => write!(f, "if"),
             Kw::Else => write!(f, "else"),
             Kw::Print => write!(f, "print"),
+            Kw::Assert => write!(f, "assert"),
+            Kw::Halt => write!(f, "halt"),
         }
     }
 }
@@ -53,6 +63,7 @@ impl Display for Kw {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Punct {
     Semi,
+    Comma,
     Eq,
     OBrace,
     CBrace,
@@ -64,6 +75,7 @@ impl Display for Punct {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Punct::Semi => write!(f, ";"),
+            Punct::Comma => write!(f, ","),
             Punct::Eq => write!(f, "="),
             Punct::OBrace => write!(f, "{{"),
             Punct::CBrace => write!(f, "}}"),
@@ -93,6 +105,11 @@ enum BindingStrength {
 
     /// Strongly-binding arithmetic operators: `*`, `/`, `%`
     ArithStrong,
+
+    /// Exponentiation: `**`
+    ///
+    /// Unlike all other binary operators, this is right-associative.
+    Pow,
 }
 
 impl BindingStrength {
@@ -100,6 +117,7 @@ impl BindingStrength {
         use BindingStrength::*;
 
         match sym {
+            BinopSym::Pow => Pow,
             BinopSym::Mul | BinopSym::Div | BinopSym::Mod => ArithStrong,
             BinopSym::BitOr | BinopSym::BitAnd | BinopSym::BitXor => ArithMid,
             BinopSym::Plus | BinopSym::Minus => ArithWeak,
@@ -121,7 +139,8 @@ impl BindingStrength {
             BindingStrength::Comp => Some(Self::ArithWeak),
             BindingStrength::ArithWeak => Some(Self::ArithMid),
             BindingStrength::ArithMid => Some(Self::ArithStrong),
-            BindingStrength::ArithStrong => None,
+            BindingStrength::ArithStrong => Some(Self::Pow),
+            BindingStrength::Pow => None,
         }
     }
 }
@@ -131,6 +150,8 @@ fn ident_or_kw() -> impl Parser<char, Tok, Error = Simple<char>> {
         "if" => Tok::Kw(Kw::If),
         "else" => Tok::Kw(Kw::Else),
         "print" => Tok::Kw(Kw::Print),
+        "assert" => Tok::Kw(Kw::Assert),
+        "halt" => Tok::Kw(Kw::Halt),
         "true" => Tok::Bool(true),
         "false" => Tok::Bool(false),
         _ => Tok::Ident(id),
@@ -143,10 +164,39 @@ fn int_tok() -> impl Parser<char, Tok, Error = Simple<char>> {
     hex_int.or(dec_int)
 }
 
+fn char_tok() -> impl Parser<char, Tok, Error = Simple<char>> {
+    let hex_escape = just('x')
+        .ignore_then(
+            filter(|c: &char| c.is_ascii_hexdigit())
+                .repeated()
+                .exactly(2)
+                .collect::<String>(),
+        )
+        .map(|digits| char::from(u8::from_str_radix(&digits, 16).unwrap()));
+
+    let escape = just('\\').ignore_then(choice((
+        just('n').to('\n'),
+        just('t').to('\t'),
+        just('r').to('\r'),
+        just('0').to('\0'),
+        just('\\'),
+        just('\''),
+        hex_escape,
+    )));
+
+    let plain = filter(|c: &char| !matches!(c, '\\' | '\'' | '\n'));
+
+    escape
+        .or(plain)
+        .delimited_by(just('\''), just('\''))
+        .map(Tok::Char)
+}
+
 fn binop() -> impl Parser<char, Tok, Error = Simple<char>> {
     choice((
         just("+").to(BinopSym::Plus),
         just("-").to(BinopSym::Minus),
+        just("**").to(BinopSym::Pow),
         just("*").to(BinopSym::Mul),
         just("/").to(BinopSym::Div),
         just("%").to(BinopSym::Mod),
@@ -175,6 +225,7 @@ fn unop() -> impl Parser<char, Tok, Error = Simple<char>> {
 fn punct() -> impl Parser<char, Tok, Error = Simple<char>> {
     choice((
         just(";").to(Punct::Semi),
+        just(",").to(Punct::Comma),
         just("=").to(Punct::Eq),
         just("{").to(Punct::OBrace),
         just("}").to(Punct::CBrace),
@@ -184,35 +235,98 @@ fn punct() -> impl Parser<char, Tok, Error = Simple<char>> {
     .map(Tok::Punct)
 }
 
-fn comment() -> impl Parser<char, Option<Tok>, Error = Simple<char>> {
-    let line_comment = just("//").ignore_then(take_until(just("\n")));
-    let block_comment = just("/*").ignore_then(take_until(just("*/")));
+/// Configuration options for the lexer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LexerOptions {
+    /// Allow block comments to nest.
+    ///
+    /// If enabled, each `/*` within a block comment opens a nested comment, and the comment only
+    /// ends once every nested comment has been closed with a matching `*/`. By default, block
+    /// comments do not nest, and end at the first `*/`.
+    pub nested_comments: bool,
+}
+
+fn nested_block_comment() -> impl Parser<char, (), Error = Simple<char>> {
+    // Yields whether the comment was closed; an unclosed comment extends to the end of input
+    let comment = recursive(|nested| {
+        just("/*")
+            .ignore_then(nested.or(just("*/").not().to(true)).repeated())
+            .ignore_then(just("*/").to(true).or(end().to(false)))
+    });
+
+    comment.validate(|closed, span, emit| {
+        if !closed {
+            emit(Simple::custom(span, "unterminated block comment"))
+        }
+    })
+}
+
+fn comment(options: LexerOptions) -> impl Parser<char, Option<(Tok, Span)>, Error = Simple<char>> {
+    let line_comment = just("//").ignore_then(take_until(just("\n"))).ignored();
+    let block_comment = if options.nested_comments {
+        nested_block_comment().boxed()
+    } else {
+        just("/*")
+            .ignore_then(take_until(just("*/")))
+            .ignored()
+            .boxed()
+    };
     line_comment.or(block_comment).to(None)
 }
 
-pub fn lexer() -> impl Parser<char, Vec<Tok>, Error = Simple<char>> {
-    let tok = choice((ident_or_kw(), int_tok(), binop(), unop(), punct()))
-        .padded()
-        .map(Some);
+/// The lexer, producing tokens along with their spans in the source text.
+pub fn lexer() -> impl Parser<char, Vec<(Tok, Span)>, Error = Simple<char>> {
+    lexer_with_options(LexerOptions::default())
+}
+
+/// The lexer, configured with the given options.
+pub fn lexer_with_options(
+    options: LexerOptions,
+) -> impl Parser<char, Vec<(Tok, Span)>, Error = Simple<char>> {
+    let tok = choice((
+        ident_or_kw(),
+        int_tok(),
+        char_tok(),
+        binop(),
+        unop(),
+        punct(),
+    ))
+    .map_with_span(|tok, span| Some((tok, span)))
+    .padded();
+
+    // With nesting enabled, `/*` always begins a comment, so that an unterminated comment is an
+    // error rather than being lexed as operators
+    let tok = if options.nested_comments {
+        just("/*").not().rewind().ignore_then(tok).boxed()
+    } else {
+        tok.boxed()
+    };
 
     text::whitespace()
-        .ignore_then(comment().padded().or(tok).repeated())
+        .ignore_then(comment(options).padded().or(tok).repeated())
         .flatten()
         .then_ignore(end())
 }
 
+/// A single statement; print statements with multiple values yield one `Ast::Print` per value.
 fn statement<'a>(
-    expr: Recursive<'a, Tok, Ast, Simple<Tok>>,
-) -> impl Parser<Tok, Ast, Error = Simple<Tok>> + 'a {
-    choice((assignment(expr.clone()), print(expr.clone()), expr))
+    expr: Recursive<'a, Tok, Spanned<Ast>, Simple<Tok>>,
+) -> impl Parser<Tok, Vec<Ast>, Error = Simple<Tok>> + 'a {
+    choice((
+        assignment(expr.clone()).map(|ast| vec![ast]),
+        print(expr.clone()),
+        assert(expr.clone()).map(|ast| vec![ast]),
+        halt().map(|ast| vec![ast]),
+        expr.map(|ast| vec![ast.node]),
+    ))
 }
 
 fn assignment(
-    expr: Recursive<Tok, Ast, Simple<Tok>>,
+    expr: Recursive<'_, Tok, Spanned<Ast>, Simple<Tok>>,
 ) -> impl Parser<Tok, Ast, Error = Simple<Tok>> + '_ {
     let var = filter_map(|span, tok| {
         if let Tok::Ident(v) = tok {
-            Ok(v)
+            Ok((v, span))
         } else {
             Err(Simple::custom(span, "expected variable name"))
         }
@@ -221,47 +335,80 @@ fn assignment(
     var.then_ignore(just(&[Tok::Punct(Punct::Eq)]))
         .then(expr)
         .then_ignore(just(&[Tok::Punct(Punct::Semi)]))
-        .map(|(v, exp)| Ast::assign(v, exp))
+        .map(|((v, var_span), exp)| Ast::Assign {
+            var: Spanned::new(v, var_span),
+            value: Box::new(exp),
+        })
 }
 
 fn print(
-    expr: Recursive<Tok, Ast, Simple<Tok>>,
-) -> impl Parser<Tok, Ast, Error = Simple<Tok>> + '_ {
+    expr: Recursive<'_, Tok, Spanned<Ast>, Simple<Tok>>,
+) -> impl Parser<Tok, Vec<Ast>, Error = Simple<Tok>> + '_ {
     just(&[Tok::Kw(Kw::Print)])
+        .ignore_then(
+            expr.separated_by(just(&[Tok::Punct(Punct::Comma)]))
+                .at_least(1),
+        )
+        .then_ignore(just(&[Tok::Punct(Punct::Semi)]))
+        .map(|vals| {
+            vals.into_iter()
+                .map(|val| Ast::Print(Box::new(val)))
+                .collect()
+        })
+}
+
+fn assert(
+    expr: Recursive<'_, Tok, Spanned<Ast>, Simple<Tok>>,
+) -> impl Parser<Tok, Ast, Error = Simple<Tok>> + '_ {
+    just(&[Tok::Kw(Kw::Assert)])
         .ignore_then(expr)
         .then_ignore(just(&[Tok::Punct(Punct::Semi)]))
-        .map(Ast::print)
+        .map(|cond| Ast::Assert(Box::new(cond)))
+}
+
+fn halt() -> impl Parser<Tok, Ast, Error = Simple<Tok>> {
+    just(&[Tok::Kw(Kw::Halt)])
+        .then_ignore(just(&[Tok::Punct(Punct::Semi)]))
+        .to(Ast::Halt)
 }
 
 fn if_chain<'a>(
-    expr: Recursive<'a, Tok, Ast, Simple<Tok>>,
-) -> impl Parser<Tok, Ast, Error = Simple<Tok>> + 'a {
+    expr: Recursive<'a, Tok, Spanned<Ast>, Simple<Tok>>,
+) -> impl Parser<Tok, Spanned<Ast>, Error = Simple<Tok>> + 'a {
     recursive(|if_chain| {
         let if_clause = just(&[Tok::Kw(Kw::If)])
             .ignore_then(expr.clone())
             .then(block(expr.clone()));
 
         let else_if_clauses = just(&[Tok::Kw(Kw::Else)])
-            .ignore_then(if_chain.map(|ast| vec![ast]).or(block(expr)))
+            .ignore_then(
+                if_chain
+                    .map(|ast: Spanned<_>| vec![ast.node])
+                    .or(block(expr)),
+            )
             .or_not();
 
         if_clause
             .then(else_if_clauses)
-            .map(|((cond, body), else_clause)| {
-                let else_body = else_clause.unwrap_or_else(|| vec![]);
-                Ast::if_cond(cond, body, else_body)
+            .map_with_span(|((cond, body), else_clause), span| {
+                let if_cond = Ast::IfCond {
+                    cond: Box::new(cond),
+                    body,
+                    else_body: else_clause.unwrap_or_default(),
+                };
+                Spanned::new(if_cond, span)
             })
     })
 }
 
 fn seq<'a>(
-    expr: Recursive<'a, Tok, Ast, Simple<Tok>>,
+    expr: Recursive<'a, Tok, Spanned<Ast>, Simple<Tok>>,
 ) -> impl Parser<Tok, Vec<Ast>, Error = Simple<Tok>> + 'a {
-    statement(expr).repeated()
+    statement(expr).repeated().flatten()
 }
 
 fn block(
-    expr: Recursive<Tok, Ast, Simple<Tok>>,
+    expr: Recursive<'_, Tok, Spanned<Ast>, Simple<Tok>>,
 ) -> impl Parser<Tok, Vec<Ast>, Error = Simple<Tok>> + '_ {
     seq(expr).delimited_by(
         just(&[Tok::Punct(Punct::OBrace)]),
@@ -270,8 +417,8 @@ fn block(
 }
 
 fn unop_factor(
-    factor: Recursive<Tok, Ast, Simple<Tok>>,
-) -> impl Parser<Tok, Ast, Error = Simple<Tok>> + '_ {
+    factor: Recursive<'_, Tok, Spanned<Ast>, Simple<Tok>>,
+) -> impl Parser<Tok, Spanned<Ast>, Error = Simple<Tok>> + '_ {
     let op = filter_map(|span, tok| {
         if let Tok::Unop(sym) = tok {
             Ok(sym)
@@ -280,27 +427,80 @@ fn unop_factor(
         }
     });
 
-    op.then(factor).map(|(sym, val)| Ast::unop(sym, val))
+    op.then(factor).map_with_span(|(sym, operand), span| {
+        let unop = Ast::Unop {
+            sym,
+            operand: Box::new(operand),
+        };
+        Spanned::new(unop, span)
+    })
 }
 
-fn factor(
-    expr: Recursive<Tok, Ast, Simple<Tok>>,
-) -> impl Parser<Tok, Ast, Error = Simple<Tok>> + '_ {
-    let lit_or_var = select! { |span|
-        Tok::HexInt(s) => {
-            let val = u64::from_str_radix(&s, 16).map_err(|e| Simple::custom(span, e))?;
-            Ast::Int(val)
-        },
+/// Build the error for an integer literal that cannot be parsed as a 64-bit value.
+///
+/// `text` is the literal as written in the source, including any radix prefix.
+fn int_literal_error(span: Span, text: &str, err: ParseIntError) -> Simple<Tok> {
+    match err.kind() {
+        IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => Simple::custom(
+            span,
+            format!("integer literal too large for 64-bit value: {text}"),
+        ),
+        _ => Simple::custom(span, err),
+    }
+}
 
+fn factor(
+    expr: Recursive<'_, Tok, Spanned<Ast>, Simple<Tok>>,
+) -> impl Parser<Tok, Spanned<Ast>, Error = Simple<Tok>> + '_ {
+    // Integer literals are range-checked after the token is accepted, so that an out-of-range
+    // literal is reported as such rather than as an unexpected token.
+    let int_lit = select! {
+        Tok::HexInt(s) => (format!("0x{s}"), u64::from_str_radix(&s, 16)),
         Tok::DecInt(s) => {
-            let val = s.parse::<u64>().map_err(|e| Simple::custom(span, e))?;
-            Ast::Int(val)
+            let val = s.parse::<u64>();
+            (s, val)
         },
+    }
+    .try_map(|(text, val), span| {
+        val.map(Ast::Int)
+            .map_err(|e| int_literal_error(span, &text, e))
+    });
 
-        Tok::Bool(b) => Ast::Boolean(b),
+    // A minus sign immediately before an integer literal, with no whitespace between them, makes
+    // it a signed literal
+    let neg_int_lit = just(&[Tok::Binop(BinopSym::Minus)])
+        .map_with_span(|_, span: Span| span.end)
+        .then(
+            select! {
+                Tok::HexInt(s) => (format!("-0x{s}"), i64::from_str_radix(&format!("-{s}"), 16)),
+                Tok::DecInt(s) => {
+                    let val = format!("-{s}").parse::<i64>();
+                    (format!("-{s}"), val)
+                },
+            }
+            .map_with_span(|lit, span: Span| (lit, span.start)),
+        )
+        .try_map(|(minus_end, ((text, val), lit_start)), span| {
+            if minus_end != lit_start {
+                return Err(Simple::custom(
+                    span,
+                    "expected integer literal immediately after minus sign",
+                ));
+            }
+            val.map(Ast::SInt)
+                .map_err(|e| int_literal_error(span, &text, e))
+        });
 
-        Tok::Ident(s) => Ast::Var(s),
-    };
+    let lit_or_var = int_lit
+        .or(neg_int_lit)
+        .or(select! {
+            Tok::Char(c) => Ast::Int(c as u64),
+
+            Tok::Bool(b) => Ast::Boolean(b),
+
+            Tok::Ident(s) => Ast::var(s),
+        })
+        .map_with_span(Spanned::new);
 
     recursive(|factor| {
         choice((
@@ -311,15 +511,26 @@ fn factor(
                 just(&[Tok::Punct(Punct::CParen)]),
             ),
             if_chain(expr.clone()),
-            block(expr).map(Ast::Block),
+            block(expr).map_with_span(|seq, span| Spanned::new(Ast::Block(seq), span)),
         ))
     })
 }
 
+/// Build a binary operator node, spanning both of its operands.
+fn spanned_binop(sym: BinopSym, lhs: Spanned<Ast>, rhs: Spanned<Ast>) -> Spanned<Ast> {
+    let span = lhs.span.start..rhs.span.end;
+    let binop = Ast::Binop {
+        sym,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+    };
+    Spanned::new(binop, span)
+}
+
 fn expr_binop_strength(
     strength: BindingStrength,
-    expr: Recursive<Tok, Ast, Simple<Tok>>,
-) -> Box<dyn Parser<Tok, Ast, Error = Simple<Tok>> + '_> {
+    expr: Recursive<'_, Tok, Spanned<Ast>, Simple<Tok>>,
+) -> Box<dyn Parser<Tok, Spanned<Ast>, Error = Simple<Tok>> + '_> {
     let op = filter_map(move |span, tok| match tok {
         Tok::Binop(sym) => {
             let sym_strength = BindingStrength::classify(sym);
@@ -339,34 +550,64 @@ fn expr_binop_strength(
         Box::new(
             expr_binop_strength(next_strength, expr.clone())
                 .then(op.then(expr_binop_strength(next_strength, expr)).repeated())
-                .foldl(|lhs, (sym, rhs)| Ast::binop(sym, lhs, rhs)),
+                .foldl(|lhs, (sym, rhs)| spanned_binop(sym, lhs, rhs)),
         )
     } else {
+        // The strongest binding level is exponentiation, which is right-associative. Operands are
+        // still parsed left to right (so that no operand is ever parsed twice), and then folded
+        // from the right.
         Box::new(
             factor(expr.clone())
                 .then(op.then(factor(expr)).repeated())
-                .foldl(|lhs, (sym, rhs)| Ast::binop(sym, lhs, rhs)),
+                .map(|(first, rest)| {
+                    let mut operands = vec![first];
+                    let mut syms = Vec::with_capacity(rest.len());
+                    for (sym, operand) in rest {
+                        syms.push(sym);
+                        operands.push(operand);
+                    }
+
+                    let mut acc = operands.pop().unwrap();
+                    while let Some(sym) = syms.pop() {
+                        let lhs = operands.pop().unwrap();
+                        acc = spanned_binop(sym, lhs, acc);
+                    }
+                    acc
+                }),
         )
     }
 }
 
-fn expr() -> Recursive<'static, Tok, Ast, Simple<Tok>> {
-    recursive(|expr| {
-        expr_binop_strength(BindingStrength::LogWeak, expr)
-    })
+fn expr() -> Recursive<'static, Tok, Spanned<Ast>, Simple<Tok>> {
+    recursive(|expr| expr_binop_strength(BindingStrength::LogWeak, expr))
 }
 
 pub fn parser() -> impl Parser<Tok, Vec<Ast>, Error = Simple<Tok>> {
     seq(expr()).then_ignore(end())
 }
 
+/// Parse a sequence of spanned tokens, as produced by the [`lexer`].
+///
+/// `len` is the length of the source text, used as the span of the end of input.
+fn parse_tokens(toks: Vec<(Tok, Span)>, len: usize) -> Result<Vec<Ast>, Vec<Simple<Tok>>> {
+    parser().parse(chumsky::Stream::from_iter(len..len, toks.into_iter()))
+}
+
 pub fn parse(input: &str) -> Result<Vec<Ast>, Vec<Simple<Tok>>> {
-    let toks = lexer().parse(input).map_err(|errs| {
+    parse_with_options(input, LexerOptions::default())
+}
+
+/// Parse a program, with the lexer configured with the given options.
+pub fn parse_with_options(
+    input: &str,
+    options: LexerOptions,
+) -> Result<Vec<Ast>, Vec<Simple<Tok>>> {
+    let toks = lexer_with_options(options).parse(input).map_err(|errs| {
         errs.into_iter()
             .map(|e| Simple::custom(e.span(), e))
             .collect::<Vec<_>>()
     })?;
-    parser().parse(toks)
+    parse_tokens(toks, input.chars().count())
 }
 
 #[cfg(test)]
@@ -375,13 +616,14 @@ mod test {
 
     fn test_lexer(input: &str, expected: &[Tok]) {
         let toks = lexer().parse(input).expect("Lexer failed");
+        let toks = toks.into_iter().map(|(tok, _)| tok).collect::<Vec<_>>();
         assert_eq!(toks, expected);
     }
 
     fn test_parser(input: &str, expected: &[Ast]) {
         let toks = lexer().parse(input).expect("Lexer failed");
         eprintln!("{toks:?}");
-        let ast = parser().parse(toks).expect("Parser failed");
+        let ast = parse_tokens(toks, input.chars().count()).expect("Parser failed");
         assert_eq!(ast, expected);
     }
 
@@ -419,6 +661,59 @@ a + b /* this also */ more"#,
         test_lexer("/* hey /* */ there", &[Tok::Ident("there".into())]);
     }
 
+    fn test_nested_lexer(input: &str, expected: &[Tok]) {
+        let options = LexerOptions {
+            nested_comments: true,
+        };
+        let toks = lexer_with_options(options)
+            .parse(input)
+            .expect("Lexer failed");
+        let toks = toks.into_iter().map(|(tok, _)| tok).collect::<Vec<_>>();
+        assert_eq!(toks, expected);
+    }
+
+    #[test]
+    fn tok_nested_comments() {
+        test_nested_lexer(
+            "a /* one */ b",
+            &[Tok::Ident("a".into()), Tok::Ident("b".into())],
+        );
+
+        test_nested_lexer(
+            "a /* one /* two */ still one */ b",
+            &[Tok::Ident("a".into()), Tok::Ident("b".into())],
+        );
+
+        test_nested_lexer(
+            "/* one /* two /* three */ */ * / */ c // /* line comments are unaffected\n",
+            &[Tok::Ident("c".into())],
+        );
+
+        let options = LexerOptions {
+            nested_comments: true,
+        };
+        let errs = lexer_with_options(options)
+            .parse("a /* one /* two */ still one")
+            .expect_err("Lexer accepted an unterminated nested comment");
+        assert!(
+            errs.iter().any(|e| matches!(
+                e.reason(),
+                chumsky::error::SimpleReason::Custom(msg) if msg == "unterminated block comment"
+            )),
+            "{errs:?}"
+        );
+
+        // Nesting is opt-in
+        test_lexer(
+            "/* one /* two */ c */",
+            &[
+                Tok::Ident("c".into()),
+                Tok::Binop(BinopSym::Mul),
+                Tok::Binop(BinopSym::Div),
+            ],
+        );
+    }
+
     macro_rules! binop_prefix_test {
         ($($op1:tt => $exp1:ident, $op2:tt => $exp2:ident ;)*) => {
             $(test_lexer(
@@ -458,14 +753,51 @@ a + b /* this also */ more"#,
         );
     }
 
+    #[test]
+    fn tok_char_literals() {
+        test_lexer(
+            "'a' 'Z' ' ' '\"' '/' 'é'",
+            &[
+                Tok::Char('a'),
+                Tok::Char('Z'),
+                Tok::Char(' '),
+                Tok::Char('"'),
+                Tok::Char('/'),
+                Tok::Char('é'),
+            ],
+        );
+
+        test_lexer(
+            r"'\n' '\t' '\r' '\0' '\\' '\'' '\x41' '\xfF'",
+            &[
+                Tok::Char('\n'),
+                Tok::Char('\t'),
+                Tok::Char('\r'),
+                Tok::Char('\0'),
+                Tok::Char('\\'),
+                Tok::Char('\''),
+                Tok::Char('A'),
+                Tok::Char('\u{ff}'),
+            ],
+        );
+
+        for invalid in [r"'\q'", r"'\x4'", r"'\x4g'", "''", "'ab'", "'a", "'\n'"] {
+            lexer()
+                .parse(invalid)
+                .expect_err(&format!("Lexer accepted invalid char literal {invalid}"));
+        }
+    }
+
     #[test]
     fn tok_keywords() {
         test_lexer(
-            "if else print true false something_else if_not_kw",
+            "if else print assert halt true false something_else if_not_kw",
             &[
                 Tok::Kw(Kw::If),
                 Tok::Kw(Kw::Else),
                 Tok::Kw(Kw::Print),
+                Tok::Kw(Kw::Assert),
+                Tok::Kw(Kw::Halt),
                 Tok::Bool(true),
                 Tok::Bool(false),
                 Tok::Ident("something_else".into()),
@@ -530,6 +862,50 @@ a + b /* this also */ more"#,
         );
     }
 
+    #[test]
+    fn parse_char_literal() {
+        test_parser(
+            r"print 'a' + '\x01';",
+            &[Ast::print(Ast::plus(Ast::Int(97), Ast::Int(1)))],
+        );
+    }
+
+    #[test]
+    fn parse_pow() {
+        test_lexer(
+            "** * ***",
+            &[
+                Tok::Binop(BinopSym::Pow),
+                Tok::Binop(BinopSym::Mul),
+                Tok::Binop(BinopSym::Pow),
+                Tok::Binop(BinopSym::Mul),
+            ],
+        );
+
+        // Exponentiation is right-associative
+        test_parser(
+            "a ** b ** c",
+            &[Ast::pow(
+                Ast::var("a"),
+                Ast::pow(Ast::var("b"), Ast::var("c")),
+            )],
+        );
+
+        // Exponentiation binds more tightly than multiplication, but less tightly than unary
+        // operators
+        test_parser(
+            "a * b ** c * d",
+            &[Ast::mul(
+                Ast::mul(Ast::var("a"), Ast::pow(Ast::var("b"), Ast::var("c"))),
+                Ast::var("d"),
+            )],
+        );
+        test_parser(
+            "~a ** b",
+            &[Ast::pow(Ast::bit_not(Ast::var("a")), Ast::var("b"))],
+        );
+    }
+
     #[test]
     fn parse_assignment() {
         test_parser("a = b;", &[Ast::assign("a", Ast::var("b"))]);
@@ -552,6 +928,50 @@ a + b /* this also */ more"#,
                 Ast::plus(Ast::var("b"), Ast::var("x")),
             ))],
         );
+        test_parser(
+            "print 1, a + 2, true;",
+            &[
+                Ast::print(Ast::Int(1)),
+                Ast::print(Ast::plus(Ast::var("a"), Ast::Int(2))),
+                Ast::print(Ast::Boolean(true)),
+            ],
+        );
+
+        assert!(parse("print;").is_err());
+        assert!(parse("print 1,;").is_err());
+        assert!(parse("print 1 2;").is_err());
+    }
+
+    #[test]
+    fn parse_assert() {
+        test_parser("assert x;", &[Ast::assert(Ast::var("x"))]);
+        test_parser(
+            "assert a + 1 == 3; print a;",
+            &[
+                Ast::assert(Ast::eq(Ast::plus(Ast::var("a"), Ast::Int(1)), Ast::Int(3))),
+                Ast::print(Ast::var("a")),
+            ],
+        );
+
+        assert!(parse("assert;").is_err());
+        assert!(parse("assert x").is_err());
+    }
+
+    #[test]
+    fn parse_halt() {
+        test_parser("halt;", &[Ast::Halt]);
+        test_parser(
+            "if a { halt; } print b;",
+            &[
+                Ast::if_cond(Ast::var("a"), vec![Ast::Halt], vec![]),
+                Ast::print(Ast::var("b")),
+            ],
+        );
+
+        // Halt is a statement, not an expression
+        assert!(parse("halt").is_err());
+        assert!(parse("a = halt;").is_err());
+        assert!(parse("print halt;").is_err());
     }
 
     #[test]
@@ -663,4 +1083,96 @@ print a + b;
             ],
         );
     }
+
+    #[test]
+    fn spans() {
+        let ast = parse("a = 4;\nprint  abc + 17;").expect("Parsing failed");
+
+        let Ast::Assign { var, value } = &ast[0] else {
+            panic!("expected assignment, found {:?}", ast[0]);
+        };
+        assert_eq!(var.span, 0..1);
+        assert_eq!(value.span, 4..5);
+
+        let Ast::Print(value) = &ast[1] else {
+            panic!("expected print, found {:?}", ast[1]);
+        };
+        assert_eq!(value.span, 14..22);
+
+        let Ast::Binop { lhs, rhs, .. } = &value.node else {
+            panic!("expected binary operation, found {value:?}");
+        };
+        assert_eq!(lhs.span, 14..17);
+        assert_eq!(rhs.span, 20..22);
+    }
+
+    #[test]
+    fn int_literal_too_large() {
+        let input = "print 99999999999999999999999;";
+        let errs = parse(input).expect_err("Parser accepted an out-of-range literal");
+        assert!(
+            errs.iter().any(|e| e.span() == (6..29)
+                && matches!(
+                    e.reason(),
+                    chumsky::error::SimpleReason::Custom(msg)
+                        if msg == "integer literal too large for 64-bit value: 99999999999999999999999"
+                )),
+            "{errs:?}"
+        );
+
+        let errs = parse("print 0x10000000000000000;")
+            .expect_err("Parser accepted an out-of-range literal");
+        assert!(
+            errs.iter().any(|e| matches!(
+                e.reason(),
+                chumsky::error::SimpleReason::Custom(msg)
+                    if msg == "integer literal too large for 64-bit value: 0x10000000000000000"
+            )),
+            "{errs:?}"
+        );
+
+        // The largest 64-bit values are still accepted
+        parse("print 18446744073709551615; print 0xffffffffffffffff;").unwrap();
+    }
+
+    #[test]
+    fn parse_signed_int() {
+        test_parser("print 5;", &[Ast::print(Ast::Int(5))]);
+        test_parser("print -5;", &[Ast::print(Ast::SInt(-5))]);
+        test_parser("print -0x10;", &[Ast::print(Ast::SInt(-16))]);
+        test_parser(
+            "a = 4 - -5;",
+            &[Ast::assign(
+                "a",
+                Ast::binop(BinopSym::Minus, Ast::Int(4), Ast::SInt(-5)),
+            )],
+        );
+        test_parser(
+            "a = 4 -5;",
+            &[Ast::assign(
+                "a",
+                Ast::binop(BinopSym::Minus, Ast::Int(4), Ast::Int(5)),
+            )],
+        );
+        test_parser(
+            "print -9223372036854775808;",
+            &[Ast::print(Ast::SInt(i64::MIN))],
+        );
+
+        // The minus sign must be immediately followed by the literal
+        assert!(parse("print - 5;").is_err());
+        assert!(parse("print -\n5;").is_err());
+
+        let errs = parse("print -9223372036854775809;")
+            .expect_err("Parser accepted an out-of-range literal");
+        assert!(
+            errs.iter().any(|e| e.span() == (6..26)
+                && matches!(
+                    e.reason(),
+                    chumsky::error::SimpleReason::Custom(msg)
+                        if msg == "integer literal too large for 64-bit value: -9223372036854775809"
+                )),
+            "{errs:?}"
+        );
+    }
 }
@@ -2,7 +2,7 @@
 
 use std::fmt::{self, Display, Formatter};
 
-use crate::ast::{Ast, BinopSym, UnopSym};
+use crate::ast::{Ast, BinopSym, Span, UnopSym};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Type {
@@ -32,7 +32,7 @@ impl BinopClass {
     fn classify(op: BinopSym) -> Self {
         use BinopSym::*;
         match op {
-            Plus | Minus | Mul | Div | Mod | BitAnd | BitOr | BitXor => BinopClass::Int,
+            Plus | Minus | Mul | Div | Mod | Pow | BitAnd | BitOr | BitXor => BinopClass::Int,
             Greater | Less | GreaterEq | LessEq | Eq | NEq => BinopClass::Comp,
             LogAnd | LogOr => BinopClass::Logical,
         }
@@ -64,6 +64,9 @@ pub enum TypeError {
     #[error("Undeclared variable `{0}`")]
     UndeclaredVariable(String),
 
+    #[error("Variable `{0}` is used before it is assigned")]
+    UsedBeforeAssignment(String),
+
     #[error("Invalid type for `if` condition: {0}")]
     InvalidConditionType(Type),
 
@@ -80,87 +83,291 @@ pub enum TypeError {
 
     #[error("Cannot print value of type {0}; printed values must be integers or booleans")]
     InvalidPrintValueType(Type),
+
+    #[error("Invalid type for `assert` condition: {0}")]
+    InvalidAssertConditionType(Type),
+
+    #[error("Right operand of `{0}` is a constant zero")]
+    DivisionByZeroLiteral(BinopSym),
 }
 
+/// Non-fatal diagnostics produced by the type checker.
+#[derive(Debug, thiserror::Error, Clone, PartialEq, Eq)]
+pub enum Warning {
+    #[error("Variable `{name}` is assigned but never read")]
+    UnusedVariable { name: String, span: Span },
+
+    #[error("`if` condition is always {value}")]
+    ConstantCondition { value: bool, span: Span },
+}
+
+impl Warning {
+    /// Get the source span this warning refers to.
+    pub fn span(&self) -> &Span {
+        match self {
+            Self::UnusedVariable { span, .. } | Self::ConstantCondition { span, .. } => span,
+        }
+    }
+}
+
+/// Type check a program, returning the first type error encountered, if any.
 pub fn typecheck(ast: &[Ast]) -> Result<Type, TypeError> {
+    typecheck_all(ast).map_err(|mut errors| errors.swap_remove(0))
+}
+
+/// Type check a program, returning all type errors encountered.
+///
+/// Where possible, the type checker recovers from errors and continues checking the rest of the
+/// program, so that independent errors are all reported. Errors that are consequences of earlier
+/// errors (e.g. uses of a variable whose assigned value failed to type check) are not reported.
+/// Errors are returned in the order in which they occur in the program.
+pub fn typecheck_all(ast: &[Ast]) -> Result<Type, Vec<TypeError>> {
     let mut context = TypingContext::default();
-    typecheck_sequence(&mut context, ast)
+    let mut errors = Vec::new();
+    let ty = typecheck_sequence(&mut context, &mut errors, ast);
+
+    if errors.is_empty() {
+        Ok(ty.expect("a program with no type errors should have a type"))
+    } else {
+        Err(errors)
+    }
+}
+
+/// Type check a program, additionally collecting any warnings about it.
+///
+/// Warnings do not prevent a program from type checking successfully; they are only returned if
+/// the program is otherwise well-typed.
+pub fn typecheck_with_warnings(ast: &[Ast]) -> Result<(Type, Vec<Warning>), Vec<TypeError>> {
+    let ty = typecheck_all(ast)?;
+
+    let mut usage = UsageContext::default();
+    usage.in_new_scope(|usage| usage.visit_sequence(ast));
+    usage.warnings.sort_by_key(|warning| warning.span().start);
+
+    Ok((ty, usage.warnings))
+}
+
+/// A variable binding tracked by the unused variable analysis.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Binding {
+    name: String,
+    span: Span,
+    read: bool,
+}
+
+/// Variable usage context, for detecting variables that are assigned but never read.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct UsageContext {
+    bindings: Vec<Binding>,
+    warnings: Vec<Warning>,
+}
+
+impl UsageContext {
+    fn lookup(&mut self, var: &str) -> Option<&mut Binding> {
+        self.bindings.iter_mut().rev().find(|b| b.name == var)
+    }
+
+    /// Perform an action in a new scope, reporting any variables first bound in that scope that
+    /// were never read.
+    fn in_new_scope(&mut self, f: impl FnOnce(&mut UsageContext)) {
+        let scope_start = self.bindings.len();
+        f(self);
+
+        let unused = self
+            .bindings
+            .drain(scope_start..)
+            .filter(|b| !b.read)
+            .map(|b| Warning::UnusedVariable {
+                name: b.name,
+                span: b.span,
+            });
+        self.warnings.extend(unused);
+    }
+
+    fn visit_sequence(&mut self, seq: &[Ast]) {
+        for ast in seq {
+            self.visit(ast);
+        }
+    }
+
+    fn visit(&mut self, ast: &Ast) {
+        match ast {
+            Ast::Block(seq) => self.in_new_scope(|usage| usage.visit_sequence(seq)),
+
+            Ast::Var(v) => {
+                if let Some(binding) = self.lookup(v) {
+                    binding.read = true;
+                }
+            }
+
+            Ast::Int(..) | Ast::SInt(..) | Ast::Boolean(..) | Ast::Halt => {}
+
+            Ast::Assign { var, value } => {
+                self.visit(value);
+                if self.lookup(var).is_none() {
+                    self.bindings.push(Binding {
+                        name: var.node.clone(),
+                        span: var.span.clone(),
+                        read: false,
+                    });
+                }
+            }
+
+            Ast::IfCond {
+                cond,
+                body,
+                else_body,
+            } => {
+                if let Some(value) = const_bool(cond) {
+                    self.warnings.push(Warning::ConstantCondition {
+                        value,
+                        span: cond.span.clone(),
+                    });
+                }
+
+                self.visit(cond);
+                self.in_new_scope(|usage| usage.visit_sequence(body));
+                self.in_new_scope(|usage| usage.visit_sequence(else_body));
+            }
+
+            Ast::Binop { lhs, rhs, .. } => {
+                self.visit(lhs);
+                self.visit(rhs);
+            }
+
+            Ast::Unop { operand, .. } | Ast::Print(operand) | Ast::Assert(operand) => {
+                self.visit(operand)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 struct TypingContext {
-    vars: Vec<(String, Type)>,
+    /// Variables in scope, with their types.
+    ///
+    /// A variable's type is `None` if the value first assigned to it failed to type check.
+    vars: Vec<(String, Option<Type>)>,
+
+    /// Names of variables that will be assigned later in the current scope or an enclosing one.
+    ///
+    /// Variables assigned by the earliest upcoming statements are at the end.
+    assigned_later: Vec<String>,
 }
 
 impl TypingContext {
-    fn lookup(&self, var: &str) -> Option<Type> {
+    fn lookup(&self, var: &str) -> Option<Option<Type>> {
         self.vars
             .iter()
             .rev()
             .find_map(|(name, ty)| if name == var { Some(*ty) } else { None })
     }
 
-    fn bind(&mut self, var: String, ty: Type) -> Result<(), TypeError> {
-        if let Some(old_ty) = self.lookup(&var) {
-            if old_ty != ty {
+    /// Bind a variable to a type, or to an unknown type if `ty` is `None`.
+    fn bind(&mut self, var: String, ty: Option<Type>) -> Result<(), TypeError> {
+        match (self.lookup(&var), ty) {
+            (Some(Some(old_ty)), Some(ty)) if old_ty != ty => {
                 Err(TypeError::VariableTypeMismatch {
                     name: var,
                     ty: old_ty,
                     new_ty: ty,
                 })
-            } else {
+            }
+            (Some(_), _) => Ok(()),
+            (None, ty) => {
+                self.vars.push((var, ty));
                 Ok(())
             }
-        } else {
-            self.vars.push((var, ty));
-            Ok(())
         }
     }
 
-    fn in_new_scope<T>(
-        &self,
-        f: impl FnOnce(&mut TypingContext) -> Result<T, TypeError>,
-    ) -> Result<T, TypeError> {
-        let mut new_scope = self.clone();
-        f(&mut new_scope)
+    /// Perform an action in a new scope.
+    ///
+    /// Any variables bound within the given closure are unbound once this function returns. The
+    /// variable list is truncated back to its length on entry rather than cloned, so entering a
+    /// scope takes constant time regardless of the number of variables already in scope.
+    fn in_new_scope<T>(&mut self, f: impl FnOnce(&mut TypingContext) -> T) -> T {
+        let scope_start = self.vars.len();
+        let res = f(self);
+        self.vars.truncate(scope_start);
+        res
     }
 }
 
-fn typecheck_sequence(context: &mut TypingContext, ast: &[Ast]) -> Result<Type, TypeError> {
-    ast.iter().fold(Ok(Type::Unit), |prev_ty, next_statement| {
-        let prev_ty = prev_ty?;
-        if prev_ty != Type::Unit {
-            Err(TypeError::NonUnitInSequence(prev_ty))
-        } else {
-            typecheck_one(context, next_statement)
+/// Type check a sequence of statements, recording any errors.
+///
+/// Returns the type of the sequence, or `None` if it could not be determined due to errors.
+fn typecheck_sequence(
+    context: &mut TypingContext,
+    errors: &mut Vec<TypeError>,
+    ast: &[Ast],
+) -> Option<Type> {
+    let outer_assigned = context.assigned_later.len();
+    context
+        .assigned_later
+        .extend(ast.iter().rev().filter_map(|statement| match statement {
+            Ast::Assign { var, .. } => Some(var.node.clone()),
+            _ => None,
+        }));
+
+    let mut ty = Some(Type::Unit);
+    for statement in ast {
+        match ty {
+            Some(Type::Unit) | None => {}
+            Some(prev_ty) => errors.push(TypeError::NonUnitInSequence(prev_ty)),
         }
-    })
+
+        ty = typecheck_one(context, errors, statement);
+        if let Ast::Assign { .. } = statement {
+            context.assigned_later.pop();
+        }
+    }
+
+    context.assigned_later.truncate(outer_assigned);
+    ty
 }
 
-fn typecheck_one(context: &mut TypingContext, ast: &Ast) -> Result<Type, TypeError> {
+/// Type check a single AST node, recording any errors.
+///
+/// Returns the type of the node, or `None` if it could not be determined due to errors.
+fn typecheck_one(
+    context: &mut TypingContext,
+    errors: &mut Vec<TypeError>,
+    ast: &Ast,
+) -> Option<Type> {
     match ast {
-        Ast::Block(seq) => context.in_new_scope(|context| typecheck_sequence(context, seq)),
+        Ast::Block(seq) => context.in_new_scope(|context| typecheck_sequence(context, errors, seq)),
 
-        Ast::Var(v) => {
-            if let Some(ty) = context.lookup(v) {
-                Ok(ty)
-            } else {
-                Err(TypeError::UndeclaredVariable(v.clone()))
+        Ast::Var(v) => match context.lookup(v) {
+            Some(ty) => ty,
+            None if context.assigned_later.contains(v) => {
+                errors.push(TypeError::UsedBeforeAssignment(v.clone()));
+                None
             }
-        }
+            None => {
+                errors.push(TypeError::UndeclaredVariable(v.clone()));
+                None
+            }
+        },
 
-        Ast::Int(_) => Ok(Type::Int),
+        // There is no signed integer type yet, so signed literals are integers in two's complement
+        Ast::Int(..) | Ast::SInt(..) => Some(Type::Int),
 
-        Ast::Boolean(_) => Ok(Type::Bool),
+        Ast::Boolean(..) => Some(Type::Bool),
 
         Ast::Assign { var, value } => {
-            let ty = typecheck_one(context, value)?;
-            if ty == Type::Unit {
-                Err(TypeError::AssignUnitValue(var.clone()))
-            } else {
-                context.bind(var.clone(), ty)?;
-                Ok(Type::Unit)
+            let ty = match typecheck_one(context, errors, value) {
+                Some(Type::Unit) => {
+                    errors.push(TypeError::AssignUnitValue(var.node.clone()));
+                    None
+                }
+                ty => ty,
+            };
+
+            if let Err(err) = context.bind(var.node.clone(), ty) {
+                errors.push(err);
             }
+            Some(Type::Unit)
         }
 
         Ast::IfCond {
@@ -168,30 +375,32 @@ fn typecheck_one(context: &mut TypingContext, ast: &Ast) -> Result<Type, TypeErr
             body,
             else_body,
         } => {
-            let cond_ty = typecheck_one(context, cond)?;
-            if cond_ty != Type::Bool {
-                Err(TypeError::InvalidConditionType(cond_ty))
+            let cond_ty = typecheck_one(context, errors, cond);
+            if let Some(cond_ty) = cond_ty.filter(|&ty| ty != Type::Bool) {
+                errors.push(TypeError::InvalidConditionType(cond_ty));
+            }
+
+            let body_ty = context.in_new_scope(|context| typecheck_sequence(context, errors, body));
+
+            if else_body.is_empty() {
+                if let Some(body_ty) = body_ty.filter(|&ty| ty != Type::Unit) {
+                    errors.push(TypeError::NonUnitBareIfStatement(body_ty));
+                }
+                Some(Type::Unit)
             } else {
-                let body_ty = context.in_new_scope(|context| typecheck_sequence(context, body))?;
+                let else_ty =
+                    context.in_new_scope(|context| typecheck_sequence(context, errors, else_body));
 
-                if else_body.is_empty() {
-                    if body_ty == Type::Unit {
-                        Ok(Type::Unit)
-                    } else {
-                        Err(TypeError::NonUnitBareIfStatement(body_ty))
-                    }
-                } else {
-                    let else_ty =
-                        context.in_new_scope(|context| typecheck_sequence(context, else_body))?;
-
-                    if body_ty == else_ty {
-                        Ok(body_ty)
-                    } else {
-                        Err(TypeError::MismatchedIfElseTypes {
+                match (body_ty, else_ty) {
+                    (Some(body_ty), Some(else_ty)) if body_ty != else_ty => {
+                        errors.push(TypeError::MismatchedIfElseTypes {
                             if_ty: body_ty,
                             else_ty,
-                        })
+                        });
+                        None
                     }
+                    (Some(ty), Some(_)) => Some(ty),
+                    _ => None,
                 }
             }
         }
@@ -204,23 +413,22 @@ fn typecheck_one(context: &mut TypingContext, ast: &Ast) -> Result<Type, TypeErr
                 BinopClass::Logical => Type::Bool,
             };
 
-            let lhs_type = typecheck_one(context, lhs)?;
-            if lhs_type != operand_type {
-                return Err(TypeError::InvalidOperandType {
-                    expected: operand_type,
-                    found: lhs_type,
-                });
+            for operand in [lhs, rhs] {
+                let found = typecheck_one(context, errors, operand);
+                if let Some(found) = found.filter(|&ty| ty != operand_type) {
+                    errors.push(TypeError::InvalidOperandType {
+                        expected: operand_type,
+                        found,
+                    });
+                }
             }
 
-            let rhs_type = typecheck_one(context, rhs)?;
-            if rhs_type != operand_type {
-                return Err(TypeError::InvalidOperandType {
-                    expected: operand_type,
-                    found: rhs_type,
-                });
+            if matches!(sym, BinopSym::Div | BinopSym::Mod) && const_int(rhs) == Some(0) {
+                errors.push(TypeError::DivisionByZeroLiteral(*sym));
             }
 
-            Ok(op_class.result_ty())
+            // The result type of an operator is known even if its operands are ill-typed
+            Some(op_class.result_ty())
         }
 
         Ast::Unop { sym, operand } => {
@@ -229,25 +437,124 @@ fn typecheck_one(context: &mut TypingContext, ast: &Ast) -> Result<Type, TypeErr
                 UnopSym::LogNot => Type::Bool,
             };
 
-            let found_type = typecheck_one(context, operand)?;
-            if found_type != expected_type {
-                Err(TypeError::InvalidOperandType {
+            let found = typecheck_one(context, errors, operand);
+            if let Some(found) = found.filter(|&ty| ty != expected_type) {
+                errors.push(TypeError::InvalidOperandType {
                     expected: expected_type,
-                    found: found_type,
-                })
-            } else {
-                Ok(expected_type)
+                    found,
+                });
             }
+
+            Some(expected_type)
         }
 
         Ast::Print(value) => {
-            let val_type = typecheck_one(context, value)?;
-            if matches!(val_type, Type::Int | Type::Bool) {
-                Ok(Type::Unit)
-            } else {
-                Err(TypeError::InvalidPrintValueType(val_type))
+            let val_type = typecheck_one(context, errors, value);
+            if let Some(val_type) = val_type.filter(|ty| !matches!(ty, Type::Int | Type::Bool)) {
+                errors.push(TypeError::InvalidPrintValueType(val_type));
+            }
+
+            Some(Type::Unit)
+        }
+
+        Ast::Assert(cond) => {
+            let cond_ty = typecheck_one(context, errors, cond);
+            if let Some(cond_ty) = cond_ty.filter(|&ty| ty != Type::Bool) {
+                errors.push(TypeError::InvalidAssertConditionType(cond_ty));
+            }
+
+            Some(Type::Unit)
+        }
+
+        Ast::Halt => Some(Type::Unit),
+    }
+}
+
+/// Evaluate an integer expression consisting only of literals and arithmetic operators.
+///
+/// Returns `None` if the expression is not constant, or if evaluating it would trap at runtime.
+fn const_int(ast: &Ast) -> Option<u64> {
+    match ast {
+        Ast::Int(val) => Some(*val),
+        Ast::SInt(val) => Some(*val as u64),
+
+        Ast::Block(seq) => match seq.as_slice() {
+            [ast] => const_int(ast),
+            _ => None,
+        },
+
+        Ast::Binop { sym, lhs, rhs } => {
+            let (lhs, rhs) = (const_int(lhs)?, const_int(rhs)?);
+            match sym {
+                BinopSym::Plus => Some(lhs.wrapping_add(rhs)),
+                BinopSym::Minus => Some(lhs.wrapping_sub(rhs)),
+                BinopSym::Mul => Some(lhs.wrapping_mul(rhs)),
+                BinopSym::Div => lhs.checked_div(rhs),
+                BinopSym::Mod => lhs.checked_rem(rhs),
+                BinopSym::Pow => u32::try_from(rhs).ok().map(|rhs| lhs.wrapping_pow(rhs)),
+                BinopSym::BitAnd => Some(lhs & rhs),
+                BinopSym::BitOr => Some(lhs | rhs),
+                BinopSym::BitXor => Some(lhs ^ rhs),
+                _ => None,
             }
         }
+
+        Ast::Unop {
+            sym: UnopSym::BitNot,
+            operand,
+            ..
+        } => const_int(operand).map(|val| !val),
+
+        _ => None,
+    }
+}
+
+/// Evaluate a boolean expression consisting only of literals, comparisons of constant integer
+/// expressions, and logical operators.
+///
+/// Returns `None` if the expression is not constant.
+fn const_bool(ast: &Ast) -> Option<bool> {
+    match ast {
+        Ast::Boolean(val) => Some(*val),
+
+        Ast::Block(seq) => match seq.as_slice() {
+            [ast] => const_bool(ast),
+            _ => None,
+        },
+
+        Ast::Binop { sym, lhs, rhs } => match BinopClass::classify(*sym) {
+            BinopClass::Comp => {
+                let (lhs, rhs) = (const_int(lhs)?, const_int(rhs)?);
+                match sym {
+                    BinopSym::Greater => Some(lhs > rhs),
+                    BinopSym::Less => Some(lhs < rhs),
+                    BinopSym::GreaterEq => Some(lhs >= rhs),
+                    BinopSym::LessEq => Some(lhs <= rhs),
+                    BinopSym::Eq => Some(lhs == rhs),
+                    BinopSym::NEq => Some(lhs != rhs),
+                    _ => None,
+                }
+            }
+
+            BinopClass::Logical => {
+                let (lhs, rhs) = (const_bool(lhs)?, const_bool(rhs)?);
+                match sym {
+                    BinopSym::LogAnd => Some(lhs && rhs),
+                    BinopSym::LogOr => Some(lhs || rhs),
+                    _ => None,
+                }
+            }
+
+            BinopClass::Int => None,
+        },
+
+        Ast::Unop {
+            sym: UnopSym::LogNot,
+            operand,
+            ..
+        } => const_bool(operand).map(|val| !val),
+
+        _ => None,
     }
 }
 
@@ -274,6 +581,7 @@ mod test {
     fn binops() {
         test_typecheck(Ok(Type::Int), "4 + 8");
         test_typecheck(Ok(Type::Int), "0x45 ^ 0x8f");
+        test_typecheck(Ok(Type::Int), "2 ** 10");
         test_typecheck(Ok(Type::Bool), "4 == 5");
         test_typecheck(Ok(Type::Bool), "8 <= 70");
         test_typecheck(Ok(Type::Bool), "(2 < 3) || (8 > 4)");
@@ -458,11 +766,272 @@ if a {
             "a = 4; if a - 2 == 0 { b = 3; print a == b; } else { b = true; print b || a > 3; }",
         );
 
-        test_typecheck(Err(TypeError::UndeclaredVariable("a".into())), "a = a + 5;");
+        test_typecheck(
+            Err(TypeError::UsedBeforeAssignment("a".into())),
+            "a = a + 5;",
+        );
 
         test_typecheck(
             Err(TypeError::UndeclaredVariable("b".into())),
             "a = 4; { b = a + 5; } { print b; }",
         );
     }
+
+    #[test]
+    fn deep_nesting() {
+        const DEPTH: usize = 200;
+
+        // Each level reassigns the outer variable and binds a fresh one of its own
+        let mut program = String::from("a = 0; ");
+        for i in 0..DEPTH {
+            program.push_str(&format!("{{ a = a + 1; b{i} = a > {i}; "));
+        }
+        program.push_str("print a;");
+        for i in (0..DEPTH).rev() {
+            program.push_str(&format!(" print b{i}; }}"));
+        }
+
+        test_typecheck(Ok(Type::Bool), &format!("{program} print a; a > 0"));
+
+        // Inner bindings go out of scope, even after deep nesting
+        test_typecheck(
+            Err(TypeError::UndeclaredVariable("b0".into())),
+            &format!("{program} print b0;"),
+        );
+
+        // Reassignment of an outer variable deep inside nested scopes is still type checked
+        // against its original type
+        let mut program = String::from("a = 0; ");
+        program.push_str(&"{ ".repeat(DEPTH));
+        program.push_str("a = true;");
+        program.push_str(&" }".repeat(DEPTH));
+        test_typecheck(
+            Err(TypeError::VariableTypeMismatch {
+                name: "a".into(),
+                ty: Type::Int,
+                new_ty: Type::Bool,
+            }),
+            &program,
+        );
+    }
+
+    #[test]
+    fn division_by_zero_literal() {
+        test_typecheck(
+            Err(TypeError::DivisionByZeroLiteral(BinopSym::Div)),
+            "print 5 / 0;",
+        );
+        test_typecheck(
+            Err(TypeError::DivisionByZeroLiteral(BinopSym::Mod)),
+            "a = 4; print a % 0x0;",
+        );
+
+        // Constant expressions are folded
+        test_typecheck(
+            Err(TypeError::DivisionByZeroLiteral(BinopSym::Div)),
+            "print 5 / (3 - 3);",
+        );
+        test_typecheck(
+            Err(TypeError::DivisionByZeroLiteral(BinopSym::Mod)),
+            "print 5 % (~0 + 1);",
+        );
+
+        // Dynamic zeros are left to runtime
+        test_typecheck(Ok(Type::Unit), "x = 0; print 5 / x;");
+        test_typecheck(Ok(Type::Unit), "print 5 / 1; print 0 % 3;");
+    }
+
+    #[test]
+    fn used_before_assignment() {
+        test_typecheck(
+            Err(TypeError::UsedBeforeAssignment("b".into())),
+            "a = b; b = 3;",
+        );
+
+        test_typecheck(
+            Err(TypeError::UsedBeforeAssignment("b".into())),
+            "a = 4; if a > 2 { print b; } b = 5;",
+        );
+
+        test_typecheck(
+            Err(TypeError::UsedBeforeAssignment("b".into())),
+            "{ a = 4; { print a + b; } b = 1; }",
+        );
+
+        // Never assigned anywhere
+        test_typecheck(
+            Err(TypeError::UndeclaredVariable("c".into())),
+            "a = c; b = 3;",
+        );
+
+        // Assigned later, but in a scope that isn't visible from the use
+        test_typecheck(
+            Err(TypeError::UndeclaredVariable("b".into())),
+            "a = 4; print a + b; { b = 2; }",
+        );
+        test_typecheck(
+            Err(TypeError::UndeclaredVariable("b".into())),
+            "a = 4; { b = 2; } print a + b;",
+        );
+    }
+
+    #[test]
+    fn assert() {
+        test_typecheck(Ok(Type::Unit), "assert true;");
+        test_typecheck(Ok(Type::Unit), "a = 4; assert a > 3;");
+        test_typecheck(Ok(Type::Int), "assert 1 < 2; 5");
+        test_typecheck(
+            Err(TypeError::InvalidAssertConditionType(Type::Int)),
+            "assert 1;",
+        );
+        test_typecheck(
+            Err(TypeError::InvalidAssertConditionType(Type::Unit)),
+            "assert { a = 3; };",
+        );
+    }
+
+    #[test]
+    fn halt() {
+        test_typecheck(Ok(Type::Unit), "halt;");
+        test_typecheck(Ok(Type::Unit), "a = 4; if a > 3 { halt; }");
+        test_typecheck(Ok(Type::Int), "halt; 5");
+        test_typecheck(
+            Err(TypeError::NonUnitBareIfStatement(Type::Int)),
+            "if true { halt; 5 }",
+        );
+    }
+
+    #[test]
+    fn multiple_errors() {
+        let ast = parse::parse("a = 4 + true; print b; c = 5; if c { print c; } c = false;")
+            .expect("Parsing failed");
+        assert_eq!(
+            typecheck_all(&ast),
+            Err(vec![
+                TypeError::InvalidOperandType {
+                    expected: Type::Int,
+                    found: Type::Bool,
+                },
+                TypeError::UndeclaredVariable("b".into()),
+                TypeError::InvalidConditionType(Type::Int),
+                TypeError::VariableTypeMismatch {
+                    name: "c".into(),
+                    ty: Type::Int,
+                    new_ty: Type::Bool,
+                },
+            ])
+        );
+
+        // Errors in a variable's value are not reported again at each use of the variable
+        let ast = parse::parse("a = b; print a + 1; print !a;").expect("Parsing failed");
+        assert_eq!(
+            typecheck_all(&ast),
+            Err(vec![TypeError::UndeclaredVariable("b".into())])
+        );
+
+        let ast = parse::parse("a = 4; print a;").expect("Parsing failed");
+        assert_eq!(typecheck_all(&ast), Ok(Type::Unit));
+    }
+
+    fn unused_variables(input: &str) -> Vec<String> {
+        let ast = parse::parse(input).expect("Parsing failed");
+        let (_, warnings) = typecheck_with_warnings(&ast).expect("Type checking failed");
+        warnings
+            .into_iter()
+            .filter_map(|w| match w {
+                Warning::UnusedVariable { name, .. } => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unused_variable_warnings() {
+        assert_eq!(unused_variables("a = 4; print a;"), Vec::<String>::new());
+        assert_eq!(unused_variables("a = 4; b = 5; print a;"), ["b"]);
+
+        // Reassignment is not a read, but reading the old value while reassigning is
+        assert_eq!(unused_variables("a = 4; a = 5;"), ["a"]);
+        assert_eq!(unused_variables("a = 4; a = a + 1;"), Vec::<String>::new());
+
+        // Reads in nested scopes count for variables from enclosing scopes
+        assert_eq!(
+            unused_variables("a = 4; { b = 5; if true { print a; } }"),
+            ["b"]
+        );
+
+        // Variables of the same name in separate scopes are separate variables
+        assert_eq!(
+            unused_variables(
+                "{ b = 1; print b; } { b = 2; } if true { b = 3; } else { b = 4; print b; }"
+            ),
+            ["b", "b"]
+        );
+
+        // Ordered by position in the source
+        assert_eq!(
+            unused_variables("z = 1; { y = 2; } x = 3;"),
+            ["z", "y", "x"]
+        );
+    }
+
+    #[test]
+    fn unused_variable_span() {
+        let ast = parse::parse("a = 1;\nbee = a;").expect("Parsing failed");
+        let (ty, warnings) = typecheck_with_warnings(&ast).expect("Type checking failed");
+        assert_eq!(ty, Type::Unit);
+        assert_eq!(
+            warnings,
+            [Warning::UnusedVariable {
+                name: "bee".into(),
+                span: 7..10,
+            }]
+        );
+    }
+
+    fn constant_conditions(input: &str) -> Vec<(bool, Span)> {
+        let ast = parse::parse(input).expect("Parsing failed");
+        let (_, warnings) = typecheck_with_warnings(&ast).expect("Type checking failed");
+        warnings
+            .into_iter()
+            .filter_map(|w| match w {
+                Warning::ConstantCondition { value, span } => Some((value, span)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn constant_condition_warnings() {
+        assert_eq!(
+            constant_conditions("if 1 == 1 { print 0; }"),
+            [(true, 3..9)]
+        );
+        assert_eq!(
+            constant_conditions("a = 1; b = 2; if a == b { print 0; }"),
+            []
+        );
+
+        assert_eq!(
+            constant_conditions("if false { print 0; }"),
+            [(false, 3..8)]
+        );
+        assert_eq!(
+            constant_conditions("if !(2 + 2 > 4) && true { print 0; }"),
+            [(true, 3..23)]
+        );
+        assert_eq!(
+            constant_conditions("a = true; if a || false { print 0; }"),
+            []
+        );
+
+        // Nested conditions are each reported
+        assert_eq!(
+            constant_conditions("x = if 3 < 2 { 1 } else { if true { 2 } else { 3 } }; print x;")
+                .into_iter()
+                .map(|(value, _)| value)
+                .collect::<Vec<_>>(),
+            [false, true]
+        );
+    }
 }
@@ -0,0 +1,44 @@
+//! Runs the example programs at the root of the repository.
+//!
+//! The VM is built with the `vm-invariants` feature for this crate's tests, so this also checks
+//! the VM's internal consistency while running each example.
+
+use std::fs;
+use std::path::Path;
+
+use hypescript_bytecode::instructions_to_vec;
+use hypescript_lang::{codegen, parse, types};
+use hypescript_vm::ExecutionContext;
+
+#[test]
+fn examples() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let mut examples: Vec<_> = fs::read_dir(&root)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "hy"))
+        .collect();
+    examples.sort();
+    assert!(!examples.is_empty());
+
+    for path in examples {
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let source = fs::read_to_string(&path).unwrap();
+        let ast = parse::parse(&source).unwrap_or_else(|_| panic!("{name}: parsing failed"));
+
+        if name.ends_with("_type_error") {
+            assert!(types::typecheck(&ast).is_err(), "{name}: type checked");
+            continue;
+        }
+        types::typecheck(&ast).unwrap_or_else(|err| panic!("{name}: {err}"));
+
+        let instructions = codegen::translate(&ast).unwrap();
+        let bytes = instructions_to_vec(&instructions);
+        ExecutionContext::new(&bytes)
+            .with_captured_output()
+            .with_trace()
+            .with_pc_trace()
+            .run()
+            .unwrap_or_else(|err| panic!("{name}: {err}"));
+    }
+}
@@ -0,0 +1,45 @@
+//! Time sources for execution time limits.
+
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A source of the current time.
+///
+/// The VM queries a clock to enforce the time limit set with
+/// [`ExecutionContext::with_time_limit`](crate::ExecutionContext::with_time_limit). The default is
+/// [`SystemClock`]; other implementations can be supplied with
+/// [`ExecutionContext::with_clock`](crate::ExecutionContext::with_clock), e.g. to simulate the
+/// passage of time deterministically in tests.
+pub trait Clock {
+    /// Get the current time.
+    fn now(&self) -> Instant;
+}
+
+/// A clock that reads the system's monotonic clock via [`Instant::now`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Rc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
@@ -2,12 +2,16 @@
 
 use std::fmt::{self, Display, Formatter};
 
-use hypescript_bytecode::Instruction;
+use hypescript_bytecode::{DecodeError, Instruction};
 
 use crate::trace::{format_trace, Snapshot};
+use crate::value::ValueError;
 
 /// A result type specialized to runtime errors.
-pub type Result<T> = std::result::Result<T, Error>;
+///
+/// The error type may be overridden, so that glob imports of this type (e.g. via the
+/// [`prelude`](crate::prelude)) do not break uses of `Result` with other error types.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 /// Categories of runtime error.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -21,6 +25,57 @@ pub enum ErrorKind {
     InputError,
     OutputError,
     ParseError,
+    InvalidEntryPoint,
+    TimeLimitExceeded,
+    UnrecognizedOpcode,
+    AssertionFailed,
+    SlotTypeMismatch,
+    GasExhausted,
+    MemoryLimitExceeded,
+    OutputLimitExceeded,
+    UnconsumedInput,
+    BitIndexOutOfRange,
+    NonBooleanCondition,
+    UnexpectedEnd,
+}
+
+impl ErrorKind {
+    /// Check whether this is an error in manipulating the operand stack.
+    pub fn is_stack_error(self) -> bool {
+        matches!(self, Self::StackUnderflow)
+    }
+
+    /// Check whether this is an error in accessing the input or output streams.
+    ///
+    /// This does not include [`ErrorKind::ParseError`], which indicates that input was read
+    /// successfully but was malformed.
+    pub fn is_io_error(self) -> bool {
+        matches!(
+            self,
+            Self::NoInputStream | Self::InputError | Self::OutputError
+        )
+    }
+
+    /// Check whether this error leaves the machine in a resumable state.
+    ///
+    /// Recoverable errors are raised before the failing instruction modifies the operand stack,
+    /// the variables, or the program counter. After such an error, the instruction can be retried
+    /// with [`ExecutionContext::step`](crate::ExecutionContext::step), e.g. once more input is
+    /// available, and execution will proceed as though the error had not occurred. Input that was
+    /// consumed before the error is not restored.
+    ///
+    /// This includes [`ErrorKind::GasExhausted`], which is raised before an instruction begins
+    /// executing; stepping can continue once a higher limit is set with
+    /// [`ExecutionContext::with_gas_limit`](crate::ExecutionContext::with_gas_limit).
+    ///
+    /// Other errors may leave the failing instruction partially executed; e.g. a `div` that
+    /// divides by zero has already popped its operands.
+    pub fn is_recoverable(self) -> bool {
+        matches!(
+            self,
+            Self::NoInputStream | Self::InputError | Self::ParseError | Self::GasExhausted
+        )
+    }
 }
 
 impl Display for ErrorKind {
@@ -35,11 +90,27 @@ impl Display for ErrorKind {
             Self::InputError => write!(f, "could not read input stream"),
             Self::OutputError => write!(f, "could not write to output stream"),
             Self::ParseError => write!(f, "could not parse integer value"),
+            Self::InvalidEntryPoint => write!(f, "entry point is not a valid instruction address"),
+            Self::TimeLimitExceeded => write!(f, "time limit exceeded"),
+            Self::UnrecognizedOpcode => write!(f, "unrecognized opcode"),
+            Self::AssertionFailed => write!(f, "assertion failed"),
+            Self::SlotTypeMismatch => write!(f, "stack does not match expected slot types"),
+            Self::GasExhausted => write!(f, "gas limit exhausted"),
+            Self::MemoryLimitExceeded => write!(f, "memory limit exceeded"),
+            Self::OutputLimitExceeded => write!(f, "output limit exceeded"),
+            Self::UnconsumedInput => write!(f, "program finished without consuming all input"),
+            Self::BitIndexOutOfRange => write!(f, "bit index out of range"),
+            Self::NonBooleanCondition => write!(f, "condition is neither 0 nor 1"),
+            Self::UnexpectedEnd => write!(f, "unexpected end of program"),
         }
     }
 }
 
 /// VM runtime errors
+///
+/// Fallible [`Value`](crate::value::Value) operations return the lighter-weight
+/// [`ValueError`](crate::value::ValueError) instead, which the VM converts into this type when
+/// such an error occurs during execution.
 #[derive(Debug, thiserror::Error)]
 //#[error("runtime error at program counter {program_counter}: {kind}")]
 pub struct Error {
@@ -54,6 +125,13 @@ pub struct Error {
 
     /// A trace of the program execution, if the context was configured to record it.
     pub trace: Option<Vec<Snapshot>>,
+
+    /// The number of bytes of output produced before the error was encountered.
+    ///
+    /// When the context has an output stream, only bytes successfully written to it are counted.
+    /// For [`ErrorKind::OutputError`], this is the position in the output stream at which writing
+    /// failed, including any part of the failing line that was written.
+    pub output_bytes: u64,
 }
 
 impl Display for Error {
@@ -72,6 +150,38 @@ impl Display for Error {
     }
 }
 
+impl Error {
+    /// Get the kind of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+impl From<ValueError> for ErrorKind {
+    fn from(err: ValueError) -> Self {
+        match err {
+            ValueError::DivideByZero => Self::DivideByZero,
+            ValueError::BitIndexOutOfRange => Self::BitIndexOutOfRange,
+        }
+    }
+}
+
+impl From<DecodeError> for ErrorKind {
+    fn from(err: DecodeError) -> Self {
+        match err {
+            DecodeError::UnexpectedEnd => Self::UnexpectedEnd,
+            DecodeError::UnrecognizedOpcode => Self::UnrecognizedOpcode,
+            DecodeError::IncompleteLiteral => Self::IncompleteLiteral,
+        }
+    }
+}
+
+impl From<ValueError> for Error {
+    fn from(err: ValueError) -> Self {
+        Error::from(ErrorKind::from(err))
+    }
+}
+
 impl From<ErrorKind> for Error {
     fn from(kind: ErrorKind) -> Self {
         Error {
@@ -79,6 +189,78 @@ impl From<ErrorKind> for Error {
             program_counter: 0,
             instr: None,
             trace: None,
+            output_bytes: 0,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ErrorKind::*;
+
+    const ALL_KINDS: &[ErrorKind] = &[
+        StackUnderflow,
+        OutOfBoundsVariableReference,
+        DivideByZero,
+        IncompleteLiteral,
+        AllocationError,
+        NoInputStream,
+        InputError,
+        OutputError,
+        ParseError,
+        InvalidEntryPoint,
+        TimeLimitExceeded,
+        UnrecognizedOpcode,
+        AssertionFailed,
+        SlotTypeMismatch,
+        GasExhausted,
+        MemoryLimitExceeded,
+        OutputLimitExceeded,
+        UnconsumedInput,
+        BitIndexOutOfRange,
+        NonBooleanCondition,
+        UnexpectedEnd,
+    ];
+
+    fn kinds_where(pred: fn(ErrorKind) -> bool) -> Vec<ErrorKind> {
+        ALL_KINDS.iter().copied().filter(|&k| pred(k)).collect()
+    }
+
+    #[test]
+    fn predicates() {
+        assert_eq!(kinds_where(ErrorKind::is_stack_error), [StackUnderflow]);
+        assert_eq!(
+            kinds_where(ErrorKind::is_io_error),
+            [NoInputStream, InputError, OutputError]
+        );
+        assert_eq!(
+            kinds_where(ErrorKind::is_recoverable),
+            [NoInputStream, InputError, ParseError, GasExhausted]
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        // A program truncated before an opcode is distinct from one truncated within a literal
+        let truncated_opcode = Instruction::decode_at(&[], 0).unwrap_err();
+        assert_eq!(ErrorKind::from(truncated_opcode), UnexpectedEnd);
+
+        let truncated_literal =
+            Instruction::decode_at(&[hypescript_bytecode::consts::PUSH16, 0], 0).unwrap_err();
+        assert_eq!(ErrorKind::from(truncated_literal), IncompleteLiteral);
+
+        assert_eq!(
+            ErrorKind::from(DecodeError::UnrecognizedOpcode),
+            UnrecognizedOpcode
+        );
+    }
+
+    #[test]
+    fn kind_accessor() {
+        let err = Error::from(DivideByZero);
+        assert_eq!(err.kind(), DivideByZero);
+        assert_eq!(err.kind(), err.kind);
+    }
+}
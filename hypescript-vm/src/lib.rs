@@ -3,19 +3,51 @@
 //! This crate implements the HypeScript VM execution engine.
 
 use crate::error::*;
-use crate::trace::{format_trace, format_vars};
+use crate::trace::{format_pc_trace, format_stack_with, format_trace_with, format_vars_with};
 
+use std::borrow::Cow;
+use std::collections::VecDeque;
 use std::fmt::{self, Debug, Display, Formatter};
 use std::io::{BufRead, Write};
-
-use hypescript_bytecode::{Instruction, Opcode};
-use trace::{format_stack, Snapshot};
-use value::Value;
-
+use std::ops::ControlFlow;
+use std::time::Duration;
+
+use clock::{Clock, SystemClock};
+use hypescript_bytecode::program::Program;
+use hypescript_bytecode::slot_types::{SlotType, SlotTypeMap};
+use hypescript_bytecode::{instructions_to_vec, Instruction, Opcode};
+use profile::Profile;
+use trace::{Snapshot, StepInfo, TraceFormat};
+use value::{Value, ValueError};
+
+pub mod clock;
 pub mod error;
+pub mod prelude;
+pub mod profile;
 pub mod trace;
 pub mod value;
 
+/// The number of instructions executed between checks of the time limit set with
+/// [`ExecutionContext::with_time_limit`].
+pub const TIME_CHECK_INTERVAL: usize = 1024;
+
+/// The gas limit applied by [`ExecutionContext::with_sandbox_defaults`].
+pub const SANDBOX_GAS_LIMIT: u64 = 100_000_000;
+
+/// The time limit applied by [`ExecutionContext::with_sandbox_defaults`].
+pub const SANDBOX_TIME_LIMIT: Duration = Duration::from_secs(10);
+
+/// The memory limit, in values, applied by [`ExecutionContext::with_sandbox_defaults`].
+///
+/// This allows the stack and variables together to occupy 8 MiB.
+pub const SANDBOX_MEMORY_LIMIT: usize = 1 << 20;
+
+/// The output limit, in bytes, applied by [`ExecutionContext::with_sandbox_defaults`].
+pub const SANDBOX_OUTPUT_LIMIT: u64 = 1 << 20;
+
+/// A function called before each instruction is executed, which may abort execution.
+type StepHook<'p> = Box<dyn FnMut(&StepInfo) -> ControlFlow<()> + 'p>;
+
 /// Execution context for a HypeScript program.
 ///
 /// This contains the machine state for a running HypeScript program. The input and output streams
@@ -30,18 +62,40 @@ pub mod value;
 ///
 /// The lifetime parameters are as follows:
 ///
-/// - `'p`: The lifetime of the program data.
+/// - `'p`: The lifetime of the program data, and of any step hook.
 /// - `'i`: The lifetime of the input stream (`'static` if it is owned or not configured).
 /// - `'o`: The lifetime of the output stream (`'static` if it is owned or not configured).
 pub struct ExecutionContext<'p, 'i, 'o> {
-    program: &'p [u8],
+    program: Cow<'p, [u8]>,
     program_counter: usize,
     stack: Vec<Value>,
     local_vars: Vec<Value>,
     input_stream: Option<Box<dyn BufRead + 'i>>,
     input_buffer: Vec<String>,
     output_stream: Option<Box<dyn Write + 'o>>,
-    trace: Option<Vec<Snapshot>>,
+    captured_output: Option<String>,
+    trace: Option<VecDeque<Snapshot>>,
+    trace_limit: Option<usize>,
+    pc_trace: Option<Vec<(usize, Opcode)>>,
+    profile: Option<Profile>,
+    instruction_count: usize,
+    peak_stack_depth: usize,
+    input_radix: u32,
+    print_width: usize,
+    output_bytes: u64,
+    time_limit: Option<Duration>,
+    clock: Box<dyn Clock>,
+    gas_limit: Option<u64>,
+    gas_used: u64,
+    cost_model: fn(Opcode) -> u32,
+    memory_limit: Option<usize>,
+    output_limit: Option<u64>,
+    require_input_consumed: bool,
+    flush_each_print: bool,
+    strict_booleans: bool,
+    slot_types: Option<SlotTypeMap>,
+    step_hook: Option<StepHook<'p>>,
+    termination: Option<Termination>,
 }
 
 impl Debug for ExecutionContext<'_, '_, '_> {
@@ -63,18 +117,69 @@ impl<'p, 'i, 'o> ExecutionContext<'p, 'i, 'o> {
     /// output streams will be unconfigured; use [`ExecutionContext::with_input_stream`] and
     /// [`ExecutionContext::with_output_stream`] to configure them.
     pub fn new(program: &'p [u8]) -> Self {
+        Self::from_program_data(Cow::Borrowed(program))
+    }
+
+    /// Create a new `ExecutionContext` for a sequence of instructions.
+    ///
+    /// The instructions are encoded into an owned copy of the program, laid out contiguously from
+    /// program address 0 as by [`write_instructions`](hypescript_bytecode::write_instructions).
+    /// Apart from not borrowing its program, the new context is identical to one created with
+    /// [`ExecutionContext::new`] from the encoded bytes.
+    pub fn from_instructions(instrs: &[Instruction]) -> Self {
+        Self::from_program_data(Cow::Owned(instructions_to_vec(instrs)))
+    }
+
+    /// Create a new `ExecutionContext` with all protective limits set to conservative defaults.
+    ///
+    /// This is equivalent to calling [`ExecutionContext::with_sandbox_defaults`] on a context
+    /// created with [`ExecutionContext::new`].
+    pub fn sandboxed(program: &'p [u8]) -> Self {
+        Self::new(program).with_sandbox_defaults()
+    }
+
+    fn from_program_data(program: Cow<'p, [u8]>) -> Self {
         Self {
             program,
             program_counter: 0,
             stack: Vec::new(),
             local_vars: Vec::new(),
             output_stream: None,
+            captured_output: None,
             input_stream: None,
             input_buffer: Vec::new(),
             trace: None,
+            trace_limit: None,
+            pc_trace: None,
+            profile: None,
+            instruction_count: 0,
+            peak_stack_depth: 0,
+            input_radix: 10,
+            print_width: 0,
+            output_bytes: 0,
+            time_limit: None,
+            clock: Box::new(SystemClock),
+            gas_limit: None,
+            gas_used: 0,
+            cost_model: Opcode::cost,
+            memory_limit: None,
+            output_limit: None,
+            require_input_consumed: false,
+            flush_each_print: false,
+            strict_booleans: false,
+            slot_types: None,
+            step_hook: None,
+            termination: None,
         }
     }
 
+    /// Create a new `ExecutionContext` for a validated [`Program`].
+    ///
+    /// This is equivalent to calling [`ExecutionContext::new`] with the program's bytes.
+    pub fn from_program(program: &'p Program) -> Self {
+        Self::new(program.bytes())
+    }
+
     /// A builder method to set the input stream for this execution context.
     pub fn with_input_stream<R: BufRead + 'i>(self, stream: R) -> Self {
         Self {
@@ -91,799 +196,3776 @@ impl<'p, 'i, 'o> ExecutionContext<'p, 'i, 'o> {
         }
     }
 
-    /// Enable recording a trace of the execution of the program.
+    /// A builder method to flush the output stream after every line printed.
     ///
-    /// If tracing is enabled, a snapshot of the machine state will be saved before each
-    /// instruction, and the full list of snapshots will be returned with the execution summary and
-    /// with any runtime errors.
-    pub fn with_trace(self) -> Self {
+    /// Each printed line is always written to the output stream as soon as it is printed, but a
+    /// buffered stream may hold it back. With this option, the stream is flushed after each line,
+    /// so that a host reading the output concurrently sees it promptly. A failed flush is reported
+    /// as an [`ErrorKind::OutputError`].
+    pub fn with_flush_each_print(self) -> Self {
         Self {
-            trace: Some(Vec::new()),
+            flush_each_print: true,
             ..self
         }
     }
 
-    /// Consume the context, and execute the loaded program.
-    pub fn run(mut self) -> Result<ExecutionSummary> {
-        while (self.program_counter) < self.program.len() {
-            let pc = self.program_counter;
-            let mut stream = &self.program[pc..];
-            let instr = Instruction::decode_from_stream(&mut stream).map_err(|err| {
-                debug_assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
-                Error {
-                    kind: ErrorKind::IncompleteLiteral,
-                    program_counter: self.program_counter,
-                    instr: None,
-                    trace: self.trace.clone(),
-                }
-            })?;
-
-            if self.trace.is_some() {
-                let snapshot = self.generate_snapshot(instr);
-                self.trace.as_mut().unwrap().push(snapshot);
-            }
-
-            let advance = self.execute_instruction(instr).map_err(|err| Error {
-                program_counter: self.program_counter,
-                instr: Some(instr),
-                trace: self.trace.clone(),
-                ..err
-            })?;
-            if advance == 0 {
-                break;
-            } else {
-                self.program_counter += advance;
-            }
+    /// A builder method to require that conditions are canonical booleans.
+    ///
+    /// By default, `jcond` treats any nonzero condition as true, and `not` maps any nonzero value
+    /// to 0. With this option, both instructions halt the machine with an
+    /// [`ErrorKind::NonBooleanCondition`] error if their condition is anything other than 0 or 1.
+    /// This is intended for catching non-boolean values that leak into conditions, e.g. due to a
+    /// miscompilation.
+    pub fn with_strict_booleans(self) -> Self {
+        Self {
+            strict_booleans: true,
+            ..self
         }
+    }
 
-        Ok(ExecutionSummary {
-            program_counter: self.program_counter,
-            stack: self.stack,
-            local_vars: self.local_vars,
-            trace: self.trace,
-        })
+    /// A builder method to optionally set the input stream for this execution context.
+    ///
+    /// This is identical to [`ExecutionContext::with_input_stream`] if `stream` is `Some`. If it is
+    /// `None`, the context will have no input stream.
+    pub fn with_optional_input_stream<R: BufRead + 'i>(self, stream: Option<R>) -> Self {
+        Self {
+            input_stream: stream.map(|stream| Box::new(stream) as Box<dyn BufRead + 'i>),
+            ..self
+        }
     }
 
-    fn generate_snapshot(&self, next_instruction: Instruction) -> Snapshot {
-        Snapshot {
-            program_counter: self.program_counter,
-            next_instruction,
-            stack: self.stack.clone(),
-            local_variables: self.local_vars.clone(),
+    /// A builder method to optionally set the output stream for this execution context.
+    ///
+    /// This is identical to [`ExecutionContext::with_output_stream`] if `stream` is `Some`. If it
+    /// is `None`, the context will have no output stream.
+    pub fn with_optional_output_stream<W: Write + 'o>(self, stream: Option<W>) -> Self {
+        Self {
+            output_stream: stream.map(|stream| Box::new(stream) as Box<dyn Write + 'o>),
+            ..self
         }
     }
-}
 
-/// A snapshot of the machine state at the end of program execution.
-#[derive(Debug, Clone)]
-pub struct ExecutionSummary {
-    pub program_counter: usize,
-    pub stack: Vec<Value>,
-    pub local_vars: Vec<Value>,
-    pub trace: Option<Vec<Snapshot>>,
-}
+    /// A builder method to capture the program's output internally.
+    ///
+    /// All output from `print` and `prints` will be buffered and returned as a string in the
+    /// [`ExecutionSummary`]. This is independent of the output stream; if an output stream is also
+    /// configured, output will be written to both.
+    pub fn with_captured_output(self) -> Self {
+        Self {
+            captured_output: Some(String::new()),
+            ..self
+        }
+    }
 
-impl Display for ExecutionSummary {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        if let Some(trace) = self.trace.as_ref() {
-            writeln!(f, "== EXECUTION TRACE ==")?;
-            format_trace(f, trace)?;
+    /// A builder method to set the numeric radix used to parse input for `read` and `reads`.
+    ///
+    /// The default radix is 10.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `radix` is not in the range 2 through 36, inclusive.
+    pub fn with_input_radix(self, radix: u32) -> Self {
+        assert!(
+            (2..=36).contains(&radix),
+            "input radix must be between 2 and 36 (got {radix})"
+        );
 
-            writeln!(f)?;
+        Self {
+            input_radix: radix,
+            ..self
         }
+    }
 
-        writeln!(f, "== EXECUTION END STATE ==")?;
-        writeln!(f, "pc {}", self.program_counter)?;
+    /// A builder method to require that the program consume all of its input.
+    ///
+    /// When the program finishes, [`ExecutionContext::run`] checks for input tokens that have not
+    /// been read, whether already buffered or still in the input stream, and fails with a
+    /// [`ErrorKind::UnconsumedInput`] error if there are any. Blank and whitespace-only lines are
+    /// ignored, as they are by reads. The check consumes the rest of the input stream.
+    pub fn with_require_input_consumed(self) -> Self {
+        Self {
+            require_input_consumed: true,
+            ..self
+        }
+    }
 
-        writeln!(f, "stack")?;
-        format_stack(f, &self.stack)?;
+    /// A builder method to set the minimum width of integers printed by `print`, `prints`,
+    /// `printpeek`, and `printpeeks`.
+    ///
+    /// Printed values narrower than `width` characters are right-aligned by padding them with
+    /// spaces on the left; wider values are printed in full. The default width is 0, i.e. no
+    /// padding. This applies equally to output streams and captured output. Values are always
+    /// printed in decimal, regardless of any radix set with
+    /// [`ExecutionContext::with_input_radix`], and `printf` output is not padded.
+    pub fn with_print_width(self, width: usize) -> Self {
+        Self {
+            print_width: width,
+            ..self
+        }
+    }
 
-        writeln!(f, "vars")?;
-        format_vars(f, &self.local_vars)
+    /// A builder method to set the entry point of the program.
+    ///
+    /// Execution will begin at the given program address rather than at 0. This allows for
+    /// bytecode layouts with a leading section that is not executed, such as a header or metadata
+    /// section.
+    ///
+    /// The entry point is validated when [`ExecutionContext::run`] is called; it must be within the
+    /// bounds of the program, and must be the address of a complete, recognized instruction in a
+    /// linear decoding of the program from address 0. Any leading section must therefore still
+    /// decode as a sequence of instructions, e.g. by embedding data in push literals.
+    pub fn with_entry_point(self, pc: usize) -> Self {
+        Self {
+            program_counter: pc,
+            ..self
+        }
     }
-}
 
-impl ExecutionContext<'_, '_, '_> {
-    fn execute_instruction(&mut self, instr: Instruction) -> Result<usize> {
-        match instr.opcode {
-            Opcode::VarSt => self.varst(),
-            Opcode::VarLd => self.varld(),
-            Opcode::VarRes => self.varres(),
-            Opcode::VarDisc => self.vardisc(),
-            Opcode::NumVars => {
-                self.numvars();
-                Ok(())
-            }
-            Opcode::Push8
-            | Opcode::Push8S
-            | Opcode::Push16
-            | Opcode::Push16S
-            | Opcode::Push32
-            | Opcode::Push32S
-            | Opcode::Push64 => {
-                self.pushn(Value::from_u64(instr.literal));
-                Ok(())
-            }
-            Opcode::Dup0 => self.dupn(0),
-            Opcode::Dup1 => self.dupn(1),
-            Opcode::Dup2 => self.dupn(2),
-            Opcode::Dup3 => self.dupn(3),
-            Opcode::Pop => self.pop(),
-            Opcode::Swap => self.swap(),
-            Opcode::Add => self.binop_infallible(Value::add),
-            Opcode::Sub => self.binop_infallible(Value::sub),
-            Opcode::Mul => self.binop_infallible(Value::mul),
-            Opcode::Mod => self.binop_fallible(Value::mod_),
-            Opcode::Div => self.binop_fallible(Value::div_unsigned),
-            Opcode::DivS => self.binop_fallible(Value::div_signed),
-            Opcode::Gt => self.binop_infallible(Value::greater_unsigned),
-            Opcode::GtS => self.binop_infallible(Value::greater_signed),
-            Opcode::Lt => self.binop_infallible(Value::less_unsigned),
-            Opcode::LtS => self.binop_infallible(Value::less_signed),
-            Opcode::Ge => self.binop_infallible(Value::greater_or_eq_unsigned),
-            Opcode::GeS => self.binop_infallible(Value::greater_or_eq_signed),
-            Opcode::Le => self.binop_infallible(Value::less_or_eq_unsigned),
-            Opcode::LeS => self.binop_infallible(Value::less_or_eq_signed),
-            Opcode::Eq => self.binop_infallible(Value::eq),
-            Opcode::And => self.binop_infallible(Value::and),
-            Opcode::Or => self.binop_infallible(Value::or),
-            Opcode::Xor => self.binop_infallible(Value::xor),
-            Opcode::Not => self.unop(Value::not),
-            Opcode::Inv => self.unop(Value::inv),
-            Opcode::Jump => self.jump(),
-            Opcode::JCond => self.jcond(),
-            Opcode::Read => self.read(false),
-            Opcode::ReadS => self.read(true),
-            Opcode::Print => self.print(false),
-            Opcode::PrintS => self.print(true),
-            Opcode::Halt => return Ok(0),
-        }?;
+    /// A builder method to pre-allocate space for the given number of local variables.
+    ///
+    /// This does not change the size of the variable array, which still starts out empty; it only
+    /// ensures that `varres` instructions need not reallocate the array until more than `n`
+    /// variables are in use. Since `vardisc` never releases capacity, a program that reserves and
+    /// discards variables in a loop will only reallocate while the array grows past its previous
+    /// peak size; a hint at least as large as that peak (e.g. the variable count computed by the
+    /// compiler) avoids reallocation altogether.
+    pub fn with_vars_hint(self, n: usize) -> Self {
+        Self {
+            local_vars: Vec::with_capacity(n),
+            ..self
+        }
+    }
 
-        Ok(1 + instr.opcode.literal_len())
+    /// A builder method to set a wall-clock time limit on the execution of the program.
+    ///
+    /// If the program runs for longer than the given duration, execution will halt with a
+    /// [`ErrorKind::TimeLimitExceeded`] error. To amortize the cost of querying the system clock,
+    /// the elapsed time is only checked once every [`TIME_CHECK_INTERVAL`] instructions, so the
+    /// limit may be overrun by the time taken to execute that many instructions.
+    pub fn with_time_limit(self, limit: Duration) -> Self {
+        Self {
+            time_limit: Some(limit),
+            ..self
+        }
     }
 
-    fn pop_stack(&mut self) -> Result<Value> {
-        self.stack
-            .pop()
-            .ok_or_else(|| Error::from(ErrorKind::StackUnderflow))
+    /// A builder method to set the clock used to enforce the time limit.
+    ///
+    /// By default, the time limit is measured with the system clock. The clock is queried once
+    /// when execution starts, and then once every [`TIME_CHECK_INTERVAL`] instructions.
+    pub fn with_clock(self, clock: impl Clock + 'static) -> Self {
+        Self {
+            clock: Box::new(clock),
+            ..self
+        }
     }
 
-    fn push_stack(&mut self, val: Value) {
-        self.stack.push(val);
+    /// A builder method to limit the total cost of the instructions executed by the program.
+    ///
+    /// Each instruction is charged according to the cost model, which defaults to
+    /// [`Opcode::cost`]. If executing an instruction would bring the total cost above `gas`,
+    /// execution halts with a [`ErrorKind::GasExhausted`] error before the instruction is
+    /// executed.
+    pub fn with_gas_limit(self, gas: u64) -> Self {
+        Self {
+            gas_limit: Some(gas),
+            ..self
+        }
     }
 
-    fn read_var(&self, n: Value) -> Result<Value> {
-        let n = n.as_u64() as usize;
-        self.local_vars
-            .get(n)
-            .copied()
-            .ok_or_else(|| Error::from(ErrorKind::OutOfBoundsVariableReference))
+    /// A builder method to set the cost model used to charge instructions against the gas limit.
+    pub fn with_cost_model(self, cost_model: fn(Opcode) -> u32) -> Self {
+        Self { cost_model, ..self }
     }
 
-    fn write_var(&mut self, n: Value, x: Value) -> Result<()> {
-        let n = n.as_u64() as usize;
-        self.local_vars
-            .get_mut(n)
-            .map(|var| *var = x)
-            .ok_or_else(|| Error::from(ErrorKind::OutOfBoundsVariableReference))
+    /// A builder method to limit the number of values held in the stack and variables together.
+    ///
+    /// A `varres` instruction that would exceed the limit fails with a
+    /// [`ErrorKind::MemoryLimitExceeded`] error before allocating. Any other instruction that
+    /// leaves the machine over the limit fails with the same error after executing, without
+    /// advancing the program counter.
+    pub fn with_memory_limit(self, values: usize) -> Self {
+        Self {
+            memory_limit: Some(values),
+            ..self
+        }
     }
 
-    fn varst(&mut self) -> Result<()> {
-        let n = self.pop_stack()?;
-        let x = self.pop_stack()?;
-        self.write_var(n, x)
+    /// A builder method to limit the number of bytes the program may print.
+    ///
+    /// A print instruction that would bring the total above the limit fails with a
+    /// [`ErrorKind::OutputLimitExceeded`] error, and prints nothing. The total is the same count
+    /// reported by [`Error::output_bytes`] and [`ExecutionSummary::output_bytes`].
+    pub fn with_output_limit(self, bytes: u64) -> Self {
+        Self {
+            output_limit: Some(bytes),
+            ..self
+        }
     }
 
-    fn varld(&mut self) -> Result<()> {
-        let n = self.pop_stack()?;
-        let x = self.read_var(n)?;
-        self.push_stack(x);
-        Ok(())
+    /// A builder method to apply conservative defaults for all protective limits.
+    ///
+    /// This is intended for running untrusted programs. The defaults are:
+    ///
+    /// - a gas limit of [`SANDBOX_GAS_LIMIT`], with the default cost model;
+    /// - a time limit of [`SANDBOX_TIME_LIMIT`];
+    /// - a memory limit of [`SANDBOX_MEMORY_LIMIT`] values; and
+    /// - an output limit of [`SANDBOX_OUTPUT_LIMIT`] bytes.
+    ///
+    /// Any of these can be overridden by calling the corresponding builder method afterwards.
+    pub fn with_sandbox_defaults(self) -> Self {
+        self.with_gas_limit(SANDBOX_GAS_LIMIT)
+            .with_time_limit(SANDBOX_TIME_LIMIT)
+            .with_memory_limit(SANDBOX_MEMORY_LIMIT)
+            .with_output_limit(SANDBOX_OUTPUT_LIMIT)
     }
 
-    fn varres(&mut self) -> Result<()> {
-        let n = self.pop_stack()?.as_u64() as usize;
-        self.local_vars
-            .try_reserve(n)
-            .map_err(|_| Error::from(ErrorKind::AllocationError))?;
-        self.local_vars
-            .resize(self.local_vars.len() + n, Value::default());
+    /// Enable verification of the operand stack against compiler-provided slot types.
+    ///
+    /// Before executing each instruction whose address has an entry in `types`, the VM checks
+    /// that:
+    ///
+    /// - the stack has exactly as many slots as `types` describes;
+    /// - every slot typed [`SlotType::Bool`] holds 0 or 1; and
+    /// - the condition popped by a `jcond`, `select`, or `assert` is typed [`SlotType::Bool`].
+    ///
+    /// If any check fails, execution halts with [`ErrorKind::SlotTypeMismatch`]. Instructions
+    /// without an entry in `types` are not checked. This is intended to catch miscompilations, and
+    /// slows execution considerably.
+    pub fn with_slot_type_verification(self, types: SlotTypeMap) -> Self {
+        Self {
+            slot_types: Some(types),
+            ..self
+        }
+    }
 
-        Ok(())
+    /// Enable recording a trace of the execution of the program.
+    ///
+    /// If tracing is enabled, a snapshot of the machine state will be saved before each
+    /// instruction, and the full list of snapshots will be returned with the execution summary and
+    /// with any runtime errors.
+    pub fn with_trace(self) -> Self {
+        Self {
+            trace: Some(VecDeque::new()),
+            trace_limit: None,
+            ..self
+        }
     }
 
-    fn vardisc(&mut self) -> Result<()> {
-        let n = self.pop_stack()?.as_u64() as usize;
-        if n < self.local_vars.len() {
-            self.local_vars.truncate(self.local_vars.len() - n);
+    /// Enable or disable recording a trace of the execution of the program.
+    ///
+    /// If `enabled` is true, this is identical to [`ExecutionContext::with_trace`]. Otherwise, any
+    /// previously enabled trace is disabled.
+    pub fn with_trace_enabled(self, enabled: bool) -> Self {
+        if enabled {
+            self.with_trace()
         } else {
-            self.local_vars.clear();
+            Self {
+                trace: None,
+                trace_limit: None,
+                ..self
+            }
         }
-        Ok(())
     }
 
-    fn numvars(&mut self) {
-        self.push_stack(Value::from_u64(self.local_vars.len() as u64));
+    /// Enable recording a trace of the execution of the program, keeping only the most recent
+    /// snapshots.
+    ///
+    /// This is identical to [`ExecutionContext::with_trace`], except that at most `limit`
+    /// snapshots are retained; once the trace is full, the oldest snapshot is discarded each time
+    /// a new one is recorded. Each snapshot's [`Snapshot::step_index`] still gives its position in
+    /// the full execution.
+    pub fn with_bounded_trace(self, limit: usize) -> Self {
+        Self {
+            trace: Some(VecDeque::with_capacity(limit)),
+            trace_limit: Some(limit),
+            ..self
+        }
     }
 
-    fn pushn(&mut self, value: Value) {
-        self.push_stack(value);
+    /// Enable recording a lightweight trace of the program counter and opcode of each executed
+    /// instruction.
+    ///
+    /// Unlike [`ExecutionContext::with_trace`], this does not copy the stack or local variables
+    /// at each step, and so is considerably cheaper. The resulting list of `(program counter,
+    /// opcode)` pairs will be returned with the execution summary.
+    pub fn with_pc_trace(self) -> Self {
+        Self {
+            pc_trace: Some(Vec::new()),
+            ..self
+        }
     }
 
-    fn dupn(&mut self, n: usize) -> Result<()> {
-        if n < self.stack.len() {
-            let v = self.stack[self.stack.len() - 1 - n];
-            self.push_stack(v);
-            Ok(())
-        } else {
-            Err(Error::from(ErrorKind::StackUnderflow))
+    /// Enable recording a profile of the number of times each opcode is executed.
+    ///
+    /// The profile will be returned with the execution summary.
+    pub fn with_profile(self) -> Self {
+        Self {
+            profile: Some(Profile::default()),
+            ..self
         }
     }
 
-    fn pop(&mut self) -> Result<()> {
-        self.pop_stack()?;
-        Ok(())
+    /// Register a function to be called before each instruction is executed.
+    ///
+    /// The hook receives a [`StepInfo`] describing the program counter, the instruction about to
+    /// be executed, and the sizes of the stack and variables array. It is called after the gas
+    /// limit check for the instruction, and before the instruction is recorded in the gas count,
+    /// trace, or profile and before any slot type checks. It is not called for instructions that
+    /// fail to decode or exhaust the gas limit.
+    /// The hook has no access to the machine itself, and so cannot modify its state. Registering a
+    /// hook replaces any previously registered one.
+    pub fn with_step_hook<F>(self, mut hook: F) -> Self
+    where
+        F: FnMut(&StepInfo) + 'p,
+    {
+        self.with_abortable_step_hook(move |info| {
+            hook(info);
+            ControlFlow::Continue(())
+        })
     }
 
-    fn swap(&mut self) -> Result<()> {
-        if self.stack.len() < 2 {
-            Err(Error::from(ErrorKind::StackUnderflow))
-        } else {
-            let len = self.stack.len();
-            self.stack.swap(len - 1, len - 2);
-            Ok(())
+    /// Register a function to be called before each instruction is executed, which may stop
+    /// execution early.
+    ///
+    /// This is identical to [`ExecutionContext::with_step_hook`], except that if the hook returns
+    /// [`ControlFlow::Break`], the instruction is not executed and the program terminates with
+    /// [`Termination::HostAborted`]. The machine state, including gas used and any trace, is left
+    /// as it was before the instruction, and the summary returned by [`ExecutionContext::run`]
+    /// reflects the partial execution.
+    pub fn with_abortable_step_hook<F>(self, hook: F) -> Self
+    where
+        F: FnMut(&StepInfo) -> ControlFlow<()> + 'p,
+    {
+        Self {
+            step_hook: Some(Box::new(hook)),
+            ..self
         }
     }
 
-    fn binop_infallible(&mut self, op: fn(Value, Value) -> Value) -> Result<()> {
-        let b = self.pop_stack()?;
-        let a = self.pop_stack()?;
-        self.push_stack(op(a, b));
-        Ok(())
+    /// Consume the context, and execute the loaded program.
+    pub fn run(self) -> Result<ExecutionSummary> {
+        self.run_with_output().map(|(summary, _)| summary)
     }
 
-    fn binop_fallible(&mut self, op: fn(Value, Value) -> Result<Value>) -> Result<()> {
-        let b = self.pop_stack()?;
-        let a = self.pop_stack()?;
-        self.push_stack(op(a, b)?);
-        Ok(())
-    }
+    /// Consume the context, execute the loaded program, and return the output stream along with
+    /// the execution summary.
+    ///
+    /// This allows an owned output stream (e.g. a `Vec<u8>` passed by value to
+    /// [`ExecutionContext::with_output_stream`]) to be recovered after execution. The returned
+    /// stream is `None` if no output stream was configured. If execution fails with a runtime
+    /// error, the output stream is dropped.
+    // `usize::is_multiple_of` is only available from Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn run_with_output(mut self) -> Result<(ExecutionSummary, Option<Box<dyn Write + 'o>>)> {
+        let deadline = self.time_limit.map(|limit| self.clock.now() + limit);
+
+        let termination = loop {
+            if let Some(deadline) = deadline {
+                if self.termination.is_none()
+                    && self.instruction_count % TIME_CHECK_INTERVAL == 0
+                    && self.clock.now() >= deadline
+                {
+                    return Err(Error {
+                        program_counter: self.program_counter,
+                        trace: self.trace_vec(),
+                        output_bytes: self.output_bytes,
+                        ..Error::from(ErrorKind::TimeLimitExceeded)
+                    });
+                }
+            }
 
-    fn unop(&mut self, op: fn(Value) -> Value) -> Result<()> {
-        let a = self.pop_stack()?;
-        self.push_stack(op(a));
-        Ok(())
-    }
+            let termination = self.step()?;
 
-    fn jump(&mut self) -> Result<()> {
-        let n = self.pop_stack()?.as_i64() as isize;
-        self.program_counter = self.program_counter.wrapping_add_signed(n);
-        Ok(())
-    }
+            #[cfg(feature = "vm-invariants")]
+            self.check_invariants();
 
-    fn jcond(&mut self) -> Result<()> {
-        let n = self.pop_stack()?.as_i64() as isize;
-        let b = self.pop_stack()?.as_u64();
+            if let Some(termination) = termination {
+                break termination;
+            }
+        };
 
-        if b != 0 {
-            self.program_counter = self.program_counter.wrapping_add_signed(n);
+        if self.require_input_consumed {
+            self.check_input_consumed().map_err(|err| Error {
+                program_counter: self.program_counter,
+                trace: self.trace_vec(),
+                output_bytes: self.output_bytes,
+                ..err
+            })?;
         }
 
-        Ok(())
+        let summary = ExecutionSummary {
+            program_counter: self.program_counter,
+            stack: self.stack,
+            local_vars: self.local_vars,
+            trace: self.trace.map(Vec::from),
+            pc_trace: self.pc_trace,
+            profile: self.profile,
+            instruction_count: self.instruction_count,
+            peak_stack_depth: self.peak_stack_depth,
+            gas_used: self.gas_used,
+            output: self.captured_output,
+            output_bytes: self.output_bytes,
+            termination,
+        };
+
+        Ok((summary, self.output_stream))
     }
 
-    fn fill_input_buffer(&mut self) -> Result<()> {
-        if let Some(input) = self.input_stream.as_mut() {
-            if self.input_buffer.is_empty() {
-                let mut line = String::new();
-                input
-                    .read_line(&mut line)
-                    .map_err(|_| Error::from(ErrorKind::InputError))?;
-                self.input_buffer = line.split_whitespace().rev().map(String::from).collect();
+    /// Execute a single instruction.
+    ///
+    /// Returns `Some` with the manner of termination if the program has finished, either by
+    /// executing a `halt` instruction, by the program counter going out of bounds of the
+    /// program, or by a step hook aborting execution; once the program has finished, further
+    /// calls do nothing and return the same value. Returns `None` if there are more instructions
+    /// to execute.
+    ///
+    /// The entry point is validated before the first instruction is executed. If an instruction
+    /// fails with a runtime error, the machine is left in whatever state the instruction left it
+    /// in, and the program counter is not advanced.
+    ///
+    /// Any time limit set with [`ExecutionContext::with_time_limit`] applies only to
+    /// [`ExecutionContext::run`], and is not checked here.
+    pub fn step(&mut self) -> Result<Option<Termination>> {
+        if let Some(termination) = self.termination {
+            return Ok(Some(termination));
+        }
+
+        if self.instruction_count == 0 {
+            self.validate_entry_point()?;
+        }
+
+        let pc = self.program_counter;
+        if pc >= self.program.len() {
+            self.termination = Some(Termination::FellOffEnd);
+            return Ok(self.termination);
+        }
+
+        let (instr, _) = Instruction::decode_at(&self.program, pc).map_err(|err| Error {
+            kind: ErrorKind::from(err),
+            program_counter: pc,
+            instr: None,
+            trace: self.trace_vec(),
+            output_bytes: self.output_bytes,
+        })?;
+
+        let gas_used = self.gas_used + u64::from((self.cost_model)(instr.opcode));
+        if self.gas_limit.is_some_and(|limit| gas_used > limit) {
+            return Err(Error {
+                program_counter: pc,
+                instr: Some(instr),
+                trace: self.trace_vec(),
+                output_bytes: self.output_bytes,
+                ..Error::from(ErrorKind::GasExhausted)
+            });
+        }
+
+        // The gas limit is checked before the hook so that an instruction retried after
+        // `GasExhausted` is only reported to the hook once. The hook runs before any other
+        // bookkeeping, so an aborted instruction leaves no trace in the gas count, trace, or
+        // profile
+        if let Some(hook) = self.step_hook.as_mut() {
+            let info = StepInfo {
+                step_index: self.instruction_count,
+                program_counter: pc,
+                next_instruction: instr,
+                stack_len: self.stack.len(),
+                local_vars_len: self.local_vars.len(),
+            };
+            if hook(&info).is_break() {
+                self.termination = Some(Termination::HostAborted);
+                return Ok(self.termination);
             }
+        }
 
-            Ok(())
+        self.gas_used = gas_used;
+
+        if self.trace.is_some() {
+            let snapshot = self.generate_snapshot(instr);
+            if let Some(trace) = self.trace.as_mut() {
+                trace.push_back(snapshot);
+                if let Some(limit) = self.trace_limit {
+                    while trace.len() > limit {
+                        trace.pop_front();
+                    }
+                }
+            }
+        }
+
+        if let Some(pc_trace) = self.pc_trace.as_mut() {
+            pc_trace.push((pc, instr.opcode));
+        }
+
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record(instr.opcode);
+        }
+
+        if let Some(types) = self.slot_types.as_ref() {
+            if let Some(types) = types.get(pc) {
+                if !slot_types_match(instr, &self.stack, types) {
+                    return Err(Error {
+                        program_counter: pc,
+                        instr: Some(instr),
+                        trace: self.trace_vec(),
+                        output_bytes: self.output_bytes,
+                        ..Error::from(ErrorKind::SlotTypeMismatch)
+                    });
+                }
+            }
+        }
+
+        // Errors raised while executing an instruction, including those converted from value
+        // errors, carry no location until this point
+        let advance = self.execute_instruction(instr).map_err(|err| Error {
+            program_counter: self.program_counter,
+            instr: Some(instr),
+            trace: self.trace_vec(),
+            output_bytes: self.output_bytes,
+            ..err
+        })?;
+
+        if self
+            .memory_limit
+            .is_some_and(|limit| self.stack.len() + self.local_vars.len() > limit)
+        {
+            return Err(Error {
+                program_counter: pc,
+                instr: Some(instr),
+                trace: self.trace_vec(),
+                output_bytes: self.output_bytes,
+                ..Error::from(ErrorKind::MemoryLimitExceeded)
+            });
+        }
+        self.instruction_count += 1;
+
+        if advance == 0 {
+            self.termination = Some(Termination::Halted);
         } else {
-            Err(Error::from(ErrorKind::NoInputStream))
+            self.program_counter += advance;
+            if self.program_counter >= self.program.len() {
+                self.termination = Some(Termination::FellOffEnd);
+            }
         }
+
+        Ok(self.termination)
     }
 
-    fn read(&mut self, signed: bool) -> Result<()> {
-        self.fill_input_buffer()?;
-        let input = self.input_buffer.pop().unwrap();
-        let val = if signed {
-            Value::from_i64(
-                input
-                    .parse()
-                    .map_err(|_| Error::from(ErrorKind::ParseError))?,
-            )
-        } else {
-            Value::from_u64(
-                input
-                    .parse()
-                    .map_err(|_| Error::from(ErrorKind::ParseError))?,
-            )
-        };
-        self.push_stack(val);
-        Ok(())
+    /// Get the current value of the program counter.
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
     }
 
-    fn print(&mut self, signed: bool) -> Result<()> {
-        let val = self.pop_stack()?;
-        if let Some(output) = self.output_stream.as_mut() {
-            if signed {
-                writeln!(output, "{}", val.as_i64())
-                    .map_err(|_| Error::from(ErrorKind::OutputError))?;
-            } else {
-                writeln!(output, "{}", val.as_u64())
-                    .map_err(|_| Error::from(ErrorKind::OutputError))?;
-            }
-        }
-        Ok(())
+    /// Get the current contents of the operand stack, with the top of the stack last.
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
     }
-}
 
-#[cfg(test)]
-mod test {
-    use hypescript_bytecode::consts::*;
+    /// Get the current contents of the local variable array.
+    pub fn local_vars(&self) -> &[Value] {
+        &self.local_vars
+    }
 
-    use super::*;
+    /// Get the instruction at the current program counter, if there is a valid one.
+    pub fn next_instruction(&self) -> Option<Instruction> {
+        Instruction::decode_at(&self.program, self.program_counter)
+            .ok()
+            .map(|(instr, _)| instr)
+    }
 
-    fn val_vec(values: &[u64]) -> Vec<Value> {
-        values.iter().copied().map(Value::from_u64).collect()
+    /// Check that the initial program counter points at a valid instruction.
+    ///
+    /// A program counter exactly at the end of the program is permitted; execution will simply
+    /// halt immediately.
+    fn validate_entry_point(&self) -> Result<()> {
+        let pc = self.program_counter;
+        if pc == self.program.len() || self.is_instruction_boundary(pc) {
+            Ok(())
+        } else {
+            Err(Error {
+                program_counter: pc,
+                ..Error::from(ErrorKind::InvalidEntryPoint)
+            })
+        }
     }
 
-    fn test_program(program: &[u8], input: &str, validate: fn(ExecutionSummary, &str)) {
-        let mut output = Vec::<u8>::new();
-        let res = ExecutionContext::new(program)
-            .with_input_stream(&mut input.as_bytes())
-            .with_output_stream(&mut output)
-            .with_trace()
-            .run();
+    /// Assert that the machine state is internally consistent, panicking if not.
+    #[cfg(feature = "vm-invariants")]
+    fn check_invariants(&self) {
+        let pc = self.program_counter;
+        match self.termination {
+            None => assert!(
+                pc < self.program.len(),
+                "running with pc {pc:#x} out of bounds"
+            ),
+            Some(Termination::Halted) => assert_eq!(
+                Instruction::decode_at(&self.program, pc).map(|(instr, _)| instr.opcode),
+                Ok(Opcode::Halt),
+                "halted with pc {pc:#x} not at a halt instruction"
+            ),
+            Some(Termination::FellOffEnd) => assert!(
+                pc >= self.program.len(),
+                "fell off end with pc {pc:#x} within the program"
+            ),
+            Some(Termination::HostAborted) => assert!(
+                pc < self.program.len(),
+                "aborted with pc {pc:#x} out of bounds"
+            ),
+        }
 
-        match res {
-            Err(err) => {
-                eprintln!("{err}");
-                panic!("VM encountered a runtime error");
+        let in_use = self.stack.len() + self.local_vars.len();
+        if let Some(limit) = self.memory_limit {
+            assert!(
+                in_use <= limit,
+                "{in_use} values in use exceeds memory limit {limit}"
+            );
+        }
+
+        if let Some(limit) = self.output_limit {
+            assert!(
+                self.output_bytes <= limit,
+                "{} bytes printed exceeds output limit {limit}",
+                self.output_bytes
+            );
+        }
+
+        if let Some(limit) = self.gas_limit {
+            assert!(
+                self.gas_used <= limit,
+                "{} gas used exceeds gas limit {limit}",
+                self.gas_used
+            );
+        }
+
+        // Only `varres` and `vardisc` change the number of variables
+        if let Some(last) = self.trace.as_ref().and_then(|trace| trace.back()) {
+            let opcode = last.next_instruction.opcode;
+            if !matches!(opcode, Opcode::VarRes | Opcode::VarDisc) {
+                assert_eq!(
+                    self.local_vars.len(),
+                    last.local_variables.len(),
+                    "variable count changed by {opcode:?}"
+                );
             }
+        }
 
-            Ok(summary) => {
-                println!("{}", summary);
-                let output = String::from_utf8(output).unwrap();
-                validate(summary, &output);
+        if let Some(trace) = &self.trace {
+            match self.trace_limit {
+                None => assert_eq!(
+                    trace.len(),
+                    self.instruction_count,
+                    "trace length does not match instruction count"
+                ),
+                Some(limit) => assert!(
+                    trace.len() <= limit,
+                    "trace length {} exceeds trace limit {limit}",
+                    trace.len()
+                ),
             }
         }
-    }
 
-    #[test]
-    fn example1() {
-        // Translated example from the challenge document:
-        // uint a = 5;
-        // uint b = 4 + a;
-        // print a;
-        // print b;
-        #[rustfmt::skip]
-        test_program(
-            &[
-                PUSH8, 2,
-                VARRES,
-                PUSH8, 5,
-                PUSH8, 0,
-                VARST,
-                PUSH8, 4,
-                PUSH8, 0,
-                VARLD,
-                ADD,
-                PUSH8, 1,
-                VARST,
-                PUSH8, 0,
-                VARLD,
-                PRINT,
-                PUSH8, 1,
-                VARLD,
-                PRINT,
-            ],
-            "",
-            |summary, output| {
-                assert_eq!(summary.local_vars, &[Value::from_u8(5), Value::from_u8(9)]);
-                assert!(summary.stack.is_empty());
-                assert_eq!(summary.program_counter, 25);
-                assert_eq!(output, "5\n9\n");
-            },
-        );
+        if let Some(pc_trace) = &self.pc_trace {
+            assert_eq!(
+                pc_trace.len(),
+                self.instruction_count,
+                "pc trace length does not match instruction count"
+            );
+        }
     }
 
-    #[test]
-    fn example2() {
-        // Translated example from the challenge document:
-        //
-        // uint a = 1;
-        // uint b = 0;
-        // if b == a {
-        //     print 0;
-        // }
-        // if a > b {
-        //     print 2;
-        // }
-        // print a + b;
-        #[rustfmt::skip]
-        test_program(
-            &[
-                PUSH8, 2,
-                VARRES, // pc 3
-                PUSH8, 1,
-                PUSH8, 0,
-                VARST,
+    /// Check whether a complete, recognized instruction begins at the given program address, in a
+    /// linear decoding of the program from address 0.
+    ///
+    /// Decoding from the address alone is not enough, as a byte within an instruction's literal
+    /// may happen to be a recognized opcode.
+    fn is_instruction_boundary(&self, pc: usize) -> bool {
+        let mut offset = 0;
+        while offset < pc {
+            match Instruction::decode_at(&self.program, offset) {
+                Ok((_, next)) => offset = next,
+                Err(_) => return false,
+            }
+        }
 
-                // var1 is initialized to zero, nothing needs doing
+        offset == pc && Instruction::decode_at(&self.program, pc).is_ok()
+    }
 
-                // pc 8
-                PUSH8, 0,
-                VARLD,
-                PUSH8, 1,
-                VARLD,
-                EQ, NOT,
-                PUSH8, 3,
-                JCOND,
-                PUSH8, 0,
-                PRINT,
+    /// Copy the recorded trace, if any, oldest snapshot first.
+    fn trace_vec(&self) -> Option<Vec<Snapshot>> {
+        self.trace
+            .as_ref()
+            .map(|trace| trace.iter().cloned().collect())
+    }
 
-                // pc 22
-                PUSH8, 0,
-                VARLD,
-                PUSH8, 1,
-                VARLD,
-                LE,
-                PUSH8, 3,
-                JCOND,
-                PUSH8, 2,
-                PRINT,
+    fn generate_snapshot(&self, next_instruction: Instruction) -> Snapshot {
+        Snapshot {
+            step_index: self.instruction_count,
+            program_counter: self.program_counter,
+            next_instruction,
+            stack: self.stack.clone(),
+            local_variables: self.local_vars.clone(),
+        }
+    }
+}
 
-                // pc 35
+/// A snapshot of the machine state at the end of program execution.
+#[derive(Debug, Clone)]
+pub struct ExecutionSummary {
+    pub program_counter: usize,
+    pub stack: Vec<Value>,
+    pub local_vars: Vec<Value>,
+    pub trace: Option<Vec<Snapshot>>,
+    pub pc_trace: Option<Vec<(usize, Opcode)>>,
+    pub profile: Option<Profile>,
+    pub instruction_count: usize,
+    pub peak_stack_depth: usize,
+    pub gas_used: u64,
+    pub output: Option<String>,
+    pub output_bytes: u64,
+    pub termination: Termination,
+}
+
+/// The manner in which a program terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    /// The program executed a `halt` instruction.
+    Halted,
+
+    /// The program counter went out of bounds of the program.
+    FellOffEnd,
+
+    /// A step hook stopped execution before the instruction at the program counter was executed.
+    ///
+    /// See [`ExecutionContext::with_abortable_step_hook`].
+    HostAborted,
+}
+
+impl ExecutionSummary {
+    /// Get the value on top of the stack at the end of execution, if any.
+    pub fn result(&self) -> Option<Value> {
+        self.stack.last().copied()
+    }
+
+    /// Get the value on top of the stack at the end of execution as a `u64`, if any.
+    pub fn result_u64(&self) -> Option<u64> {
+        self.result().map(|v| v.as_u64())
+    }
+
+    /// Get the value on top of the stack at the end of execution as an `i64`, if any.
+    pub fn result_i64(&self) -> Option<i64> {
+        self.result().map(|v| v.as_i64())
+    }
+
+    /// Iterate over the steps of the execution trace, in order.
+    ///
+    /// This yields nothing if tracing was not enabled. If the trace was bounded, only the retained
+    /// steps are yielded.
+    pub fn trace_steps(&self) -> impl Iterator<Item = &Snapshot> {
+        self.trace.iter().flatten()
+    }
+
+    /// Create a machine-readable report of the end state of the machine.
+    pub fn to_report(&self) -> ExecutionReport {
+        ExecutionReport {
+            program_counter: self.program_counter,
+            stack: self.stack.iter().copied().map(ValueReport::from).collect(),
+            local_vars: self
+                .local_vars
+                .iter()
+                .copied()
+                .map(ValueReport::from)
+                .collect(),
+            instruction_count: self.instruction_count,
+            peak_stack_depth: self.peak_stack_depth,
+        }
+    }
+}
+
+/// A plain-data report of the machine state at the end of program execution.
+///
+/// This is intended for consumption by tooling, in contrast to the human-oriented `Display`
+/// implementation of [`ExecutionSummary`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionReport {
+    pub program_counter: usize,
+    pub stack: Vec<ValueReport>,
+    pub local_vars: Vec<ValueReport>,
+    pub instruction_count: usize,
+    pub peak_stack_depth: usize,
+}
+
+/// A stack or variable value in an [`ExecutionReport`], interpreted as both unsigned and signed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueReport {
+    pub unsigned: u64,
+    pub signed: i64,
+}
+
+impl From<Value> for ValueReport {
+    fn from(value: Value) -> Self {
+        Self {
+            unsigned: value.as_u64(),
+            signed: value.as_i64(),
+        }
+    }
+}
+
+impl ExecutionSummary {
+    /// Format this summary, printing stack and variable values in the given format.
+    ///
+    /// The [`Display`] implementation formats with [`TraceFormat::Full`].
+    pub fn format_with<W: fmt::Write>(&self, f: &mut W, format: TraceFormat) -> fmt::Result {
+        if let Some(trace) = self.trace.as_ref() {
+            writeln!(f, "== EXECUTION TRACE ==")?;
+            format_trace_with(f, trace, format)?;
+
+            writeln!(f)?;
+        }
+
+        if let Some(pc_trace) = self.pc_trace.as_ref() {
+            writeln!(f, "== PC TRACE ==")?;
+            format_pc_trace(f, pc_trace)?;
+
+            writeln!(f)?;
+        }
+
+        if let Some(profile) = self.profile.as_ref() {
+            writeln!(f, "== OPCODE PROFILE ==")?;
+            write!(f, "{profile}")?;
+
+            writeln!(f)?;
+        }
+
+        writeln!(f, "== EXECUTION END STATE ==")?;
+        writeln!(f, "pc {}", self.program_counter)?;
+        writeln!(f, "instructions executed {}", self.instruction_count)?;
+        writeln!(f, "peak stack depth {}", self.peak_stack_depth)?;
+
+        writeln!(f, "stack")?;
+        format_stack_with(f, &self.stack, None, format)?;
+
+        writeln!(f, "vars")?;
+        format_vars_with(f, &self.local_vars, format)
+    }
+}
+
+impl Display for ExecutionSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.format_with(f, TraceFormat::Full)
+    }
+}
+
+impl ExecutionContext<'_, '_, '_> {
+    fn execute_instruction(&mut self, instr: Instruction) -> Result<usize> {
+        match instr.opcode {
+            Opcode::VarSt => self.varst(),
+            Opcode::VarLd => self.varld(),
+            Opcode::VarRes => self.varres(),
+            Opcode::VarDisc => self.vardisc(),
+            Opcode::NumVars => {
+                self.numvars();
+                Ok(())
+            }
+            Opcode::Push8
+            | Opcode::Push8S
+            | Opcode::Push16
+            | Opcode::Push16S
+            | Opcode::Push32
+            | Opcode::Push32S
+            | Opcode::Push64 => {
+                self.pushn(Value::from_u64(instr.literal));
+                Ok(())
+            }
+            Opcode::Dup0 => self.dupn(0),
+            Opcode::Dup1 => self.dupn(1),
+            Opcode::Dup2 => self.dupn(2),
+            Opcode::Dup3 => self.dupn(3),
+            Opcode::DupN => self.dupn(instr.literal as usize),
+            Opcode::Pop => self.pop(),
+            Opcode::Swap => self.swap(),
+            Opcode::Add => self.binop_infallible(Value::add),
+            Opcode::Sub => self.binop_infallible(Value::sub),
+            Opcode::Mul => self.binop_infallible(Value::mul),
+            Opcode::Mod => self.binop_fallible(Value::mod_),
+            Opcode::Div => self.binop_fallible(Value::div_unsigned),
+            Opcode::DivS => self.binop_fallible(Value::div_signed),
+            Opcode::Pow => self.binop_infallible(Value::pow),
+            Opcode::FAdd => self.binop_infallible(Value::add_float),
+            Opcode::FSub => self.binop_infallible(Value::sub_float),
+            Opcode::FMul => self.binop_infallible(Value::mul_float),
+            Opcode::FDiv => self.binop_infallible(Value::div_float),
+            Opcode::FGt => self.binop_infallible(Value::greater_float),
+            Opcode::FLt => self.binop_infallible(Value::less_float),
+            Opcode::FEq => self.binop_infallible(Value::eq_float),
+            Opcode::LAnd => self.binop_infallible(Value::logical_and),
+            Opcode::LOr => self.binop_infallible(Value::logical_or),
+            Opcode::Gt => self.binop_infallible(Value::greater_unsigned),
+            Opcode::GtS => self.binop_infallible(Value::greater_signed),
+            Opcode::Lt => self.binop_infallible(Value::less_unsigned),
+            Opcode::LtS => self.binop_infallible(Value::less_signed),
+            Opcode::Ge => self.binop_infallible(Value::greater_or_eq_unsigned),
+            Opcode::GeS => self.binop_infallible(Value::greater_or_eq_signed),
+            Opcode::Le => self.binop_infallible(Value::less_or_eq_unsigned),
+            Opcode::LeS => self.binop_infallible(Value::less_or_eq_signed),
+            Opcode::Eq => self.binop_infallible(Value::eq),
+            Opcode::And => self.binop_infallible(Value::and),
+            Opcode::Or => self.binop_infallible(Value::or),
+            Opcode::Xor => self.binop_infallible(Value::xor),
+            Opcode::Select => self.select(),
+            Opcode::ModPow => self.modpow(),
+            Opcode::AddCarry => self.binop_with_flag(Value::carrying_add),
+            Opcode::SubBorrow => self.binop_with_flag(Value::borrowing_sub),
+            Opcode::BitTest => self.binop_fallible(Value::bit_test),
+            Opcode::BitSet => self.binop_fallible(Value::bit_set),
+            Opcode::BitClear => self.binop_fallible(Value::bit_clear),
+            Opcode::Not => self.not(),
+            Opcode::Inv => self.unop(Value::inv),
+            Opcode::Ne => self.binop_infallible(Value::ne),
+            Opcode::Jump => self.jump(),
+            Opcode::JCond => self.jcond(),
+            Opcode::PrintF => self.print_float(),
+            Opcode::PrintPeek => self.print_peek(false),
+            Opcode::PrintPeekS => self.print_peek(true),
+            Opcode::Read => self.read(false),
+            Opcode::ReadS => self.read(true),
+            Opcode::ReadN => self.read_n(instr.literal as usize),
+            Opcode::ReadBool => self.read_bool(),
+            Opcode::PrintStack => self.print_stack(),
+            Opcode::Print => self.print(false),
+            Opcode::PrintS => self.print(true),
+            Opcode::Assert => self.assert(),
+            Opcode::Halt => return Ok(0),
+        }?;
+
+        Ok(1 + instr.opcode.literal_len())
+    }
+
+    fn pop_stack(&mut self) -> Result<Value> {
+        self.stack
+            .pop()
+            .ok_or_else(|| Error::from(ErrorKind::StackUnderflow))
+    }
+
+    fn push_stack(&mut self, val: Value) {
+        self.stack.push(val);
+        self.peak_stack_depth = self.peak_stack_depth.max(self.stack.len());
+    }
+
+    /// Convert a variable index popped from the stack to a `usize`.
+    ///
+    /// Indices that do not fit in a `usize` are out of bounds regardless of the host's pointer
+    /// width, rather than being truncated to a possibly valid index.
+    fn var_index(n: Value) -> Result<usize> {
+        usize::try_from(n.as_u64())
+            .map_err(|_| Error::from(ErrorKind::OutOfBoundsVariableReference))
+    }
+
+    fn read_var(&self, n: Value) -> Result<Value> {
+        let n = Self::var_index(n)?;
+        self.local_vars
+            .get(n)
+            .copied()
+            .ok_or_else(|| Error::from(ErrorKind::OutOfBoundsVariableReference))
+    }
+
+    fn write_var(&mut self, n: Value, x: Value) -> Result<()> {
+        let n = Self::var_index(n)?;
+        self.local_vars
+            .get_mut(n)
+            .map(|var| *var = x)
+            .ok_or_else(|| Error::from(ErrorKind::OutOfBoundsVariableReference))
+    }
+
+    fn varst(&mut self) -> Result<()> {
+        let n = self.pop_stack()?;
+        let x = self.pop_stack()?;
+        self.write_var(n, x)
+    }
+
+    fn varld(&mut self) -> Result<()> {
+        let n = self.pop_stack()?;
+        let x = self.read_var(n)?;
+        self.push_stack(x);
+        Ok(())
+    }
+
+    fn varres(&mut self) -> Result<()> {
+        let n = self.pop_stack()?.as_u64() as usize;
+        let in_use = self.stack.len() + self.local_vars.len();
+        if self
+            .memory_limit
+            .is_some_and(|limit| in_use.saturating_add(n) > limit)
+        {
+            return Err(Error::from(ErrorKind::MemoryLimitExceeded));
+        }
+
+        self.local_vars
+            .try_reserve(n)
+            .map_err(|_| Error::from(ErrorKind::AllocationError))?;
+        self.local_vars
+            .resize(self.local_vars.len() + n, Value::default());
+
+        Ok(())
+    }
+
+    /// Discard variables, retaining the capacity of the variable array for later `varres`
+    /// instructions.
+    fn vardisc(&mut self) -> Result<()> {
+        let n = self.pop_stack()?.as_u64() as usize;
+        if n < self.local_vars.len() {
+            self.local_vars.truncate(self.local_vars.len() - n);
+        } else {
+            self.local_vars.clear();
+        }
+        Ok(())
+    }
+
+    fn numvars(&mut self) {
+        self.push_stack(Value::from_u64(self.local_vars.len() as u64));
+    }
+
+    fn pushn(&mut self, value: Value) {
+        self.push_stack(value);
+    }
+
+    fn dupn(&mut self, n: usize) -> Result<()> {
+        if n < self.stack.len() {
+            let v = self.stack[self.stack.len() - 1 - n];
+            self.push_stack(v);
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::StackUnderflow))
+        }
+    }
+
+    fn pop(&mut self) -> Result<()> {
+        self.pop_stack()?;
+        Ok(())
+    }
+
+    fn swap(&mut self) -> Result<()> {
+        if self.stack.len() < 2 {
+            Err(Error::from(ErrorKind::StackUnderflow))
+        } else {
+            let len = self.stack.len();
+            self.stack.swap(len - 1, len - 2);
+            Ok(())
+        }
+    }
+
+    fn binop_infallible(&mut self, op: fn(Value, Value) -> Value) -> Result<()> {
+        let b = self.pop_stack()?;
+        let a = self.pop_stack()?;
+        self.push_stack(op(a, b));
+        Ok(())
+    }
+
+    fn binop_fallible(&mut self, op: fn(Value, Value) -> Result<Value, ValueError>) -> Result<()> {
+        let b = self.pop_stack()?;
+        let a = self.pop_stack()?;
+        self.push_stack(op(a, b)?);
+        Ok(())
+    }
+
+    /// Execute a binary operation that pushes its result, then a boolean flag.
+    fn binop_with_flag(&mut self, op: fn(Value, Value) -> (Value, bool)) -> Result<()> {
+        let b = self.pop_stack()?;
+        let a = self.pop_stack()?;
+        let (result, flag) = op(a, b);
+        self.push_stack(result);
+        self.push_stack(Value::from_u64(flag as u64));
+        Ok(())
+    }
+
+    fn unop(&mut self, op: fn(Value) -> Value) -> Result<()> {
+        let a = self.pop_stack()?;
+        self.push_stack(op(a));
+        Ok(())
+    }
+
+    fn jump(&mut self) -> Result<()> {
+        let n = self.pop_stack()?.as_i64() as isize;
+        self.program_counter = self.program_counter.wrapping_add_signed(n);
+        Ok(())
+    }
+
+    fn jcond(&mut self) -> Result<()> {
+        let n = self.pop_stack()?.as_i64() as isize;
+        let b = self.pop_stack()?.as_u64();
+        self.check_boolean(b)?;
+
+        if b != 0 {
+            self.program_counter = self.program_counter.wrapping_add_signed(n);
+        }
+
+        Ok(())
+    }
+
+    fn not(&mut self) -> Result<()> {
+        let a = self.pop_stack()?;
+        self.check_boolean(a.as_u64())?;
+        self.push_stack(a.not());
+        Ok(())
+    }
+
+    /// Check a condition value against the strict boolean option.
+    ///
+    /// See [`ExecutionContext::with_strict_booleans`].
+    fn check_boolean(&self, cond: u64) -> Result<()> {
+        if self.strict_booleans && cond > 1 {
+            Err(Error::from(ErrorKind::NonBooleanCondition))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn select(&mut self) -> Result<()> {
+        let else_val = self.pop_stack()?;
+        let then_val = self.pop_stack()?;
+        let cond = self.pop_stack()?.as_u64();
+
+        self.push_stack(if cond != 0 { then_val } else { else_val });
+        Ok(())
+    }
+
+    fn modpow(&mut self) -> Result<()> {
+        let modulus = self.pop_stack()?;
+        let exp = self.pop_stack()?;
+        let base = self.pop_stack()?;
+
+        self.push_stack(base.modpow(exp, modulus)?);
+        Ok(())
+    }
+
+    fn assert(&mut self) -> Result<()> {
+        if self.pop_stack()?.as_u64() == 0 {
+            Err(Error::from(ErrorKind::AssertionFailed))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Ensure that the input buffer contains at least one token.
+    ///
+    /// Lines containing no tokens are skipped. Reaching the end of the input stream before a
+    /// token is found is an error.
+    fn fill_input_buffer(&mut self) -> Result<()> {
+        if let Some(input) = self.input_stream.as_mut() {
+            while self.input_buffer.is_empty() {
+                let line = read_input_line(input)
+                    .map_err(|_| Error::from(ErrorKind::InputError))?
+                    .ok_or_else(|| Error::from(ErrorKind::InputError))?;
+                self.input_buffer = line.split_whitespace().rev().map(String::from).collect();
+            }
+
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::NoInputStream))
+        }
+    }
+
+    /// Check that no input tokens remain, either buffered or in the input stream.
+    fn check_input_consumed(&mut self) -> Result<()> {
+        if !self.input_buffer.is_empty() {
+            return Err(Error::from(ErrorKind::UnconsumedInput));
+        }
+
+        if let Some(input) = self.input_stream.as_mut() {
+            while let Some(line) =
+                read_input_line(input).map_err(|_| Error::from(ErrorKind::InputError))?
+            {
+                if !line.trim().is_empty() {
+                    return Err(Error::from(ErrorKind::UnconsumedInput));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(&mut self, signed: bool) -> Result<()> {
+        let val = self.read_value(signed)?;
+        self.push_stack(val);
+        Ok(())
+    }
+
+    /// Read `count` unsigned values, pushing them in the order they were read.
+    ///
+    /// If any read fails, nothing is pushed; values consumed from the input before the failure are
+    /// discarded.
+    fn read_n(&mut self, count: usize) -> Result<()> {
+        let vals = (0..count)
+            .map(|_| self.read_value(false))
+            .collect::<Result<Vec<_>>>()?;
+        vals.into_iter().for_each(|val| self.push_stack(val));
+        Ok(())
+    }
+
+    fn read_bool(&mut self) -> Result<()> {
+        self.fill_input_buffer()?;
+        let val = match self.input_buffer.pop().unwrap().as_str() {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            _ => return Err(Error::from(ErrorKind::ParseError)),
+        };
+        self.push_stack(Value::from_u64(val as u64));
+        Ok(())
+    }
+
+    fn read_value(&mut self, signed: bool) -> Result<Value> {
+        self.fill_input_buffer()?;
+        let input = self.input_buffer.pop().unwrap();
+        let val = if signed {
+            Value::from_i64(
+                i64::from_str_radix(&input, self.input_radix)
+                    .map_err(|_| Error::from(ErrorKind::ParseError))?,
+            )
+        } else {
+            Value::from_u64(
+                u64::from_str_radix(&input, self.input_radix)
+                    .map_err(|_| Error::from(ErrorKind::ParseError))?,
+            )
+        };
+        Ok(val)
+    }
+
+    fn print(&mut self, signed: bool) -> Result<()> {
+        let val = self.pop_stack()?;
+        self.write_value(val, signed)
+    }
+
+    fn print_float(&mut self) -> Result<()> {
+        let val = self.pop_stack()?;
+        self.write_line(&val.as_f64().to_string())
+    }
+
+    fn print_stack(&mut self) -> Result<()> {
+        let formatted = self
+            .stack
+            .iter()
+            .rev()
+            .map(|val| val.as_u64().to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.write_line(&formatted)
+    }
+
+    fn print_peek(&mut self, signed: bool) -> Result<()> {
+        let val = self
+            .stack
+            .last()
+            .copied()
+            .ok_or_else(|| Error::from(ErrorKind::StackUnderflow))?;
+        self.write_value(val, signed)
+    }
+
+    fn write_value(&mut self, val: Value, signed: bool) -> Result<()> {
+        let width = self.print_width;
+        let formatted = if signed {
+            format!("{:>width$}", val.as_i64())
+        } else {
+            format!("{:>width$}", val.as_u64())
+        };
+        self.write_line(&formatted)
+    }
+
+    fn write_line(&mut self, formatted: &str) -> Result<()> {
+        let line_len = formatted.len() as u64 + 1;
+        if self
+            .output_limit
+            .is_some_and(|limit| self.output_bytes + line_len > limit)
+        {
+            return Err(Error::from(ErrorKind::OutputLimitExceeded));
+        }
+
+        if let Some(captured) = self.captured_output.as_mut() {
+            captured.push_str(formatted);
+            captured.push('\n');
+        }
+
+        if let Some(output) = self.output_stream.as_mut() {
+            // Write the line piecewise, rather than with `writeln!`, to count the bytes written
+            // before any failure
+            let line = format!("{formatted}\n");
+            let mut remaining = line.as_bytes();
+            while !remaining.is_empty() {
+                match output.write(remaining) {
+                    Ok(0) => return Err(Error::from(ErrorKind::OutputError)),
+                    Ok(n) => {
+                        self.output_bytes += n as u64;
+                        remaining = &remaining[n..];
+                    }
+                    Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(_) => return Err(Error::from(ErrorKind::OutputError)),
+                }
+            }
+
+            if self.flush_each_print {
+                output
+                    .flush()
+                    .map_err(|_| Error::from(ErrorKind::OutputError))?;
+            }
+        } else {
+            self.output_bytes += line_len;
+        }
+        Ok(())
+    }
+}
+
+/// Check the operand stack against the expected types of its slots before executing `instr`.
+///
+/// See [`ExecutionContext::with_slot_type_verification`].
+fn slot_types_match(instr: Instruction, stack: &[Value], types: &[SlotType]) -> bool {
+    if stack.len() != types.len() {
+        return false;
+    }
+
+    let canonical = stack
+        .iter()
+        .zip(types)
+        .all(|(val, &ty)| ty != SlotType::Bool || val.as_u64() <= 1);
+
+    let condition = match instr.opcode {
+        Opcode::JCond => types.len().checked_sub(2),
+        Opcode::Select => types.len().checked_sub(3),
+        Opcode::Assert => types.len().checked_sub(1),
+        _ => None,
+    };
+
+    let condition_is_bool = match condition {
+        Some(i) => types[i] == SlotType::Bool,
+        None => true,
+    };
+
+    canonical && condition_is_bool
+}
+
+/// Read a single line of UTF-8 text from an input stream.
+///
+/// Lines may be terminated by `\n`, `\r\n`, or a lone `\r`, or by the end of the stream. The line
+/// terminator is consumed, but not included in the returned line. (A `\r\n` terminator is
+/// consumed as a `\r`-terminated line followed by an empty line.)
+///
+/// Returns `None` if the stream is already at its end.
+fn read_input_line(input: &mut dyn BufRead) -> std::io::Result<Option<String>> {
+    let mut line = Vec::new();
+    let mut read_any = false;
+
+    loop {
+        let buf = input.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        read_any = true;
+
+        if let Some(end) = buf.iter().position(|&b| b == b'\n' || b == b'\r') {
+            line.extend_from_slice(&buf[..end]);
+            input.consume(end + 1);
+            break;
+        } else {
+            line.extend_from_slice(buf);
+            let len = buf.len();
+            input.consume(len);
+        }
+    }
+
+    if read_any {
+        String::from_utf8(line)
+            .map(Some)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hypescript_bytecode::consts::*;
+
+    use super::*;
+
+    fn val_vec(values: &[u64]) -> Vec<Value> {
+        values.iter().copied().map(Value::from_u64).collect()
+    }
+
+    fn test_program(program: &[u8], input: &str, validate: fn(ExecutionSummary, &str)) {
+        let mut output = Vec::<u8>::new();
+        let res = ExecutionContext::new(program)
+            .with_input_stream(&mut input.as_bytes())
+            .with_output_stream(&mut output)
+            .with_trace()
+            .run();
+
+        match res {
+            Err(err) => {
+                eprintln!("{err}");
+                panic!("VM encountered a runtime error");
+            }
+
+            Ok(summary) => {
+                println!("{}", summary);
+                let output = String::from_utf8(output).unwrap();
+                validate(summary, &output);
+            }
+        }
+    }
+
+    #[test]
+    fn example1() {
+        // Translated example from the challenge document:
+        // uint a = 5;
+        // uint b = 4 + a;
+        // print a;
+        // print b;
+        #[rustfmt::skip]
+        test_program(
+            &[
+                PUSH8, 2,
+                VARRES,
+                PUSH8, 5,
+                PUSH8, 0,
+                VARST,
+                PUSH8, 4,
+                PUSH8, 0,
+                VARLD,
+                ADD,
+                PUSH8, 1,
+                VARST,
+                PUSH8, 0,
+                VARLD,
+                PRINT,
+                PUSH8, 1,
+                VARLD,
+                PRINT,
+            ],
+            "",
+            |summary, output| {
+                assert_eq!(summary.local_vars, &[Value::from_u8(5), Value::from_u8(9)]);
+                assert!(summary.stack.is_empty());
+                assert_eq!(summary.program_counter, 25);
+                assert_eq!(output, "5\n9\n");
+            },
+        );
+    }
+
+    #[test]
+    fn example2() {
+        // Translated example from the challenge document:
+        //
+        // uint a = 1;
+        // uint b = 0;
+        // if b == a {
+        //     print 0;
+        // }
+        // if a > b {
+        //     print 2;
+        // }
+        // print a + b;
+        #[rustfmt::skip]
+        test_program(
+            &[
+                PUSH8, 2,
+                VARRES, // pc 3
+                PUSH8, 1,
+                PUSH8, 0,
+                VARST,
+
+                // var1 is initialized to zero, nothing needs doing
+
+                // pc 8
+                PUSH8, 0,
+                VARLD,
+                PUSH8, 1,
+                VARLD,
+                EQ, NOT,
+                PUSH8, 3,
+                JCOND,
+                PUSH8, 0,
+                PRINT,
+
+                // pc 22
+                PUSH8, 0,
+                VARLD,
+                PUSH8, 1,
+                VARLD,
+                LE,
+                PUSH8, 3,
+                JCOND,
+                PUSH8, 2,
+                PRINT,
+
+                // pc 35
+                PUSH8, 0,
+                VARLD,
+                PUSH8, 1,
+                VARLD,
+                ADD,
+                PRINT,
+                // pc 43
+            ],
+            "",
+            |summary, output| {
+                assert_eq!(summary.local_vars, &[Value::from_u8(1), Value::from_u8(0)]);
+                assert!(summary.stack.is_empty());
+                assert_eq!(summary.program_counter, 43);
+                assert_eq!(output, "2\n1\n");
+            },
+        );
+    }
+
+    #[test]
+    fn counter() {
+        // Basic counter, storing locals purely on stack
+        //
+        // uint a = 0;
+        // while a < 10 {
+        //     a = a + 1;
+        //     print a;
+        // }
+        #[rustfmt::skip]
+        test_program(
+            &[
+                PUSH8, 0,
+                PUSH8, 5,
+                JUMP,
+
+                PUSH8, 1,
+                ADD,
+                DUP0,
+                PRINT,
+
+                DUP0,
+                PUSH8, 10,
+                LT,
+                PUSH8S, (-12i8) as u8,
+                JCOND,
+
+                POP,
+            ],
+            "",
+            |summary, output| {
+                assert!(summary.local_vars.is_empty());
+                assert!(summary.stack.is_empty(), "stack = {:?}", summary.stack);
+                assert_eq!(output, "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n");
+            },
+        );
+    }
+
+    #[test]
+    fn counter_asm() {
+        // The same counter as above, assembled from mnemonics
+        let instrs = hypescript_bytecode::hyasm! {
+            push8 0;
+            push8 5;
+            jump;
+
+            push8 1;
+            add;
+            dup0;
+            print;
+
+            dup0;
+            push8 10;
+            lt;
+            push8s -12;
+            jcond;
+
+            pop;
+        };
+
+        let summary = ExecutionContext::from_instructions(&instrs)
+            .with_captured_output()
+            .run()
+            .expect("VM encountered a runtime error");
+        assert!(summary.stack.is_empty(), "stack = {:?}", summary.stack);
+        assert_eq!(
+            summary.output.as_deref(),
+            Some("1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n")
+        );
+    }
+
+    #[test]
+    fn var_management() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                // pc 0, trace step 0
+                PUSH8, 4,
+                VARRES,
+
+                // pc 3, tr 2
+                NUMVARS,
+                POP,
+
+                // pc 5, tr 4
+                PUSH8, 2,
+                VARDISC,
+
+                // pc 8, tr 6
+                NUMVARS,
+                POP,
+
+                // pc 10, tr 8
+                PUSH8, 12,
+                PUSH8, 0,
+                VARST,
+
+                // pc 15, tr 11
+                PUSH8, 15,
+                PUSH8, 1,
+                VARST,
+
+                // pc 20, tr 14
                 PUSH8, 0,
                 VARLD,
+                POP,
+
+                // pc 24, tr 17
                 PUSH8, 1,
                 VARLD,
+                POP,
+
+                // pc 28, tr 20
+            ],
+            "",
+            |summary, _| {
+                let trace = summary.trace.as_ref().unwrap();
+
+                // Reserve 4 vars, push num vars
+                assert_eq!(trace[2].local_variables, &[Value::from_u8(0); 4]);
+                assert_eq!(*trace[3].stack.last().unwrap(), Value::from_u8(4));
+
+                // Discard 2 vars, push num vars
+                assert_eq!(trace[6].local_variables, &[Value::from_u8(0); 2]);
+                assert_eq!(*trace[7].stack.last().unwrap(), Value::from_u8(2));
+
+                // Store 12 in var 0, 15 in var 1
+                assert_eq!(trace[11].local_variables, &[Value::from_u8(12), Value::from_u8(0)]);
+                assert_eq!(trace[14].local_variables, &[Value::from_u8(12), Value::from_u8(15)]);
+
+                // Load var 0, var 1
+                assert_eq!(*trace[16].stack.last().unwrap(), Value::from_u8(12));
+                assert_eq!(*trace[19].stack.last().unwrap(), Value::from_u8(15));
+            }
+        );
+    }
+
+    #[test]
+    fn stack_management() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                // tr 0
+                PUSH8, 4,
+                PUSH8S, 0xff,
+
+                // tr 2
+                PUSH16, 0xbe, 0xef,
+                PUSH16S, 0xca, 0xfe,
+
+                // tr 4
+                PUSH32, 0, 0, 0xde, 0xed,
+                PUSH32S, 0xde, 0xad, 0xbe, 0xef,
+
+                // tr 6
+                PUSH64, 0xde, 0xad, 0xbe, 0xef, 0xca, 0xfe, 0xfa, 0xce,
+
+                // tr 7
+                SWAP, SWAP,
+
+                // tr 9
+                DUP0, POP,
+
+                // tr 11
+                DUP1, POP,
+
+                // tr 13
+                DUP2, POP,
+
+                // tr 15
+                DUP3, POP,
+
+                // tr 17
+            ],
+            "",
+            |summary, _ | {
+                let trace = summary.trace.as_ref().unwrap();
+
+                // push8, push8s
+                assert_eq!(trace[2].stack, val_vec(&[4, 0xffffffffffffffff]));
+
+                // push16, push16s
+                assert_eq!(trace[4].stack, val_vec(&[
+                        4,
+                        0xffffffffffffffff,
+                        0xbeef,
+                        0xffffffffffffcafe,
+                ]));
+
+                // push32, push32s
+                assert_eq!(trace[6].stack, val_vec(&[
+                        4,
+                        0xffffffffffffffff,
+                        0xbeef,
+                        0xffffffffffffcafe,
+                        0xdeed,
+                        0xffffffffdeadbeef,
+                ]));
+
+                // push64
+                assert_eq!(trace[7].stack, val_vec(&[
+                        4,
+                        0xffffffffffffffff,
+                        0xbeef,
+                        0xffffffffffffcafe,
+                        0xdeed,
+                        0xffffffffdeadbeef,
+                        0xdeadbeefcafeface,
+                ]));
+
+                // swap
+                assert_eq!(trace[8].stack, val_vec(&[
+                        4,
+                        0xffffffffffffffff,
+                        0xbeef,
+                        0xffffffffffffcafe,
+                        0xdeed,
+                        0xdeadbeefcafeface,
+                        0xffffffffdeadbeef,
+                ]));
+
+                // swap, dup0
+                assert_eq!(trace[10].stack, val_vec(&[
+                        4,
+                        0xffffffffffffffff,
+                        0xbeef,
+                        0xffffffffffffcafe,
+                        0xdeed,
+                        0xffffffffdeadbeef,
+                        0xdeadbeefcafeface,
+                        0xdeadbeefcafeface,
+                ]));
+
+                // pop, dup1
+                assert_eq!(trace[12].stack, val_vec(&[
+                        4,
+                        0xffffffffffffffff,
+                        0xbeef,
+                        0xffffffffffffcafe,
+                        0xdeed,
+                        0xffffffffdeadbeef,
+                        0xdeadbeefcafeface,
+                        0xffffffffdeadbeef,
+                ]));
+
+                // pop, dup2
+                assert_eq!(trace[14].stack, val_vec(&[
+                        4,
+                        0xffffffffffffffff,
+                        0xbeef,
+                        0xffffffffffffcafe,
+                        0xdeed,
+                        0xffffffffdeadbeef,
+                        0xdeadbeefcafeface,
+                        0xdeed,
+                ]));
+
+                // pop, dup3
+                assert_eq!(trace[16].stack, val_vec(&[
+                        4,
+                        0xffffffffffffffff,
+                        0xbeef,
+                        0xffffffffffffcafe,
+                        0xdeed,
+                        0xffffffffdeadbeef,
+                        0xdeadbeefcafeface,
+                        0xffffffffffffcafe,
+                ]));
+            },
+        );
+    }
+
+    #[test]
+    fn arithmetic() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                // tr 0
+                PUSH8, 2,
+                PUSH8, 6,
                 ADD,
+
+                // tr 3
+                POP,
+                PUSH8, 12,
+                PUSH8, 5,
+                SUB,
+
+                // tr 7
+                POP,
+                PUSH8, 7,
+                PUSH8, 6,
+                MUL,
+
+                // tr 11
+                POP,
+                PUSH8, 17,
+                PUSH8, 8,
+                MOD,
+
+                // tr 15
+                POP,
+                PUSH8, 88,
+                PUSH8, 11,
+                DIV,
+
+                // tr 19
+                POP,
+                PUSH8S, -20_i8 as u8,
+                PUSH8, 5,
+                DIVS,
+
+                // tr 23
+            ],
+            "",
+            |summary, _| {
+                let trace = summary.trace.as_ref().unwrap();
+
+                // 2 + 6
+                assert_eq!(trace[3].stack, val_vec(&[8]));
+
+                // 12 - 5
+                assert_eq!(trace[7].stack, val_vec(&[7]));
+
+                // 7 * 6
+                assert_eq!(trace[11].stack, val_vec(&[42]));
+
+                // 17 % 8
+                assert_eq!(trace[15].stack, val_vec(&[1]));
+
+                // 88 / 11
+                assert_eq!(trace[19].stack, val_vec(&[8]));
+
+                // -20 / 5
+                assert_eq!(summary.stack, val_vec(&[-4_i64 as u64]));
+            },
+        );
+    }
+
+    #[test]
+    fn pow() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                // tr 0
+                PUSH8, 3,
+                PUSH8, 4,
+                POW,
+
+                // tr 3
+                POP,
+                PUSH8, 2,
+                PUSH8, 64,
+                POW,
+
+                // tr 7
+                POP,
+                PUSH8, 0,
+                PUSH8, 0,
+                POW,
+
+                // tr 11
+            ],
+            "",
+            |summary, _| {
+                let trace = summary.trace.as_ref().unwrap();
+
+                // 3 ** 4
+                assert_eq!(trace[3].stack, val_vec(&[81]));
+
+                // 2 ** 64 wraps around
+                assert_eq!(trace[7].stack, val_vec(&[0]));
+
+                // 0 ** 0
+                assert_eq!(summary.stack, val_vec(&[1]));
+            },
+        );
+    }
+
+    #[test]
+    fn float_arithmetic() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                // 1.5 + 2.5 == 4.0
+                PUSH64, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0,
+                PUSH64, 0x40, 0x04, 0, 0, 0, 0, 0, 0,
+                FADD,
+                DUP0,
+                PRINTF,
+                PUSH64, 0x40, 0x10, 0, 0, 0, 0, 0, 0,
+                FEQ,
                 PRINT,
-                // pc 43
+
+                // 1.0 / 0.0
+                PUSH64, 0x3f, 0xf0, 0, 0, 0, 0, 0, 0,
+                PUSH8, 0,
+                FDIV,
+                PRINTF,
+
+                // 0.0 / 0.0, compared against itself and 1.0
+                PUSH8, 0,
+                PUSH8, 0,
+                FDIV,
+                DUP0,
+                PRINTF,
+                DUP0,
+                DUP0,
+                FEQ,
+                PRINT,
+                DUP0,
+                PUSH64, 0x3f, 0xf0, 0, 0, 0, 0, 0, 0,
+                FLT,
+                PRINT,
+                PUSH64, 0x3f, 0xf0, 0, 0, 0, 0, 0, 0,
+                FGT,
+                PRINT,
+            ],
+            "",
+            |summary, output| {
+                assert_eq!(output, "4\n1\ninf\nNaN\n0\n0\n0\n");
+                assert!(summary.stack.is_empty());
+            },
+        );
+    }
+
+    #[test]
+    fn print_stack() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                PRINTSTACK,
+                PUSH8, 1,
+                PUSH8S, -2_i8 as u8,
+                PUSH16, 0x01, 0x00,
+                PRINTSTACK,
+                POP,
+                PRINTSTACK,
+            ],
+            "",
+            |summary, output| {
+                assert_eq!(output, "\n256 18446744073709551614 1\n18446744073709551614 1\n");
+                assert_eq!(summary.stack, val_vec(&[1, -2_i64 as u64]));
+            },
+        );
+    }
+
+    #[test]
+    fn input() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                READ,
+                READS,
+                READS,
+                READ,
+            ],
+            "12 13\n-145   \t\t     78", // Some random-ish assortments of whitespace in there, just to test
+            |summary, _| {
+                let trace = summary.trace.as_ref().unwrap();
+                assert_eq!(trace[1].stack, val_vec(&[12]));
+                assert_eq!(trace[2].stack, val_vec(&[12, 13]));
+                assert_eq!(trace[3].stack, val_vec(&[12, 13, -145_i64 as u64]));
+                assert_eq!(summary.stack, val_vec(&[12, 13, -145_i64 as u64, 78]));
+            }
+        );
+    }
+
+    #[test]
+    fn entry_point() {
+        #[rustfmt::skip]
+        let program = &[
+            // Leading data in a push literal, not executed
+            PUSH32, 0xde, 0xad, 0xbe, 0xef,
+
+            // pc 5
+            PUSH8, 7,
+            PUSH8, 3,
+            SUB,
+        ];
+
+        let summary = ExecutionContext::new(program)
+            .with_entry_point(5)
+            .run()
+            .expect("VM encountered a runtime error");
+
+        assert_eq!(summary.stack, val_vec(&[4]));
+        assert_eq!(summary.program_counter, 10);
+    }
+
+    #[test]
+    fn invalid_entry_point() {
+        let program = &[PUSH16, 0x12, 0x34, POP];
+
+        // Middle of a literal
+        let err = ExecutionContext::new(program)
+            .with_entry_point(1)
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidEntryPoint);
+        assert_eq!(err.program_counter, 1);
+
+        // A literal byte that happens to be a valid opcode
+        let err = ExecutionContext::new(&[PUSH8, POP, POP])
+            .with_entry_point(1)
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidEntryPoint);
+
+        // Out of bounds
+        let err = ExecutionContext::new(program)
+            .with_entry_point(5)
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidEntryPoint);
+
+        // End of program is fine
+        let summary = ExecutionContext::new(program)
+            .with_entry_point(4)
+            .run()
+            .unwrap();
+        assert_eq!(summary.program_counter, 4);
+    }
+
+    #[test]
+    fn not_equal() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                PUSH8, 4,
+                PUSH8, 4,
+                NE,
+
+                PUSH8, 4,
+                PUSH8, 5,
+                NE,
+
+                PUSH8S, -1_i8 as u8,
+                PUSH64, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                NE,
+            ],
+            "",
+            |summary, _| {
+                assert_eq!(summary.stack, val_vec(&[0, 1, 0]));
+            },
+        );
+    }
+
+    #[test]
+    fn logical_connectives() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                PUSH8, 2,
+                PUSH8, 1,
+                LAND,
+
+                PUSH8, 2,
+                PUSH8, 1,
+                AND,
+
+                PUSH8, 0,
+                PUSH8, 6,
+                LOR,
+
+                PUSH8, 0,
+                PUSH8, 0,
+                LOR,
+            ],
+            "",
+            |summary, _| {
+                assert_eq!(summary.stack, val_vec(&[1, 0, 1, 0]));
+            },
+        );
+    }
+
+    #[test]
+    fn select() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                PUSH8, 1,
+                PUSH8, 10,
+                PUSH8, 20,
+                SELECT,
+
+                PUSH8, 0,
+                PUSH8, 10,
+                PUSH8, 20,
+                SELECT,
+
+                // Any nonzero condition selects the then-value
+                PUSH8, 7,
+                PUSH8, 30,
+                PUSH8, 40,
+                SELECT,
+            ],
+            "",
+            |summary, _| {
+                assert_eq!(summary.stack, val_vec(&[10, 20, 30]));
+                assert_eq!(summary.peak_stack_depth, 5);
+            },
+        );
+
+        let err = ExecutionContext::new(&[PUSH8, 1, PUSH8, 2, SELECT])
+            .run()
+            .expect_err("Select with two operands did not underflow");
+        assert_eq!(err.kind, ErrorKind::StackUnderflow);
+    }
+
+    #[test]
+    fn carry_arithmetic() {
+        // 128-bit addition of 0x1_ffff_ffff_ffff_ffff and 0x2_0000_0000_0000_0001, with the high
+        // word stored in variable 0
+        let instrs = hypescript_bytecode::hyasm! {
+            push8 1;
+            varres;
+
+            push8 1;
+            push8 2;
+            add;
+            push8 0;
+            varst;
+
+            push64 0xffff_ffff_ffff_ffff;
+            push8 1;
+            addc;
+            push8 0;
+            varld;
+            add;
+            push8 0;
+            varst;
+        };
+        let summary = ExecutionContext::from_instructions(&instrs).run().unwrap();
+        assert_eq!(summary.stack, val_vec(&[0]));
+        assert_eq!(summary.local_vars, val_vec(&[4]));
+
+        let instrs = hypescript_bytecode::hyasm! {
+            push8 0;
+            push8 1;
+            subb;
+            push8 9;
+            push8 4;
+            subb;
+            push8 3;
+            push8 4;
+            addc;
+        };
+        let summary = ExecutionContext::from_instructions(&instrs).run().unwrap();
+        assert_eq!(summary.stack, val_vec(&[u64::MAX, 1, 5, 0, 7, 0]));
+    }
+
+    #[test]
+    fn modpow() {
+        let instrs = hypescript_bytecode::hyasm! {
+            push8 4;
+            push8 13;
+            push16 497;
+            modpow;
+            print;
+
+            push64 0xffff_ffff_ffff_ffff;
+            push64 0xffff_ffff_ffff_ffff;
+            push64 0x8000_0000_0000_0005;
+            modpow;
+        };
+        let summary = ExecutionContext::from_instructions(&instrs)
+            .with_captured_output()
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.output.as_deref(), Some("445\n"));
+        assert_eq!(summary.stack, val_vec(&[3322427856246731656]));
+
+        let instrs = hypescript_bytecode::hyasm! {
+            push8 3;
+            push8 4;
+            push8 0;
+            modpow;
+        };
+        let err = ExecutionContext::from_instructions(&instrs)
+            .run()
+            .expect_err("Modulus of zero did not fail");
+        assert_eq!(err.kind, ErrorKind::DivideByZero);
+        assert_eq!(err.program_counter, 6);
+        assert_eq!(err.instr, Some(Instruction::from(Opcode::ModPow)));
+    }
+
+    #[test]
+    fn bit_manipulation() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                PUSH8, 0b0110,
+                PUSH8, 2,
+                BITTEST,
+
+                PUSH8, 0b0110,
+                PUSH8, 0,
+                BITTEST,
+
+                PUSH8, 0b0110,
+                PUSH8, 4,
+                BITSET,
+
+                PUSH8, 0b0110,
+                PUSH8, 1,
+                BITCLEAR,
+            ],
+            "",
+            |summary, _| {
+                assert_eq!(summary.stack, val_vec(&[1, 0, 0b10110, 0b0100]));
+            },
+        );
+
+        let err = ExecutionContext::new(&[PUSH8, 1, PUSH8, 64, BITSET])
+            .run()
+            .expect_err("Out of range bit index was accepted");
+        assert_eq!(err.kind, ErrorKind::BitIndexOutOfRange);
+        assert_eq!(err.program_counter, 4);
+    }
+
+    #[test]
+    fn summary_result() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                PUSH8, 3,
+                PUSH8S, -20_i8 as u8,
+                PUSH8, 5,
+                DIVS,
             ],
             "",
-            |summary, output| {
-                assert_eq!(summary.local_vars, &[Value::from_u8(1), Value::from_u8(0)]);
-                assert!(summary.stack.is_empty());
-                assert_eq!(summary.program_counter, 43);
-                assert_eq!(output, "2\n1\n");
+            |summary, _| {
+                assert_eq!(summary.result(), Some(Value::from_i64(-4)));
+                assert_eq!(summary.result_i64(), Some(-4));
+                assert_eq!(summary.result_u64(), Some(-4_i64 as u64));
             },
         );
+
+        test_program(&[], "", |summary, _| {
+            assert_eq!(summary.result(), None);
+            assert_eq!(summary.result_i64(), None);
+        });
     }
 
     #[test]
-    fn counter() {
-        // Basic counter, storing locals purely on stack
-        //
-        // uint a = 0;
-        // while a < 10 {
-        //     a = a + 1;
-        //     print a;
-        // }
+    fn pc_trace() {
+        // Same as the `counter` test program
+        #[rustfmt::skip]
+        let program = &[
+            PUSH8, 0,
+            PUSH8, 5,
+            JUMP,
+
+            PUSH8, 1,
+            ADD,
+            DUP0,
+            PRINT,
+
+            DUP0,
+            PUSH8, 10,
+            LT,
+            PUSH8S, (-12i8) as u8,
+            JCOND,
+
+            POP,
+        ];
+
+        let summary = ExecutionContext::new(program)
+            .with_trace()
+            .with_pc_trace()
+            .run()
+            .expect("VM encountered a runtime error");
+
+        let trace = summary.trace.as_ref().unwrap();
+        let pc_trace = summary.pc_trace.as_ref().unwrap();
+
+        // Preamble, initial loop check, 10 iterations of body and check, final pop
+        assert_eq!(pc_trace.len(), 3 + 5 + 10 * 9 + 1);
+        assert_eq!(pc_trace.len(), trace.len());
+        for ((pc, opcode), snapshot) in pc_trace.iter().zip(trace) {
+            assert_eq!(*pc, snapshot.program_counter);
+            assert_eq!(*opcode, snapshot.next_instruction.opcode);
+        }
+
+        assert_eq!(
+            pc_trace[..3],
+            [(0, Opcode::Push8), (2, Opcode::Push8), (4, Opcode::Jump)]
+        );
+        assert_eq!(*pc_trace.last().unwrap(), (17, Opcode::Pop));
+    }
+
+    #[test]
+    fn abortable_step_hook() {
+        // The counter program, stopped as it is about to print 3
+        let instrs = hypescript_bytecode::hyasm! {
+            push8 0;
+            push8 5;
+            jump;
+
+            push8 1;
+            add;
+            dup0;
+            print;
+
+            dup0;
+            push8 10;
+            lt;
+            push8s -12;
+            jcond;
+
+            pop;
+        };
+
+        let mut prints = 0;
+        let summary = ExecutionContext::from_instructions(&instrs)
+            .with_captured_output()
+            .with_trace()
+            .with_cost_model(|_| 1)
+            .with_abortable_step_hook(|info| {
+                if info.program_counter == 9 {
+                    prints += 1;
+                    if prints == 3 {
+                        return ControlFlow::Break(());
+                    }
+                }
+                ControlFlow::Continue(())
+            })
+            .run()
+            .expect("VM encountered a runtime error");
+
+        assert_eq!(summary.termination, Termination::HostAborted);
+        assert_eq!(summary.program_counter, 9);
+        assert_eq!(summary.stack, val_vec(&[3, 3]));
+        assert_eq!(summary.output.as_deref(), Some("1\n2\n"));
+        assert_eq!(summary.instruction_count, 3 + 5 + 2 * 9 + 3);
+
+        // The aborted instruction is neither charged nor traced
+        assert_eq!(summary.gas_used, summary.instruction_count as u64);
+        let trace = summary.trace.as_ref().unwrap();
+        assert_eq!(trace.len(), summary.instruction_count);
+        assert_eq!(trace.last().unwrap().program_counter, 8);
+
+        // Stepping an aborted machine does nothing
+        let mut context =
+            ExecutionContext::from_instructions(&instrs).with_abortable_step_hook(|info| {
+                if info.program_counter == 10 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            });
+        for _ in 0..3 {
+            assert_eq!(context.step().unwrap(), None);
+        }
+        assert_eq!(context.step().unwrap(), Some(Termination::HostAborted));
+        assert_eq!(context.step().unwrap(), Some(Termination::HostAborted));
+        assert_eq!(context.program_counter(), 10);
+
+        // An instruction that exhausts the gas limit is only reported to the hook once it is
+        // retried with a higher limit
+        use std::cell::Cell;
+        let calls = Cell::new(0);
+        let mut context = ExecutionContext::from_instructions(&instrs)
+            .with_cost_model(|_| 1)
+            .with_gas_limit(0)
+            .with_step_hook(|_| calls.set(calls.get() + 1));
+        assert_eq!(context.step().unwrap_err().kind, ErrorKind::GasExhausted);
+        assert_eq!(calls.get(), 0);
+        let mut context = context.with_gas_limit(1);
+        assert_eq!(context.step().unwrap(), None);
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn step_hook() {
+        let instrs = hypescript_bytecode::hyasm! {
+            push8 1;
+            varres;
+            push8 4;
+            push8 0;
+            varst;
+            push8 0;
+            varld;
+            print;
+        };
+
+        let mut steps = Vec::new();
+        let summary = ExecutionContext::from_instructions(&instrs)
+            .with_step_hook(|info| steps.push(*info))
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.instruction_count, 8);
+
+        let pcs = steps
+            .iter()
+            .map(|info| info.program_counter)
+            .collect::<Vec<_>>();
+        assert_eq!(pcs, [0, 2, 3, 5, 7, 8, 10, 11]);
+
+        let instructions = steps
+            .iter()
+            .map(|info| info.next_instruction)
+            .collect::<Vec<_>>();
+        assert_eq!(instructions, instrs);
+
+        for (i, info) in steps.iter().enumerate() {
+            assert_eq!(info.step_index, i);
+        }
+        assert_eq!(
+            steps
+                .iter()
+                .map(|info| (info.stack_len, info.local_vars_len))
+                .collect::<Vec<_>>(),
+            [
+                (0, 0),
+                (1, 0),
+                (0, 1),
+                (1, 1),
+                (2, 1),
+                (0, 1),
+                (1, 1),
+                (1, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn report() {
         #[rustfmt::skip]
         test_program(
             &[
+                PUSH8, 1,
+                VARRES,
+                PUSH8, 7,
                 PUSH8, 0,
-                PUSH8, 5,
-                JUMP,
+                VARST,
 
-                PUSH8, 1,
+                PUSH8S, -3_i8 as u8,
+                PUSH8, 4,
+                PUSH8, 5,
                 ADD,
-                DUP0,
-                PRINT,
+                HALT,
+                PUSH8, 6,
+            ],
+            "",
+            |summary, _| {
+                let report = summary.to_report();
+                assert_eq!(report.program_counter, 15);
+                assert_eq!(
+                    report.stack,
+                    &[
+                        ValueReport { unsigned: -3_i64 as u64, signed: -3 },
+                        ValueReport { unsigned: 9, signed: 9 },
+                    ]
+                );
+                assert_eq!(report.local_vars, &[ValueReport { unsigned: 7, signed: 7 }]);
+                assert_eq!(report.instruction_count, 10);
+                assert_eq!(report.peak_stack_depth, 3);
+            },
+        );
+    }
 
-                DUP0,
+    #[test]
+    fn dup_arbitrary_depth() {
+        #[rustfmt::skip]
+        test_program(
+            &[
                 PUSH8, 10,
-                LT,
-                PUSH8S, (-12i8) as u8,
-                JCOND,
+                PUSH8, 11,
+                PUSH8, 12,
+                PUSH8, 13,
+                PUSH8, 14,
+                DUPN, 4,
+                DUPN, 0,
+            ],
+            "",
+            |summary, _| {
+                assert_eq!(summary.stack, val_vec(&[10, 11, 12, 13, 14, 10, 10]));
+            },
+        );
 
-                POP,
+        let err = ExecutionContext::new(&[PUSH8, 1, PUSH8, 2, DUPN, 2])
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::StackUnderflow);
+        assert_eq!(err.program_counter, 4);
+        assert_eq!(err.instr, Some(Instruction::new(Opcode::DupN, 2)));
+    }
+
+    #[test]
+    fn print_peek() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                PUSH8, 5,
+                PRINTPEEK,
+                PUSH8S, -2_i8 as u8,
+                PRINTPEEKS,
+                ADD,
+                PRINT,
             ],
             "",
             |summary, output| {
-                assert!(summary.local_vars.is_empty());
-                assert!(summary.stack.is_empty(), "stack = {:?}", summary.stack);
-                assert_eq!(output, "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n");
+                assert!(summary.stack.is_empty());
+                assert_eq!(output, "5\n-2\n3\n");
             },
         );
+
+        let err = ExecutionContext::new(&[PRINTPEEK]).run().unwrap_err();
+        assert_eq!(err.kind, ErrorKind::StackUnderflow);
     }
 
     #[test]
-    fn var_management() {
+    fn input_radix() {
+        let res = ExecutionContext::new(&[READ, READS])
+            .with_input_stream("ff -1a".as_bytes())
+            .with_input_radix(16)
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(res.stack, val_vec(&[255, -26_i64 as u64]));
+
+        let err = ExecutionContext::new(&[READ])
+            .with_input_stream("ff".as_bytes())
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ParseError);
+    }
+
+    #[test]
+    #[should_panic]
+    fn input_radix_invalid() {
+        let _ = ExecutionContext::new(&[]).with_input_radix(37);
+    }
+
+    #[test]
+    fn profile() {
+        #[rustfmt::skip]
+        let program = &[
+            PUSH8, 0,
+            PUSH8, 5,
+            JUMP,
+
+            PUSH8, 1,
+            ADD,
+            DUP0,
+            PRINT,
+
+            DUP0,
+            PUSH8, 10,
+            LT,
+            PUSH8S, (-12i8) as u8,
+            JCOND,
+
+            POP,
+        ];
+
+        let render = || {
+            ExecutionContext::new(program)
+                .with_profile()
+                .run()
+                .expect("VM encountered a runtime error")
+                .profile
+                .unwrap()
+                .to_string()
+        };
+
+        let rendered = render();
+        assert_eq!(rendered, render());
+        assert_eq!(
+            rendered,
+            [
+                " push8      23",
+                " dup0       21",
+                " push8s     11",
+                " lt         11",
+                " jcond      11",
+                " add        10",
+                " print      10",
+                " pop        1",
+                " jump       1",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn input_line_endings() {
+        for input in [
+            "12\r\n-13\r\n7\r\n",
+            "12\r-13\r7\r",
+            "12\n-13\n7",
+            "\n\n12 -13\r\n\r\n \t\n7\n\n",
+        ] {
+            let summary = ExecutionContext::new(&[READ, READS, READ])
+                .with_input_stream(input.as_bytes())
+                .run()
+                .unwrap_or_else(|err| panic!("{err} (input {input:?})"));
+            assert_eq!(summary.stack, val_vec(&[12, -13_i64 as u64, 7]));
+        }
+    }
+
+    #[test]
+    fn read_n() {
         #[rustfmt::skip]
         test_program(
             &[
-                // pc 0, trace step 0
-                PUSH8, 4,
-                VARRES,
+                READN, 3,
+                READ,
+                READN, 0,
+            ],
+            "12 13\n145 78",
+            |summary, _| {
+                let trace = summary.trace.as_ref().unwrap();
+                assert_eq!(trace[1].stack, val_vec(&[12, 13, 145]));
+                assert_eq!(trace[2].stack, val_vec(&[12, 13, 145, 78]));
+                assert_eq!(summary.stack, val_vec(&[12, 13, 145, 78]));
+            },
+        );
+    }
 
-                // pc 3, tr 2
-                NUMVARS,
-                POP,
+    #[test]
+    fn read_n_short_input() {
+        let mut context =
+            ExecutionContext::new(&[PUSH8, 9, READN, 3]).with_input_stream(&b"1 2"[..]);
+        assert_eq!(context.step().unwrap(), None);
+
+        // Nothing is pushed if the batch cannot be completed
+        let err = context.step().unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InputError);
+        assert_eq!(err.instr, Some(Instruction::new(Opcode::ReadN, 3)));
+        assert_eq!(context.program_counter(), 2);
+        assert_eq!(context.stack(), val_vec(&[9]));
+
+        let err = ExecutionContext::new(&[READN, 2])
+            .with_input_stream(&b"1 x"[..])
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ParseError);
+    }
 
-                // pc 5, tr 4
-                PUSH8, 2,
-                VARDISC,
+    #[test]
+    fn value_error_context() {
+        for opcode in [DIV, DIVS, MOD] {
+            let err = ExecutionContext::new(&[PUSH8, 1, PUSH8, 0, opcode])
+                .run()
+                .unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::DivideByZero);
+            assert_eq!(err.program_counter, 4);
+            assert_eq!(err.instr.unwrap().opcode, Opcode::try_from(opcode).unwrap());
+        }
+    }
 
-                // pc 8, tr 6
-                NUMVARS,
-                POP,
+    #[test]
+    fn value_error_in_loop() {
+        // Divide 100 by a counter decrementing from 3, until it reaches zero
+        #[rustfmt::skip]
+        let program = &[
+            PUSH8, 3,
+            PUSH8, 100,
+            DUP1,
+            DIV,
+            POP,
+            PUSH8, 1,
+            SUB,
+            PUSH8, 1,
+            PUSH8S, -13_i8 as u8,
+            JCOND,
+        ];
+
+        let err = ExecutionContext::new(program)
+            .with_trace()
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::DivideByZero);
+        assert_eq!(err.program_counter, 5);
+        assert_eq!(err.instr, Some(Instruction::from(Opcode::Div)));
+
+        let trace = err.trace.unwrap();
+        assert_eq!(trace.len(), 1 + 3 * 9 + 3);
+        assert_eq!(trace.last().unwrap().stack, val_vec(&[0, 100, 0]));
+    }
 
-                // pc 10, tr 8
-                PUSH8, 12,
-                PUSH8, 0,
-                VARST,
+    #[test]
+    fn from_program() {
+        #[rustfmt::skip]
+        let bytes: &[u8] = &[
+            PUSH8, 2,
+            DUP0,
+            PRINT,
+            PUSH8, 1,
+            SUB,
+            DUP0,
+            PUSH8S, (-9i8) as u8,
+            JCOND,
+        ];
+
+        let program = Program::try_from(bytes).unwrap();
+        let summary = ExecutionContext::from_program(&program)
+            .with_captured_output()
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.output.as_deref(), Some("2\n1\n"));
+    }
 
-                // pc 15, tr 11
-                PUSH8, 15,
-                PUSH8, 1,
-                VARST,
+    #[test]
+    fn optional_streams() {
+        let program = &[READ, DUP0, PRINT];
+
+        let mut output = Vec::new();
+        let summary = ExecutionContext::new(program)
+            .with_optional_input_stream(Some(&b"17"[..]))
+            .with_optional_output_stream(Some(&mut output))
+            .run()
+            .unwrap();
+        assert_eq!(summary.stack, val_vec(&[17]));
+        assert_eq!(output, b"17\n");
+
+        let err = ExecutionContext::new(program)
+            .with_input_stream(&b"17"[..])
+            .with_optional_input_stream(None::<&[u8]>)
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::NoInputStream);
+
+        let mut output = Vec::new();
+        let (_, stream) = ExecutionContext::new(program)
+            .with_input_stream(&b"17"[..])
+            .with_output_stream(&mut output)
+            .with_optional_output_stream(None::<Vec<u8>>)
+            .run_with_output()
+            .unwrap();
+        assert!(stream.is_none());
+        drop(stream);
+        assert!(output.is_empty());
+    }
 
-                // pc 20, tr 14
-                PUSH8, 0,
-                VARLD,
-                POP,
+    #[test]
+    fn trace_enabled() {
+        let program = &[PUSH8, 1, POP];
+
+        let summary = ExecutionContext::new(program)
+            .with_trace_enabled(true)
+            .run()
+            .unwrap();
+        assert_eq!(summary.trace.unwrap().len(), 2);
+
+        let summary = ExecutionContext::new(program)
+            .with_trace_enabled(false)
+            .run()
+            .unwrap();
+        assert!(summary.trace.is_none());
+
+        let summary = ExecutionContext::new(program)
+            .with_bounded_trace(1)
+            .with_trace_enabled(false)
+            .run()
+            .unwrap();
+        assert!(summary.trace.is_none());
+    }
 
-                // pc 24, tr 17
-                PUSH8, 1,
-                VARLD,
-                POP,
+    #[test]
+    fn trace_steps() {
+        let program = &[PUSH8, 1, DUP0, ADD, POP];
+
+        let summary = ExecutionContext::new(program).with_trace().run().unwrap();
+        assert_eq!(summary.trace_steps().count(), 4);
+        assert_eq!(
+            summary
+                .trace_steps()
+                .map(|snapshot| snapshot.program_counter)
+                .collect::<Vec<_>>(),
+            [0, 2, 3, 4]
+        );
 
-                // pc 28, tr 20
+        let summary = ExecutionContext::new(program)
+            .with_bounded_trace(2)
+            .run()
+            .unwrap();
+        assert_eq!(
+            summary
+                .trace_steps()
+                .map(|snapshot| snapshot.step_index)
+                .collect::<Vec<_>>(),
+            [2, 3]
+        );
+
+        let summary = ExecutionContext::new(program).run().unwrap();
+        assert_eq!(summary.trace_steps().count(), 0);
+    }
+
+    #[test]
+    fn strict_booleans() {
+        let jcond = hypescript_bytecode::hyasm! {
+            push8 2;
+            push8 2;
+            jcond;
+            push8 7;
+        };
+        let not = hypescript_bytecode::hyasm! {
+            push8 2;
+            not;
+        };
+
+        // Without strict booleans, 2 is true
+        let summary = ExecutionContext::from_instructions(&jcond).run().unwrap();
+        assert!(summary.stack.is_empty());
+        let summary = ExecutionContext::from_instructions(&not).run().unwrap();
+        assert_eq!(summary.stack, val_vec(&[0]));
+
+        let err = ExecutionContext::from_instructions(&jcond)
+            .with_strict_booleans()
+            .run()
+            .expect_err("Non-boolean jcond condition was accepted");
+        assert_eq!(err.kind, ErrorKind::NonBooleanCondition);
+        assert_eq!(err.program_counter, 4);
+
+        let err = ExecutionContext::from_instructions(&not)
+            .with_strict_booleans()
+            .run()
+            .expect_err("Non-boolean not operand was accepted");
+        assert_eq!(err.kind, ErrorKind::NonBooleanCondition);
+        assert_eq!(err.program_counter, 2);
+
+        // Canonical booleans are still accepted
+        let instrs = hypescript_bytecode::hyasm! {
+            push8 0;
+            push8 1;
+            jcond;
+            push8 1;
+            not;
+            push8 0;
+            not;
+        };
+        let summary = ExecutionContext::from_instructions(&instrs)
+            .with_strict_booleans()
+            .run()
+            .unwrap();
+        assert_eq!(summary.stack, val_vec(&[0, 1]));
+    }
+
+    #[test]
+    fn read_bool() {
+        #[rustfmt::skip]
+        test_program(
+            &[
+                READBOOL,
+                READBOOL,
+                READBOOL,
+                READBOOL,
             ],
-            "",
+            "true false\n1 0",
             |summary, _| {
-                let trace = summary.trace.as_ref().unwrap();
+                assert_eq!(summary.stack, val_vec(&[1, 0, 1, 0]));
+            },
+        );
 
-                // Reserve 4 vars, push num vars
-                assert_eq!(trace[2].local_variables, &[Value::from_u8(0); 4]);
-                assert_eq!(*trace[3].stack.last().unwrap(), Value::from_u8(4));
+        for input in ["maybe", "TRUE", "2", "-0"] {
+            let err = ExecutionContext::new(&[READBOOL])
+                .with_input_stream(input.as_bytes())
+                .run()
+                .unwrap_err();
+            assert_eq!(err.kind, ErrorKind::ParseError, "input {input:?}");
+        }
+    }
 
-                // Discard 2 vars, push num vars
-                assert_eq!(trace[6].local_variables, &[Value::from_u8(0); 2]);
-                assert_eq!(*trace[7].stack.last().unwrap(), Value::from_u8(2));
+    #[test]
+    fn print_width() {
+        let program = &[
+            PUSH8,
+            42,
+            PRINT,
+            PUSH8S,
+            -7_i8 as u8,
+            PRINTPEEKS,
+            PUSH32,
+            0,
+            1,
+            0x86,
+            0xa0,
+            PRINT,
+        ];
+
+        let mut output = Vec::new();
+        let summary = ExecutionContext::new(program)
+            .with_output_stream(&mut output)
+            .with_captured_output()
+            .with_print_width(5)
+            .run()
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "   42\n   -7\n100000\n");
+        assert_eq!(summary.output.unwrap(), "   42\n   -7\n100000\n");
+    }
 
-                // Store 12 in var 0, 15 in var 1
-                assert_eq!(trace[11].local_variables, &[Value::from_u8(12), Value::from_u8(0)]);
-                assert_eq!(trace[14].local_variables, &[Value::from_u8(12), Value::from_u8(15)]);
+    #[test]
+    fn output_error_position() {
+        /// A writer that accepts at most `capacity` bytes, in chunks of at most 3 bytes.
+        struct Limited {
+            written: Vec<u8>,
+            capacity: usize,
+        }
 
-                // Load var 0, var 1
-                assert_eq!(*trace[16].stack.last().unwrap(), Value::from_u8(12));
-                assert_eq!(*trace[19].stack.last().unwrap(), Value::from_u8(15));
+        impl Write for Limited {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                let n = buf.len().min(3).min(self.capacity - self.written.len());
+                if n == 0 {
+                    return Err(std::io::ErrorKind::BrokenPipe.into());
+                }
+                self.written.extend_from_slice(&buf[..n]);
+                Ok(n)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        #[rustfmt::skip]
+        let program = &[
+            PUSH16, 0x30, 0x39,
+            PRINT,
+            PUSH8, 7,
+            PRINT,
+            PUSH8, 255,
+            PRINT,
+            PUSH8, 1,
+            PRINT,
+        ];
+
+        let mut output = Limited {
+            written: Vec::new(),
+            capacity: 10,
+        };
+        let err = ExecutionContext::new(program)
+            .with_output_stream(&mut output)
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::OutputError);
+        assert_eq!(err.program_counter, 9);
+        assert_eq!(err.output_bytes, 10);
+        assert_eq!(output.written, b"12345\n7\n25");
+
+        // Bytes are counted for errors other than output errors, too
+        let err = ExecutionContext::new(&[PUSH8, 7, PRINT, ADD])
+            .with_output_stream(Vec::new())
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::StackUnderflow);
+        assert_eq!(err.output_bytes, 2);
+    }
+
+    #[test]
+    fn flush_each_print() {
+        #[derive(Debug, PartialEq, Eq)]
+        enum Event {
+            Write(String),
+            Flush,
+        }
+
+        /// A writer that records the calls made to it.
+        #[derive(Default)]
+        struct Recorder(Vec<Event>);
+
+        impl Write for Recorder {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0
+                    .push(Event::Write(String::from_utf8(buf.to_vec()).unwrap()));
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.0.push(Event::Flush);
+                Ok(())
             }
+        }
+
+        let program = &[PUSH8, 1, PRINT, PUSH8, 2, PRINT, PUSH8, 3, PRINTPEEK];
+
+        let mut output = Recorder::default();
+        ExecutionContext::new(program)
+            .with_output_stream(&mut output)
+            .with_flush_each_print()
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(
+            output.0,
+            [
+                Event::Write("1\n".into()),
+                Event::Flush,
+                Event::Write("2\n".into()),
+                Event::Flush,
+                Event::Write("3\n".into()),
+                Event::Flush,
+            ]
         );
+
+        // Lines are still written one at a time, but never flushed, by default
+        let mut output = Recorder::default();
+        ExecutionContext::new(program)
+            .with_output_stream(&mut output)
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(output.0.len(), 3);
+        assert!(output
+            .0
+            .iter()
+            .all(|event| matches!(event, Event::Write(_))));
+    }
+
+    #[test]
+    fn slot_type_verification() {
+        use SlotType::*;
+
+        let instrs = [
+            Instruction::new(Opcode::Push8, 5),
+            Instruction::new(Opcode::Push8, 2),
+            Instruction::from(Opcode::Gt),
+            Instruction::new(Opcode::Push8, 1),
+            Instruction::from(Opcode::JCond),
+            Instruction::from(Opcode::Halt),
+        ];
+        let program = hypescript_bytecode::instructions_to_vec(&instrs);
+        let run = |types: Vec<Vec<SlotType>>| {
+            ExecutionContext::new(&program)
+                .with_slot_type_verification(SlotTypeMap::from_instructions(&instrs, types))
+                .run()
+        };
+
+        let types = vec![
+            vec![],
+            vec![Int],
+            vec![Int, Int],
+            vec![Bool],
+            vec![Bool, Int],
+            vec![],
+        ];
+        run(types.clone()).unwrap();
+
+        // Missing entries are not checked
+        run(types[..2].to_vec()).unwrap();
+
+        // Condition not marked as a boolean
+        let mut confused = types.clone();
+        confused[4] = vec![Int, Int];
+        let err = run(confused).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::SlotTypeMismatch);
+        assert_eq!(err.program_counter, 7);
+        assert_eq!(err.instr, Some(Instruction::from(Opcode::JCond)));
+
+        // Non-canonical boolean
+        let mut confused = types.clone();
+        confused[1] = vec![Bool];
+        let err = run(confused).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::SlotTypeMismatch);
+        assert_eq!(err.program_counter, 2);
+
+        // Wrong stack depth
+        let mut confused = types;
+        confused[2] = vec![Int];
+        let err = run(confused).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::SlotTypeMismatch);
+        assert_eq!(err.program_counter, 4);
+    }
+
+    #[test]
+    fn input_blank_lines() {
+        let input = "\n   \n1\n\n\t\n2 3\n \n\n4\n\n  \t \n";
+        let mut context = ExecutionContext::new(&[READ, READN, 2, READ, READ])
+            .with_input_stream(input.as_bytes());
+
+        for expected in [&[1][..], &[1, 2, 3], &[1, 2, 3, 4]] {
+            assert_eq!(context.step().unwrap(), None);
+            assert_eq!(context.stack(), val_vec(expected));
+        }
+
+        // Only whitespace remains
+        let err = context.step().unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InputError);
+    }
+
+    #[test]
+    fn input_exhausted() {
+        for input in ["", "\n\r\n", "4"] {
+            let err = ExecutionContext::new(&[READ, READ])
+                .with_input_stream(input.as_bytes())
+                .run()
+                .unwrap_err();
+            assert_eq!(err.kind, ErrorKind::InputError, "input {input:?}");
+        }
+    }
+
+    #[test]
+    fn require_input_consumed() {
+        let run = |input: &str, require: bool| {
+            let context =
+                ExecutionContext::new(&[READ, READ, ADD]).with_input_stream(input.as_bytes());
+            if require {
+                context.with_require_input_consumed().run()
+            } else {
+                context.run()
+            }
+        };
+
+        // Surplus input is ignored by default
+        assert_eq!(run("1 2 3\n", false).unwrap().result_u64(), Some(3));
+
+        // Leftover tokens on the last line read, or on later lines, are errors
+        for input in ["1 2 3\n", "1\n2\n3\n", "1 2\n\n  \n7"] {
+            let err = run(input, true).unwrap_err();
+            assert_eq!(err.kind, ErrorKind::UnconsumedInput, "input {input:?}");
+            assert_eq!(err.program_counter, 3);
+            assert_eq!(err.instr, None);
+        }
+
+        // Trailing blank lines are not
+        for input in ["1 2", "1\n2\n", "1 2\n\n \t\n"] {
+            let summary = run(input, true).unwrap();
+            assert_eq!(summary.result_u64(), Some(3), "input {input:?}");
+        }
     }
 
     #[test]
-    fn stack_management() {
+    fn time_limit() {
+        // Infinite loop
         #[rustfmt::skip]
-        test_program(
-            &[
-                // tr 0
-                PUSH8, 4,
-                PUSH8S, 0xff,
-
-                // tr 2
-                PUSH16, 0xbe, 0xef,
-                PUSH16S, 0xca, 0xfe,
+        let program = &[
+            NUMVARS,
+            POP,
+            PUSH8S, (-3i8) as u8,
+            JUMP,
+        ];
+
+        let err = ExecutionContext::new(program)
+            .with_time_limit(Duration::from_millis(10))
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TimeLimitExceeded);
+
+        let summary = ExecutionContext::new(&[PUSH8, 1, HALT])
+            .with_time_limit(Duration::from_secs(60))
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.stack, val_vec(&[1]));
+    }
 
-                // tr 4
-                PUSH32, 0, 0, 0xde, 0xed,
-                PUSH32S, 0xde, 0xad, 0xbe, 0xef,
+    #[test]
+    fn simulated_clock() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+        use std::time::Instant;
+
+        /// A clock that advances by one millisecond each time it is queried.
+        struct TickingClock {
+            start: Instant,
+            ticks: Cell<u32>,
+        }
 
-                // tr 6
-                PUSH64, 0xde, 0xad, 0xbe, 0xef, 0xca, 0xfe, 0xfa, 0xce,
+        impl Clock for TickingClock {
+            fn now(&self) -> Instant {
+                let ticks = self.ticks.get();
+                self.ticks.set(ticks + 1);
+                self.start + Duration::from_millis(ticks as u64)
+            }
+        }
 
-                // tr 7
-                SWAP, SWAP,
+        let new_clock = || {
+            Rc::new(TickingClock {
+                start: Instant::now(),
+                ticks: Cell::new(0),
+            })
+        };
 
-                // tr 9
-                DUP0, POP,
+        // 5000 instructions, one byte each
+        let program = [NUMVARS, POP].repeat(2500);
+
+        // The clock is queried once at the start, and then before instructions 0, 1024, 2048,
+        // 3072, and 4096; the fifth query after the start is the first to reach the deadline
+        let clock = new_clock();
+        let err = ExecutionContext::new(&program)
+            .with_time_limit(Duration::from_millis(5))
+            .with_clock(clock.clone())
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TimeLimitExceeded);
+        assert_eq!(err.program_counter, 4 * TIME_CHECK_INTERVAL);
+        assert_eq!(clock.ticks.get(), 6);
+
+        // With one more tick to spare, the program finishes before the next check
+        let clock = new_clock();
+        let summary = ExecutionContext::new(&program)
+            .with_time_limit(Duration::from_millis(6))
+            .with_clock(clock.clone())
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.instruction_count, 5000);
+        assert_eq!(clock.ticks.get(), 6);
+
+        // The clock is never queried without a time limit
+        let clock = new_clock();
+        ExecutionContext::new(&program)
+            .with_clock(clock.clone())
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(clock.ticks.get(), 0);
+    }
 
-                // tr 11
-                DUP1, POP,
+    #[test]
+    fn gas_limit() {
+        let varres_heavy = [PUSH8, 0, VARRES].repeat(10);
+        let pop_heavy = [PUSH8, 0, POP].repeat(10);
+
+        // Both programs execute 20 instructions
+        let summary = ExecutionContext::new(&pop_heavy)
+            .with_gas_limit(50)
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.instruction_count, 20);
+        assert_eq!(summary.gas_used, 30);
+
+        let err = ExecutionContext::new(&varres_heavy)
+            .with_gas_limit(50)
+            .run()
+            .expect_err("Gas limit was not enforced");
+        assert_eq!(err.kind, ErrorKind::GasExhausted);
+        // Five pushes and reservations use exactly 50 gas, so the sixth push exhausts it
+        assert_eq!(err.program_counter, 15);
+        assert_eq!(err.instr, Some(Instruction::new(Opcode::Push8, 0)));
+
+        // The cost model is configurable, and an exact budget suffices
+        let summary = ExecutionContext::new(&varres_heavy)
+            .with_gas_limit(20)
+            .with_cost_model(|_| 1)
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.gas_used, 20);
+
+        // Without a limit, gas is still counted
+        let summary = ExecutionContext::new(&varres_heavy)
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.gas_used, 100);
+
+        // Exhausting gas is recoverable; stepping resumes once the limit is raised
+        let mut context = ExecutionContext::new(&varres_heavy).with_gas_limit(50);
+        let err = loop {
+            match context.step() {
+                Ok(None) => {}
+                Ok(Some(termination)) => panic!("Program terminated with {termination:?}"),
+                Err(err) => break err,
+            }
+        };
+        assert!(err.kind.is_recoverable());
+        assert_eq!(context.program_counter(), 15);
+
+        let summary = context
+            .with_gas_limit(100)
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.gas_used, 100);
+        assert_eq!(summary.instruction_count, 20);
+    }
 
-                // tr 13
-                DUP2, POP,
+    #[test]
+    fn memory_limit() {
+        // Reservations are refused before allocating
+        let err = ExecutionContext::new(&[PUSH8, 1, PUSH64, 0xff, 0, 0, 0, 0, 0, 0, 0, VARRES])
+            .with_memory_limit(16)
+            .run()
+            .expect_err("Memory limit was not enforced");
+        assert_eq!(err.kind, ErrorKind::MemoryLimitExceeded);
+        assert_eq!(err.program_counter, 11);
+
+        // The stack and variables are counted together
+        let program = &[PUSH8, 3, VARRES, PUSH8, 1, PUSH8, 2];
+        let err = ExecutionContext::new(program)
+            .with_memory_limit(4)
+            .run()
+            .expect_err("Memory limit was not enforced");
+        assert_eq!(err.kind, ErrorKind::MemoryLimitExceeded);
+        assert_eq!(err.program_counter, 5);
+
+        ExecutionContext::new(program)
+            .with_memory_limit(5)
+            .run()
+            .expect("VM encountered a runtime error");
+    }
 
-                // tr 15
-                DUP3, POP,
+    #[test]
+    fn output_limit() {
+        let program = &[PUSH8, 10, PRINT, PUSH8, 200, PRINT];
+        let mut output = Vec::new();
+        let err = ExecutionContext::new(program)
+            .with_output_stream(&mut output)
+            .with_output_limit(6)
+            .run()
+            .expect_err("Output limit was not enforced");
+        assert_eq!(err.kind, ErrorKind::OutputLimitExceeded);
+        assert_eq!(err.program_counter, 5);
+        assert_eq!(err.output_bytes, 3);
+        assert_eq!(output, b"10\n");
+
+        let summary = ExecutionContext::new(program)
+            .with_captured_output()
+            .with_output_limit(7)
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.output.as_deref(), Some("10\n200\n"));
+    }
 
-                // tr 17
-            ],
-            "",
-            |summary, _ | {
-                let trace = summary.trace.as_ref().unwrap();
+    #[test]
+    fn output_bytes() {
+        let instrs = hypescript_bytecode::hyasm! {
+            push8 7;
+            print;
+            push16 1234;
+            print;
+            push8 1;
+            push8 1;
+            eq;
+            print;
+        };
 
-                // push8, push8s
-                assert_eq!(trace[2].stack, val_vec(&[4, 0xffffffffffffffff]));
+        let mut output = Vec::new();
+        let summary = ExecutionContext::from_instructions(&instrs)
+            .with_output_stream(&mut output)
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.output_bytes, output.len() as u64);
+        assert_eq!(summary.output_bytes, 2 + 5 + 2);
+
+        // Output is counted even when it is discarded
+        let summary = ExecutionContext::from_instructions(&instrs)
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.output_bytes, 9);
+    }
 
-                // push16, push16s
-                assert_eq!(trace[4].stack, val_vec(&[
-                        4,
-                        0xffffffffffffffff,
-                        0xbeef,
-                        0xffffffffffffcafe,
-                ]));
+    #[test]
+    fn sandbox() {
+        // Push values forever
+        #[rustfmt::skip]
+        let program = &[
+            PUSH8, 1,
+
+            DUP0,
+            PUSH8S, (-4i8) as u8,
+            JUMP,
+        ];
+
+        let err = ExecutionContext::sandboxed(program)
+            .run()
+            .expect_err("Sandbox did not contain the program");
+        assert_eq!(err.kind, ErrorKind::MemoryLimitExceeded);
+
+        // Defaults can be overridden
+        let err = ExecutionContext::sandboxed(program)
+            .with_gas_limit(1000)
+            .run()
+            .expect_err("Sandbox did not contain the program");
+        assert_eq!(err.kind, ErrorKind::GasExhausted);
+
+        let summary = ExecutionContext::new(&[PUSH8, 1, PRINT])
+            .with_captured_output()
+            .with_sandbox_defaults()
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.output.as_deref(), Some("1\n"));
+    }
 
-                // push32, push32s
-                assert_eq!(trace[6].stack, val_vec(&[
-                        4,
-                        0xffffffffffffffff,
-                        0xbeef,
-                        0xffffffffffffcafe,
-                        0xdeed,
-                        0xffffffffdeadbeef,
-                ]));
+    #[test]
+    fn captured_output() {
+        #[rustfmt::skip]
+        let program = &[
+            PUSH8, 2,
+            VARRES,
+            PUSH8, 5,
+            PUSH8, 0,
+            VARST,
+            PUSH8, 4,
+            PUSH8, 0,
+            VARLD,
+            ADD,
+            PUSH8, 1,
+            VARST,
+            PUSH8, 0,
+            VARLD,
+            PRINT,
+            PUSH8, 1,
+            VARLD,
+            PRINT,
+        ];
+
+        let summary = ExecutionContext::new(program)
+            .with_captured_output()
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.output.as_deref(), Some("5\n9\n"));
+
+        let summary = ExecutionContext::new(program)
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.output, None);
+    }
 
-                // push64
-                assert_eq!(trace[7].stack, val_vec(&[
-                        4,
-                        0xffffffffffffffff,
-                        0xbeef,
-                        0xffffffffffffcafe,
-                        0xdeed,
-                        0xffffffffdeadbeef,
-                        0xdeadbeefcafeface,
-                ]));
+    #[test]
+    fn termination() {
+        let summary = ExecutionContext::new(&[PUSH8, 1, HALT, PUSH8, 2])
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.termination, Termination::Halted);
+        assert_eq!(summary.stack, val_vec(&[1]));
+
+        let summary = ExecutionContext::new(&[PUSH8, 1, PUSH8, 2])
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.termination, Termination::FellOffEnd);
+        assert_eq!(summary.stack, val_vec(&[1, 2]));
+    }
 
-                // swap
-                assert_eq!(trace[8].stack, val_vec(&[
-                        4,
-                        0xffffffffffffffff,
-                        0xbeef,
-                        0xffffffffffffcafe,
-                        0xdeed,
-                        0xdeadbeefcafeface,
-                        0xffffffffdeadbeef,
-                ]));
+    #[test]
+    fn huge_var_index() {
+        for index in [u64::MAX, 1 << 32, (1 << 32) + 1] {
+            for access in [
+                hypescript_bytecode::hyasm! { push64 index; varld; },
+                hypescript_bytecode::hyasm! { push8 7; push64 index; varst; },
+            ] {
+                let instrs = [hypescript_bytecode::hyasm! { push8 2; varres; }, access].concat();
+                let err = ExecutionContext::from_instructions(&instrs)
+                    .run()
+                    .unwrap_err();
+                assert_eq!(err.kind, ErrorKind::OutOfBoundsVariableReference);
+            }
+        }
+    }
 
-                // swap, dup0
-                assert_eq!(trace[10].stack, val_vec(&[
-                        4,
-                        0xffffffffffffffff,
-                        0xbeef,
-                        0xffffffffffffcafe,
-                        0xdeed,
-                        0xffffffffdeadbeef,
-                        0xdeadbeefcafeface,
-                        0xdeadbeefcafeface,
-                ]));
+    #[test]
+    fn unrecognized_opcode() {
+        let err = ExecutionContext::new(&[PUSH8, 1, 0x20, PUSH8, 2])
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnrecognizedOpcode);
+        assert_eq!(err.program_counter, 2);
+        assert!(err.instr.is_none());
+    }
 
-                // pop, dup1
-                assert_eq!(trace[12].stack, val_vec(&[
-                        4,
-                        0xffffffffffffffff,
-                        0xbeef,
-                        0xffffffffffffcafe,
-                        0xdeed,
-                        0xffffffffdeadbeef,
-                        0xdeadbeefcafeface,
-                        0xffffffffdeadbeef,
-                ]));
+    #[test]
+    fn assert() {
+        let summary = ExecutionContext::new(&[PUSH8, 7, PUSH8, 1, ASSERT, PUSH8, 0xff, ASSERT])
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.stack, val_vec(&[7]));
+
+        let err = ExecutionContext::new(&[PUSH8, 1, ASSERT, PUSH8, 0, ASSERT, PUSH8, 2])
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::AssertionFailed);
+        assert_eq!(err.program_counter, 5);
+        assert_eq!(err.instr, Some(Instruction::from(Opcode::Assert)));
+
+        let err = ExecutionContext::new(&[ASSERT]).run().unwrap_err();
+        assert_eq!(err.kind, ErrorKind::StackUnderflow);
+    }
 
-                // pop, dup2
-                assert_eq!(trace[14].stack, val_vec(&[
-                        4,
-                        0xffffffffffffffff,
-                        0xbeef,
-                        0xffffffffffffcafe,
-                        0xdeed,
-                        0xffffffffdeadbeef,
-                        0xdeadbeefcafeface,
-                        0xdeed,
-                ]));
+    #[test]
+    fn incomplete_literal() {
+        let cases: &[&[u8]] = &[
+            &[PUSH8],
+            &[PUSH8S],
+            &[DUPN],
+            &[PUSH16, 1],
+            &[PUSH16S],
+            &[PUSH32, 1, 2, 3],
+            &[PUSH32S, 1],
+            &[PUSH64, 1, 2, 3, 4, 5, 6, 7],
+        ];
+
+        for &truncated in cases {
+            let mut program = vec![PUSH8, 1];
+            program.extend_from_slice(truncated);
+
+            let err = ExecutionContext::new(&program).run().unwrap_err();
+            assert_eq!(err.kind, ErrorKind::IncompleteLiteral, "{program:?}");
+            assert_eq!(err.program_counter, 2, "{program:?}");
+            assert!(err.instr.is_none());
+        }
 
-                // pop, dup3
-                assert_eq!(trace[16].stack, val_vec(&[
-                        4,
-                        0xffffffffffffffff,
-                        0xbeef,
-                        0xffffffffffffcafe,
-                        0xdeed,
-                        0xffffffffdeadbeef,
-                        0xdeadbeefcafeface,
-                        0xffffffffffffcafe,
-                ]));
-            },
-        );
+        // Complete literals at the very end of the program are fine
+        let summary = ExecutionContext::new(&[PUSH8, 1, PUSH16, 0, 2])
+            .run()
+            .expect("VM encountered a runtime error");
+        assert_eq!(summary.stack, val_vec(&[1, 2]));
     }
 
     #[test]
-    fn arithmetic() {
-        #[rustfmt::skip]
-        test_program(
-            &[
-                // tr 0
-                PUSH8, 2,
-                PUSH8, 6,
-                ADD,
+    fn recover_output_stream() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
 
-                // tr 3
-                POP,
-                PUSH8, 12,
-                PUSH8, 5,
-                SUB,
+        // An owned stream whose contents can be inspected from outside the VM
+        struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
 
-                // tr 7
-                POP,
-                PUSH8, 7,
-                PUSH8, 6,
-                MUL,
+        impl Write for SharedBuffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
 
-                // tr 11
-                POP,
-                PUSH8, 17,
-                PUSH8, 8,
-                MOD,
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
 
-                // tr 15
-                POP,
-                PUSH8, 88,
-                PUSH8, 11,
-                DIV,
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let (summary, output) = ExecutionContext::new(&[PUSH8, 3, PRINT, PUSH8S, 0xff, PRINTS])
+            .with_output_stream(SharedBuffer(Rc::clone(&buffer)))
+            .run_with_output()
+            .expect("VM encountered a runtime error");
+        assert!(summary.stack.is_empty());
+        assert_eq!(&*buffer.borrow(), b"3\n-1\n");
+
+        // The recovered stream is still usable by the host
+        let mut output = output.expect("output stream should be returned");
+        writeln!(output, "done").unwrap();
+        assert_eq!(&*buffer.borrow(), b"3\n-1\ndone\n");
+
+        let (_, output) = ExecutionContext::new(&[])
+            .run_with_output()
+            .expect("VM encountered a runtime error");
+        assert!(output.is_none());
+    }
 
-                // tr 19
-                POP,
-                PUSH8S, -20_i8 as u8,
-                PUSH8, 5,
-                DIVS,
+    #[test]
+    fn single_step() {
+        let mut context = ExecutionContext::new(&[PUSH8, 3, DUP0, ADD, POP, POP]);
+        assert_eq!(
+            context.next_instruction(),
+            Some(Instruction::new(Opcode::Push8, 3))
+        );
 
-                // tr 23
-            ],
-            "",
-            |summary, _| {
-                let trace = summary.trace.as_ref().unwrap();
+        assert_eq!(context.step().unwrap(), None);
+        assert_eq!(context.program_counter(), 2);
+        assert_eq!(context.stack(), val_vec(&[3]));
 
-                // 2 + 6
-                assert_eq!(trace[3].stack, val_vec(&[8]));
+        assert_eq!(context.step().unwrap(), None);
+        assert_eq!(context.step().unwrap(), None);
+        assert_eq!(context.stack(), val_vec(&[6]));
+        assert_eq!(
+            context.next_instruction(),
+            Some(Instruction::from(Opcode::Pop))
+        );
 
-                // 12 - 5
-                assert_eq!(trace[7].stack, val_vec(&[7]));
+        assert_eq!(context.step().unwrap(), None);
 
-                // 7 * 6
-                assert_eq!(trace[11].stack, val_vec(&[42]));
+        // Errors leave the machine in place, and can be retried
+        let err = context.step().unwrap_err();
+        assert_eq!(err.kind, ErrorKind::StackUnderflow);
+        assert_eq!(context.program_counter(), 5);
+        assert!(context.step().is_err());
 
-                // 17 % 8
-                assert_eq!(trace[15].stack, val_vec(&[1]));
+        let mut context = ExecutionContext::new(&[PUSH8, 1, HALT]);
+        assert_eq!(context.step().unwrap(), None);
+        assert_eq!(context.step().unwrap(), Some(Termination::Halted));
+        assert_eq!(context.step().unwrap(), Some(Termination::Halted));
+        assert_eq!(context.program_counter(), 2);
 
-                // 88 / 11
-                assert_eq!(trace[19].stack, val_vec(&[8]));
+        let mut context = ExecutionContext::new(&[PUSH8, 1]);
+        assert_eq!(context.step().unwrap(), Some(Termination::FellOffEnd));
+        assert_eq!(context.next_instruction(), None);
 
-                // -20 / 5
-                assert_eq!(summary.stack, val_vec(&[-4_i64 as u64]));
-            },
+        let mut context = ExecutionContext::new(&[PUSH8, 1]).with_entry_point(1);
+        assert_eq!(
+            context.step().unwrap_err().kind,
+            ErrorKind::InvalidEntryPoint
         );
     }
 
     #[test]
-    fn input() {
-        #[rustfmt::skip]
-        test_program(
-            &[
-                READ,
-                READS,
-                READS,
-                READ,
-            ],
-            "12 13\n-145   \t\t     78", // Some random-ish assortments of whitespace in there, just to test
-            |summary, _| {
-                let trace = summary.trace.as_ref().unwrap();
-                assert_eq!(trace[1].stack, val_vec(&[12]));
-                assert_eq!(trace[2].stack, val_vec(&[12, 13]));
-                assert_eq!(trace[3].stack, val_vec(&[12, 13, -145_i64 as u64]));
-                assert_eq!(summary.stack, val_vec(&[12, 13, -145_i64 as u64, 78]));
+    fn state_between_steps() {
+        let instrs = hypescript_bytecode::hyasm! {
+            push8 1;
+            varres;
+            push8 9;
+            push8 0;
+            varst;
+            halt;
+        };
+        let mut context = ExecutionContext::from_instructions(&instrs);
+
+        assert_eq!(context.program_counter(), 0);
+        assert_eq!(context.next_instruction(), Some(instrs[0]));
+        assert!(context.stack().is_empty());
+        assert!(context.local_vars().is_empty());
+
+        context.step().unwrap();
+        context.step().unwrap();
+        context.step().unwrap();
+        assert_eq!(context.program_counter(), 5);
+        assert_eq!(context.next_instruction(), Some(instrs[3]));
+        assert_eq!(context.stack(), val_vec(&[9]));
+        assert_eq!(context.local_vars(), val_vec(&[0]));
+
+        context.step().unwrap();
+        context.step().unwrap();
+        assert_eq!(context.program_counter(), 8);
+        assert_eq!(
+            context.next_instruction(),
+            Some(Instruction::from(Opcode::Halt))
+        );
+        assert!(context.stack().is_empty());
+        assert_eq!(context.local_vars(), val_vec(&[9]));
+
+        assert_eq!(context.step().unwrap(), Some(Termination::Halted));
+        assert_eq!(context.program_counter(), 8);
+        assert_eq!(context.local_vars(), val_vec(&[9]));
+    }
+
+    #[test]
+    fn vars_hint() {
+        // Reserve and discard a growing number of variables, three times over
+        let program = [
+            PUSH8, 2, VARRES, PUSH8, 2, VARDISC, PUSH8, 8, VARRES, PUSH8, 8, VARDISC,
+        ];
+        let program = program.repeat(3);
+
+        // Count the number of times the variable array is reallocated
+        let count_allocations = |mut context: ExecutionContext| {
+            let mut allocations = 0;
+            let mut prev = (context.local_vars.as_ptr(), context.local_vars.capacity());
+            while context.step().unwrap().is_none() {
+                let cur = (context.local_vars.as_ptr(), context.local_vars.capacity());
+                if cur != prev {
+                    allocations += 1;
+                    prev = cur;
+                }
             }
+            assert!(context.local_vars.is_empty());
+            allocations
+        };
+
+        // Capacity is retained across discards, so only growth past the previous peak reallocates
+        let unhinted = count_allocations(ExecutionContext::new(&program));
+        assert!((1..=2).contains(&unhinted), "{unhinted} allocations");
+
+        assert_eq!(
+            count_allocations(ExecutionContext::new(&program).with_vars_hint(8)),
+            0
         );
     }
 
+    #[test]
+    fn typed_stack_format() {
+        use hypescript_bytecode::slot_types::SlotType;
+        use std::fmt::Write;
+
+        let stack = val_vec(&[1, 0, 5]);
+        let mut formatted = String::new();
+        trace::format_stack_typed(
+            &mut formatted,
+            &stack,
+            Some(&[SlotType::Bool, SlotType::Bool]),
+        )
+        .unwrap();
+
+        let mut expected = String::new();
+        writeln!(expected, "  0: {:x}\t\t5\t5", stack[2]).unwrap();
+        writeln!(expected, "  1: {:x}\t\tfalse", stack[1]).unwrap();
+        writeln!(expected, "  2: {:x}\t\ttrue", stack[0]).unwrap();
+        assert_eq!(formatted, expected);
+
+        let mut untyped = String::new();
+        trace::format_stack(&mut untyped, &stack).unwrap();
+        let mut typeless = String::new();
+        trace::format_stack_typed(&mut typeless, &stack, None).unwrap();
+        assert_eq!(untyped, typeless);
+    }
+
+    #[test]
+    fn trace_digest() {
+        let digest = |program: &[u8]| {
+            let summary = ExecutionContext::new(program)
+                .with_trace()
+                .run()
+                .expect("VM encountered a runtime error");
+            trace::trace_digest(&summary.trace.unwrap())
+        };
+
+        let program = &[
+            PUSH8, 1, VARRES, PUSH8, 7, PUSH8, 0, VARST, PUSH8, 2, DUP0, MUL,
+        ];
+        assert_eq!(digest(program), digest(program));
+
+        // The digest must not change between platforms or releases
+        assert_eq!(digest(program), 0x08cc_c844_0d4e_bbec);
+
+        // Changes to literals, stack contents, and instruction order are all detected
+        let literal = &[
+            PUSH8, 1, VARRES, PUSH8, 8, PUSH8, 0, VARST, PUSH8, 2, DUP0, MUL,
+        ];
+        let opcode = &[
+            PUSH8, 1, VARRES, PUSH8, 7, PUSH8, 0, VARST, PUSH8, 2, DUP0, ADD,
+        ];
+        let order = &[
+            PUSH8, 1, VARRES, PUSH8, 2, PUSH8, 7, PUSH8, 0, VARST, DUP0, MUL,
+        ];
+        for changed in [&literal[..], opcode, order] {
+            assert_ne!(digest(program), digest(changed));
+        }
+
+        assert_eq!(trace::trace_digest(&[]), 0xcbf29ce484222325);
+    }
+
+    #[test]
+    fn bounded_trace() {
+        let program = [
+            PUSH8, 0, PUSH8, 1, PUSH8, 2, PUSH8, 3, PUSH8, 4, PUSH8, 5, POP, POP,
+        ];
+
+        let summary = ExecutionContext::new(&program)
+            .with_trace()
+            .run()
+            .expect("VM encountered a runtime error");
+        let trace = summary.trace.unwrap();
+        assert_eq!(trace.len(), 8);
+        assert!(trace.iter().enumerate().all(|(i, s)| s.step_index == i));
+
+        let summary = ExecutionContext::new(&program)
+            .with_bounded_trace(3)
+            .run()
+            .expect("VM encountered a runtime error");
+        let trace = summary.trace.unwrap();
+        let steps: Vec<_> = trace.iter().map(|s| s.step_index).collect();
+        assert_eq!(steps, [5, 6, 7]);
+        assert_eq!(trace[0].program_counter, 10);
+        assert_eq!(trace[0].stack, val_vec(&[0, 1, 2, 3, 4]));
+
+        let mut formatted = String::new();
+        trace::format_trace(&mut formatted, &trace).unwrap();
+        let headers: Vec<_> = formatted.lines().filter(|l| l.ends_with("==")).collect();
+        assert_eq!(headers, ["5 ==", "6 ==", "7 =="]);
+
+        // Errors carry the bounded trace, ending with the failing instruction
+        let err = ExecutionContext::new(&[PUSH8, 1, POP, POP])
+            .with_bounded_trace(2)
+            .run()
+            .unwrap_err();
+        let steps: Vec<_> = err.trace.unwrap().iter().map(|s| s.step_index).collect();
+        assert_eq!(steps, [1, 2]);
+
+        let summary = ExecutionContext::new(&program)
+            .with_bounded_trace(0)
+            .run()
+            .expect("VM encountered a runtime error");
+        assert!(summary.trace.unwrap().is_empty());
+    }
+
     // TODO: other instructions, and runtime errors
 }
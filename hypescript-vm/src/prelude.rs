@@ -0,0 +1,9 @@
+//! Commonly used types, for glob import.
+//!
+//! ```
+//! use hypescript_vm::prelude::*;
+//! ```
+
+pub use crate::error::{Error, ErrorKind, Result};
+pub use crate::value::Value;
+pub use crate::{ExecutionContext, ExecutionSummary};
@@ -0,0 +1,81 @@
+//! Per-opcode execution profiles.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+use hypescript_bytecode::Opcode;
+
+/// A count of the number of times each opcode was executed during a program run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    counts: HashMap<Opcode, u64>,
+}
+
+impl Profile {
+    /// Record a single execution of the given opcode.
+    pub fn record(&mut self, opcode: Opcode) {
+        *self.counts.entry(opcode).or_insert(0) += 1;
+    }
+
+    /// Get the number of times the given opcode was executed.
+    pub fn count(&self, opcode: Opcode) -> u64 {
+        self.counts.get(&opcode).copied().unwrap_or(0)
+    }
+
+    /// Get the total number of instructions executed.
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Get the recorded opcodes and their counts, in a deterministic order.
+    ///
+    /// Entries are sorted by descending count. Opcodes with equal counts are sorted by ascending
+    /// binary opcode value.
+    pub fn sorted(&self) -> Vec<(Opcode, u64)> {
+        let mut entries: Vec<_> = self.counts.iter().map(|(&op, &n)| (op, n)).collect();
+        entries.sort_by(|(op_a, n_a), (op_b, n_b)| {
+            n_b.cmp(n_a)
+                .then_with(|| u8::from(*op_a).cmp(&u8::from(*op_b)))
+        });
+        entries
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (opcode, count) in self.sorted() {
+            writeln!(f, " {:<10} {count}", opcode.mnemonic())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display_order() {
+        let ops = [
+            Opcode::Add,
+            Opcode::Push8,
+            Opcode::Print,
+            Opcode::Push8,
+            Opcode::Sub,
+            Opcode::Push8,
+            Opcode::Add,
+        ];
+
+        let mut forward = Profile::default();
+        ops.iter().copied().for_each(|op| forward.record(op));
+
+        let mut backward = Profile::default();
+        ops.iter().rev().copied().for_each(|op| backward.record(op));
+
+        let expected = " push8      3\n add        2\n sub        1\n print      1\n";
+        assert_eq!(forward.to_string(), expected);
+        assert_eq!(backward.to_string(), expected);
+        assert_eq!(forward.total(), 7);
+    }
+}
@@ -2,13 +2,20 @@
 
 use std::fmt::{self, Display, Formatter};
 
-use hypescript_bytecode::Instruction;
+use hypescript_bytecode::slot_types::{SlotType, SlotTypeMap};
+use hypescript_bytecode::{Instruction, Opcode};
 
 use crate::value::Value;
 
 /// A snapshot of the machine state before executing an instruction.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Snapshot {
+    /// The number of instructions executed before this snapshot was taken.
+    ///
+    /// This counts from the start of execution, so it is unaffected by bounded traces discarding
+    /// older snapshots.
+    pub step_index: usize,
+
     /// The current program counter, the address of the next instruction.
     pub program_counter: usize,
 
@@ -22,46 +29,397 @@ pub struct Snapshot {
     pub local_variables: Vec<Value>,
 }
 
-impl Display for Snapshot {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        writeln!(f, "pc {}", self.program_counter)?;
-        writeln!(f, "{}", self.next_instruction)?;
+/// A lightweight view of the machine state before executing an instruction, passed to step hooks.
+///
+/// Unlike [`Snapshot`], this does not copy the stack or local variables, and so is cheap to
+/// produce at every step. See
+/// [`ExecutionContext::with_step_hook`](crate::ExecutionContext::with_step_hook).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepInfo {
+    /// The number of instructions executed before this step.
+    pub step_index: usize,
 
-        writeln!(f, "stack")?;
-        format_stack(f, &self.stack)?;
+    /// The current program counter, the address of the next instruction.
+    pub program_counter: usize,
+
+    /// The instruction about to be executed.
+    pub next_instruction: Instruction,
 
-        writeln!(f, "vars")?;
-        format_vars(f, &self.local_variables)
+    /// The current depth of the operand stack.
+    pub stack_len: usize,
+
+    /// The current length of the local variables array.
+    pub local_vars_len: usize,
+}
+
+/// Which columns to print for each value when formatting stacks and variables.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TraceFormat {
+    /// Print each value in hexadecimal, unsigned decimal, and signed decimal.
+    #[default]
+    Full,
+
+    /// Print each value in unsigned and signed decimal only.
+    DecimalOnly,
+
+    /// Print each value in hexadecimal only.
+    HexOnly,
+}
+
+/// A field of a [`Snapshot`], as reported by [`Snapshot::differences`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SnapshotField {
+    StepIndex,
+    ProgramCounter,
+    NextInstruction,
+    Stack,
+    LocalVariables,
+}
+
+impl Snapshot {
+    /// List the fields in which this snapshot differs from `other`, in declaration order.
+    pub fn differences(&self, other: &Snapshot) -> Vec<SnapshotField> {
+        let mut fields = Vec::new();
+        if self.step_index != other.step_index {
+            fields.push(SnapshotField::StepIndex);
+        }
+        if self.program_counter != other.program_counter {
+            fields.push(SnapshotField::ProgramCounter);
+        }
+        if self.next_instruction != other.next_instruction {
+            fields.push(SnapshotField::NextInstruction);
+        }
+        if self.stack != other.stack {
+            fields.push(SnapshotField::Stack);
+        }
+        if self.local_variables != other.local_variables {
+            fields.push(SnapshotField::LocalVariables);
+        }
+        fields
+    }
+
+    /// Format this snapshot, printing stack and variable values in the given format.
+    pub fn format_with<W: fmt::Write>(&self, stream: &mut W, format: TraceFormat) -> fmt::Result {
+        self.format(stream, None, format)
+    }
+
+    fn format<W: fmt::Write>(
+        &self,
+        stream: &mut W,
+        types: Option<&[SlotType]>,
+        format: TraceFormat,
+    ) -> fmt::Result {
+        writeln!(stream, "pc {}", self.program_counter)?;
+        writeln!(stream, "{}", self.next_instruction)?;
+
+        writeln!(stream, "stack")?;
+        format_stack_with(stream, &self.stack, types, format)?;
+
+        writeln!(stream, "vars")?;
+        format_vars_with(stream, &self.local_variables, format)
+    }
+}
+
+impl Display for Snapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.format(f, None, TraceFormat::Full)
     }
 }
 
 pub fn format_stack<W: fmt::Write>(stream: &mut W, stack: &[Value]) -> fmt::Result {
-    for (i, v) in stack.iter().rev().enumerate() {
-        writeln!(stream, " {i:2}: {v:x}\t\t{v}\t{v:-}")?;
+    format_stack_typed(stream, stack, None)
+}
+
+/// Format the operand stack, rendering slots known to be booleans as `true` or `false`.
+///
+/// `types` gives the types of the stack slots, bottom of the stack first. If it is shorter than the
+/// stack, the slots nearest the bottom are matched against it and any remaining slots are
+/// formatted as integers.
+pub fn format_stack_typed<W: fmt::Write>(
+    stream: &mut W,
+    stack: &[Value],
+    types: Option<&[SlotType]>,
+) -> fmt::Result {
+    format_stack_with(stream, stack, types, TraceFormat::Full)
+}
+
+/// Format the operand stack, printing values in the given format.
+///
+/// `types` is interpreted as for [`format_stack_typed`]. Boolean slots are printed as `true` or
+/// `false` in place of their decimal columns, so in [`TraceFormat::HexOnly`] they are printed in
+/// hexadecimal like any other value.
+pub fn format_stack_with<W: fmt::Write>(
+    stream: &mut W,
+    stack: &[Value],
+    types: Option<&[SlotType]>,
+    format: TraceFormat,
+) -> fmt::Result {
+    let types = types.unwrap_or_default();
+    for (i, v) in stack.iter().enumerate().rev() {
+        let depth = stack.len() - 1 - i;
+        let is_bool = types.get(i) == Some(&SlotType::Bool);
+        match format {
+            TraceFormat::Full if is_bool => {
+                writeln!(stream, " {depth:2}: {v:x}\t\t{}", v.as_u64() != 0)?
+            }
+            TraceFormat::DecimalOnly if is_bool => {
+                writeln!(stream, " {depth:2}: {}", v.as_u64() != 0)?
+            }
+            _ => format_value(stream, depth, *v, format)?,
+        }
     }
 
     Ok(())
 }
 
 pub fn format_vars<W: fmt::Write>(stream: &mut W, vars: &[Value]) -> fmt::Result {
+    format_vars_with(stream, vars, TraceFormat::Full)
+}
+
+/// Format the local variables, printing values in the given format.
+pub fn format_vars_with<W: fmt::Write>(
+    stream: &mut W,
+    vars: &[Value],
+    format: TraceFormat,
+) -> fmt::Result {
     for (i, v) in vars.iter().enumerate() {
-        writeln!(stream, " {i:2}: {v:x}\t\t{v}\t{v:-}")?;
+        format_value(stream, i, *v, format)?;
     }
 
     Ok(())
 }
 
+fn format_value<W: fmt::Write>(
+    stream: &mut W,
+    index: usize,
+    v: Value,
+    format: TraceFormat,
+) -> fmt::Result {
+    match format {
+        TraceFormat::Full => writeln!(stream, " {index:2}: {v:x}\t\t{v}\t{v:-}"),
+        TraceFormat::DecimalOnly => writeln!(stream, " {index:2}: {v}\t{v:-}"),
+        TraceFormat::HexOnly => writeln!(stream, " {index:2}: {v:x}"),
+    }
+}
+
 pub fn format_trace<W: fmt::Write>(stream: &mut W, trace: &[Snapshot]) -> fmt::Result {
+    format_trace_with(stream, trace, TraceFormat::Full)
+}
+
+/// Format a trace, printing stack and variable values in the given format.
+pub fn format_trace_with<W: fmt::Write>(
+    stream: &mut W,
+    trace: &[Snapshot],
+    format: TraceFormat,
+) -> fmt::Result {
     let mut first = true;
-    for (i, snapshot) in trace.iter().enumerate() {
+    for snapshot in trace {
         if !first {
             writeln!(stream)?;
         } else {
             first = false;
         }
 
-        write!(stream, "{i} ==\n{snapshot}")?;
+        writeln!(stream, "{} ==", snapshot.step_index)?;
+        snapshot.format_with(stream, format)?;
     }
 
     Ok(())
 }
+
+/// Format a trace, rendering stack slots known to be booleans as `true` or `false`, and other
+/// values in the given format.
+///
+/// The types of the stack slots at each step are looked up in `types` by program counter.
+pub fn format_trace_typed<W: fmt::Write>(
+    stream: &mut W,
+    trace: &[Snapshot],
+    types: &SlotTypeMap,
+    format: TraceFormat,
+) -> fmt::Result {
+    let mut first = true;
+    for snapshot in trace {
+        if !first {
+            writeln!(stream)?;
+        } else {
+            first = false;
+        }
+
+        writeln!(stream, "{} ==", snapshot.step_index)?;
+        snapshot.format(stream, types.get(snapshot.program_counter), format)?;
+    }
+
+    Ok(())
+}
+
+pub fn format_pc_trace<W: fmt::Write>(stream: &mut W, pc_trace: &[(usize, Opcode)]) -> fmt::Result {
+    for (i, (pc, opcode)) in pc_trace.iter().enumerate() {
+        writeln!(stream, "{i} == pc {pc}: {}", opcode.mnemonic())?;
+    }
+
+    Ok(())
+}
+
+/// Compute a digest of a trace, for detecting changes in execution behavior.
+///
+/// The digest covers the program counter, instruction, stack, and local variables of every
+/// snapshot, in order. It is computed with 64-bit FNV-1a over a fixed little-endian encoding, so it
+/// is stable across platforms and program runs, and may be stored in golden tests. It is not
+/// cryptographically secure.
+pub fn trace_digest(trace: &[Snapshot]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    for snapshot in trace {
+        hasher.write_u64(snapshot.program_counter as u64);
+        hasher.write(&[u8::from(snapshot.next_instruction.opcode)]);
+        hasher.write_u64(snapshot.next_instruction.literal);
+
+        for values in [&snapshot.stack, &snapshot.local_variables] {
+            hasher.write_u64(values.len() as u64);
+            for value in values {
+                hasher.write_u64(value.as_u64());
+            }
+        }
+    }
+
+    hasher.finish()
+}
+
+/// The 64-bit FNV-1a hash function.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn differences() {
+        let snapshot = Snapshot {
+            step_index: 3,
+            program_counter: 5,
+            next_instruction: Instruction::from(Opcode::Add),
+            stack: vec![Value::from_u64(1), Value::from_u64(2)],
+            local_variables: vec![Value::from_u64(7)],
+        };
+
+        assert_eq!(snapshot, snapshot.clone());
+        assert!(snapshot.differences(&snapshot.clone()).is_empty());
+
+        let other = Snapshot {
+            program_counter: 6,
+            stack: vec![Value::from_u64(3)],
+            ..snapshot.clone()
+        };
+        assert_ne!(snapshot, other);
+        assert_eq!(
+            snapshot.differences(&other),
+            [SnapshotField::ProgramCounter, SnapshotField::Stack]
+        );
+
+        let other = Snapshot {
+            step_index: 4,
+            next_instruction: Instruction::new(Opcode::Push8, 1),
+            local_variables: Vec::new(),
+            ..snapshot.clone()
+        };
+        assert_eq!(
+            other.differences(&snapshot),
+            [
+                SnapshotField::StepIndex,
+                SnapshotField::NextInstruction,
+                SnapshotField::LocalVariables
+            ]
+        );
+    }
+
+    #[test]
+    fn format_modes() {
+        let vars = [Value::from_i64(-2)];
+        let format = |mode| {
+            let mut out = String::new();
+            format_vars_with(&mut out, &vars, mode).unwrap();
+            out
+        };
+
+        assert_eq!(
+            format(TraceFormat::Full),
+            "  0: fffffffffffffffe\t\t18446744073709551614\t-2\n"
+        );
+        assert_eq!(
+            format(TraceFormat::DecimalOnly),
+            "  0: 18446744073709551614\t-2\n"
+        );
+        assert_eq!(format(TraceFormat::HexOnly), "  0: fffffffffffffffe\n");
+
+        let mut full = String::new();
+        format_vars(&mut full, &vars).unwrap();
+        assert_eq!(full, format(TraceFormat::Full));
+    }
+
+    #[test]
+    fn format_modes_typed() {
+        let stack = [Value::from_u64(1), Value::from_u64(10)];
+        let types = [SlotType::Bool, SlotType::Int];
+        let format = |mode| {
+            let mut out = String::new();
+            format_stack_with(&mut out, &stack, Some(&types), mode).unwrap();
+            out
+        };
+
+        assert_eq!(
+            format(TraceFormat::Full),
+            "  0: 000000000000000a\t\t10\t10\n  1: 0000000000000001\t\ttrue\n"
+        );
+        assert_eq!(format(TraceFormat::DecimalOnly), "  0: 10\t10\n  1: true\n");
+        assert_eq!(
+            format(TraceFormat::HexOnly),
+            "  0: 000000000000000a\n  1: 0000000000000001\n"
+        );
+    }
+
+    #[test]
+    fn snapshot_format_with() {
+        let snapshot = Snapshot {
+            step_index: 0,
+            program_counter: 2,
+            next_instruction: Instruction::from(Opcode::Dup0),
+            stack: vec![Value::from_u64(255)],
+            local_variables: vec![Value::from_u64(3)],
+        };
+
+        let mut full = String::new();
+        snapshot.format_with(&mut full, TraceFormat::Full).unwrap();
+        assert_eq!(full, snapshot.to_string());
+
+        let mut hex = String::new();
+        snapshot
+            .format_with(&mut hex, TraceFormat::HexOnly)
+            .unwrap();
+        assert_eq!(
+            hex,
+            "pc 2\ndup0\nstack\n  0: 00000000000000ff\nvars\n  0: 0000000000000003\n"
+        );
+    }
+}
@@ -1,18 +1,46 @@
 //! Stack and variable values.
 
-use std::fmt::{self, Display, Formatter, LowerHex};
+use std::fmt::{self, Debug, Display, Formatter, LowerHex};
 
 use crate::error::*;
 
 use hypescript_util::array_from_slice;
 
+/// Error returned by fallible [`Value`] operations.
+///
+/// These errors carry no information about where in a program they occurred; the VM converts them
+/// into a full [`Error`] when they arise during execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ValueError {
+    #[error("divide by zero")]
+    DivideByZero,
+
+    #[error("bit index out of range")]
+    BitIndexOutOfRange,
+}
+
+/// Error returned by [`Value::try_from_slice`] and [`Value::try_from_slice_signed`] when the given
+/// slice is not of length 1, 2, 4, or 8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("invalid value length")]
+pub struct InvalidLengthError;
+
 /// A value in a stack or variable slot.
 ///
 /// This wraps a `u64`, and provides utility methods for manipulating and retrieving its value as
 /// various types.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+///
+/// The `Debug` representation shows the value in both hexadecimal and unsigned decimal, e.g.
+/// `Value(0xff = 255)`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct Value(u64);
 
+impl Debug for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Value({:#x} = {})", self.0, self.0)
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         if f.sign_minus() {
@@ -72,6 +100,38 @@ impl Value {
         self.0.to_be_bytes()
     }
 
+    /// Get an array of this value's bytes, in little-endian order.
+    ///
+    /// This is intended for interoperating with external little-endian data formats. Bytecode
+    /// literals are always big-endian; see [`Value::as_bytes`].
+    pub fn as_bytes_le(&self) -> [u8; 8] {
+        self.0.to_le_bytes()
+    }
+
+    /// Get the byte at index `i` of this value, in big-endian order.
+    ///
+    /// Index 0 is the most significant byte, and index 7 the least significant.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `i` is greater than 7.
+    pub fn byte(self, i: usize) -> u8 {
+        self.as_bytes()[i]
+    }
+
+    /// Create a copy of this value with the byte at index `i` replaced by `b`.
+    ///
+    /// Bytes are indexed in big-endian order, as in [`Value::byte`].
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `i` is greater than 7.
+    pub fn with_byte(self, i: usize, b: u8) -> Self {
+        let mut bytes = self.as_bytes();
+        bytes[i] = b;
+        Self::from_u64(u64::from_be_bytes(bytes))
+    }
+
     from_method! {
         (from_u8 u8)
         (from_i8 i8)
@@ -83,6 +143,22 @@ impl Value {
         (from_i64 i64)
     }
 
+    /// Create a `Value` from the IEEE-754 bit pattern of an `f64`.
+    ///
+    /// The bits are stored unchanged, so NaN payloads and signed zeros are preserved. Only the
+    /// `_float` methods interpret the bits as a float; all other operations treat them as an
+    /// integer.
+    pub fn from_f64(val: f64) -> Self {
+        Self(val.to_bits())
+    }
+
+    /// Get this value as an `f64`, interpreting its bits as an IEEE-754 double.
+    ///
+    /// This is the inverse of [`Value::from_f64`].
+    pub fn as_f64(&self) -> f64 {
+        f64::from_bits(self.0)
+    }
+
     /// Create a `Value` from a byte slice.
     ///
     /// This will interpret the bytes of the given slice as an unsigned integer in big-endian byte
@@ -90,14 +166,24 @@ impl Value {
     ///
     /// # Panics
     ///
-    /// This function will panic if the given slice is not of length 1, 2, 4, or 8.
+    /// This function will panic if the given slice is not of length 1, 2, 4, or 8. See
+    /// [`Value::try_from_slice`] for a non-panicking version.
     pub fn from_slice(val: &[u8]) -> Self {
+        Self::try_from_slice(val).unwrap_or_else(|_| panic!("invalid value length"))
+    }
+
+    /// Create a `Value` from a byte slice, checking the slice length.
+    ///
+    /// This is identical to [`Value::from_slice`], except that it returns
+    /// [`InvalidLengthError`] rather than panicking if the given slice is not of length 1,
+    /// 2, 4, or 8.
+    pub fn try_from_slice(val: &[u8]) -> Result<Self, InvalidLengthError> {
         match val.len() {
-            1 => Self::from_u8(val[0]),
-            2 => Self::from_u16(u16::from_be_bytes(array_from_slice(val))),
-            4 => Self::from_u32(u32::from_be_bytes(array_from_slice(val))),
-            8 => Self::from_u64(u64::from_be_bytes(array_from_slice(val))),
-            _ => panic!("invalid value length"),
+            1 => Ok(Self::from_u8(val[0])),
+            2 => Ok(Self::from_u16(u16::from_be_bytes(array_from_slice(val)))),
+            4 => Ok(Self::from_u32(u32::from_be_bytes(array_from_slice(val)))),
+            8 => Ok(Self::from_u64(u64::from_be_bytes(array_from_slice(val)))),
+            _ => Err(InvalidLengthError),
         }
     }
 
@@ -108,13 +194,62 @@ impl Value {
     ///
     /// # Panics
     ///
-    /// This function will panic if the given slice is not of length 1, 2, 4, or 8.
+    /// This function will panic if the given slice is not of length 1, 2, 4, or 8. See
+    /// [`Value::try_from_slice_signed`] for a non-panicking version.
     pub fn from_slice_signed(val: &[u8]) -> Self {
+        Self::try_from_slice_signed(val).unwrap_or_else(|_| panic!("invalid value length"))
+    }
+
+    /// Create a `Value` from a byte slice, performing sign extension and checking the slice
+    /// length.
+    ///
+    /// This is identical to [`Value::from_slice_signed`], except that it returns
+    /// [`InvalidLengthError`] rather than panicking if the given slice is not of length 1,
+    /// 2, 4, or 8.
+    pub fn try_from_slice_signed(val: &[u8]) -> Result<Self, InvalidLengthError> {
+        match val.len() {
+            1 => Ok(Self::from_i8(val[0] as i8)),
+            2 => Ok(Self::from_i16(i16::from_be_bytes(array_from_slice(val)))),
+            4 => Ok(Self::from_i32(i32::from_be_bytes(array_from_slice(val)))),
+            8 => Ok(Self::from_i64(i64::from_be_bytes(array_from_slice(val)))),
+            _ => Err(InvalidLengthError),
+        }
+    }
+
+    /// Create a `Value` from a byte slice in little-endian byte order.
+    ///
+    /// This is identical to [`Value::from_slice`], except that the bytes are interpreted in
+    /// little-endian order. It is intended for interoperating with external little-endian data
+    /// formats; bytecode literals are always big-endian.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the given slice is not of length 1, 2, 4, or 8.
+    pub fn from_slice_le(val: &[u8]) -> Self {
+        match val.len() {
+            1 => Self::from_u8(val[0]),
+            2 => Self::from_u16(u16::from_le_bytes(array_from_slice(val))),
+            4 => Self::from_u32(u32::from_le_bytes(array_from_slice(val))),
+            8 => Self::from_u64(u64::from_le_bytes(array_from_slice(val))),
+            _ => panic!("invalid value length"),
+        }
+    }
+
+    /// Create a `Value` from a byte slice in little-endian byte order, performing sign extension.
+    ///
+    /// This is identical to [`Value::from_slice_signed`], except that the bytes are interpreted in
+    /// little-endian order. It is intended for interoperating with external little-endian data
+    /// formats; bytecode literals are always big-endian.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the given slice is not of length 1, 2, 4, or 8.
+    pub fn from_slice_signed_le(val: &[u8]) -> Self {
         match val.len() {
             1 => Self::from_i8(val[0] as i8),
-            2 => Self::from_i16(i16::from_be_bytes(array_from_slice(val))),
-            4 => Self::from_i32(i32::from_be_bytes(array_from_slice(val))),
-            8 => Self::from_i64(i64::from_be_bytes(array_from_slice(val))),
+            2 => Self::from_i16(i16::from_le_bytes(array_from_slice(val))),
+            4 => Self::from_i32(i32::from_le_bytes(array_from_slice(val))),
+            8 => Self::from_i64(i64::from_le_bytes(array_from_slice(val))),
             _ => panic!("invalid value length"),
         }
     }
@@ -129,6 +264,20 @@ impl Value {
         Self::from_u64(self.as_u64().wrapping_sub(rhs.as_u64()))
     }
 
+    /// Add two values as unsigned integers, returning the wrapped sum and whether a carry out
+    /// occurred.
+    pub fn carrying_add(self, rhs: Self) -> (Self, bool) {
+        let (sum, carry) = self.as_u64().overflowing_add(rhs.as_u64());
+        (Self::from_u64(sum), carry)
+    }
+
+    /// Subtract two values as unsigned integers, returning the wrapped difference and whether a
+    /// borrow occurred.
+    pub fn borrowing_sub(self, rhs: Self) -> (Self, bool) {
+        let (diff, borrow) = self.as_u64().overflowing_sub(rhs.as_u64());
+        (Self::from_u64(diff), borrow)
+    }
+
     /// Multiply two values as integers, wrapping on overflow.
     pub fn mul(self, rhs: Self) -> Self {
         Self::from_u64(self.as_u64().wrapping_mul(rhs.as_u64()))
@@ -138,13 +287,12 @@ impl Value {
     ///
     /// # Errors
     ///
-    /// If `rhs` is zero, this function will return an error with kind [`ErrorKind::DivideByZero`],
-    /// and program counter set to zero.
-    pub fn div_unsigned(self, rhs: Self) -> Result<Self> {
+    /// If `rhs` is zero, this function will return [`ValueError::DivideByZero`].
+    pub fn div_unsigned(self, rhs: Self) -> Result<Self, ValueError> {
         Ok(Self::from_u64(
             self.as_u64()
                 .checked_div(rhs.as_u64())
-                .ok_or(Error::from(ErrorKind::DivideByZero))?,
+                .ok_or(ValueError::DivideByZero)?,
         ))
     }
 
@@ -152,13 +300,12 @@ impl Value {
     ///
     /// # Errors
     ///
-    /// If `rhs` is zero, this function will return an error with kind [`ErrorKind::DivideByZero`],
-    /// and program counter set to zero.
-    pub fn div_signed(self, rhs: Self) -> Result<Self> {
+    /// If `rhs` is zero, this function will return [`ValueError::DivideByZero`].
+    pub fn div_signed(self, rhs: Self) -> Result<Self, ValueError> {
         Ok(Self::from_i64(
             self.as_i64()
                 .checked_div(rhs.as_i64())
-                .ok_or(Error::from(ErrorKind::DivideByZero))?,
+                .ok_or(ValueError::DivideByZero)?,
         ))
     }
 
@@ -166,16 +313,81 @@ impl Value {
     ///
     /// # Errors
     ///
-    /// If `rhs` is zero, this function will return an error with kind [`ErrorKind::DivideByZero`],
-    /// and program counter set to zero.
-    pub fn mod_(self, rhs: Self) -> Result<Self> {
+    /// If `rhs` is zero, this function will return [`ValueError::DivideByZero`].
+    pub fn mod_(self, rhs: Self) -> Result<Self, ValueError> {
         Ok(Self::from_u64(
             self.as_u64()
                 .checked_rem(rhs.as_u64())
-                .ok_or(Error::from(ErrorKind::DivideByZero))?,
+                .ok_or(ValueError::DivideByZero)?,
         ))
     }
 
+    /// Raise `self` to the power of `rhs`, as unsigned integers, wrapping on overflow.
+    ///
+    /// The result is the exact power reduced modulo 2<sup>64</sup>. Any value raised to the power
+    /// of zero is 1, including zero.
+    pub fn pow(self, rhs: Self) -> Self {
+        let (base, exp) = (self.as_u64(), rhs.as_u64());
+
+        // base^exp = (base^(2^32))^(exp >> 32) * base^(exp & 0xffffffff)
+        let base_2_32 = (0..32).fold(base, |b, _| b.wrapping_mul(b));
+        let high = base_2_32.wrapping_pow((exp >> 32) as u32);
+        let low = base.wrapping_pow(exp as u32);
+        Self::from_u64(high.wrapping_mul(low))
+    }
+
+    /// Raise `self` to the power of `exp`, modulo `modulus`, as unsigned integers.
+    ///
+    /// The result is computed exactly, without intermediate overflow, and is always less than
+    /// `modulus`. Any value raised to the power of zero is 1 (or 0, if `modulus` is 1).
+    ///
+    /// # Errors
+    ///
+    /// If `modulus` is zero, this function will return [`ValueError::DivideByZero`].
+    pub fn modpow(self, exp: Self, modulus: Self) -> Result<Self, ValueError> {
+        let modulus = u128::from(modulus.as_u64());
+        if modulus == 0 {
+            return Err(ValueError::DivideByZero);
+        }
+
+        // Square-and-multiply, consuming the exponent from its least significant bit
+        let mut base = u128::from(self.as_u64()) % modulus;
+        let mut exp = exp.as_u64();
+        let mut result = 1 % modulus;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result * base % modulus;
+            }
+            base = base * base % modulus;
+            exp >>= 1;
+        }
+
+        Ok(Self::from_u64(result as u64))
+    }
+
+    /// Add two values as IEEE-754 double precision floats.
+    pub fn add_float(self, rhs: Self) -> Self {
+        Self::from_f64(self.as_f64() + rhs.as_f64())
+    }
+
+    /// Subtract two values as IEEE-754 double precision floats.
+    pub fn sub_float(self, rhs: Self) -> Self {
+        Self::from_f64(self.as_f64() - rhs.as_f64())
+    }
+
+    /// Multiply two values as IEEE-754 double precision floats.
+    pub fn mul_float(self, rhs: Self) -> Self {
+        Self::from_f64(self.as_f64() * rhs.as_f64())
+    }
+
+    /// Divide two values as IEEE-754 double precision floats.
+    ///
+    /// Unlike integer division, this never fails; dividing by zero yields an infinity or NaN, as
+    /// specified by IEEE-754.
+    pub fn div_float(self, rhs: Self) -> Self {
+        Self::from_f64(self.as_f64() / rhs.as_f64())
+    }
+
     /// Check if `self` is greater than `rhs`, as unsigned integers.
     ///
     /// Returns a value of 1 for true, and 0 for false.
@@ -239,6 +451,35 @@ impl Value {
         Self::from_u64((self.0 == rhs.0) as u64)
     }
 
+    /// Check if `self` is not equal to `rhs`.
+    ///
+    /// Returns a value of 1 for true, and 0 for false.
+    pub fn ne(self, rhs: Self) -> Self {
+        Self::from_u64((self.0 != rhs.0) as u64)
+    }
+
+    /// Check if `self` is greater than `rhs`, as IEEE-754 double precision floats.
+    ///
+    /// Returns a value of 1 for true, and 0 for false. Any comparison involving NaN is false.
+    pub fn greater_float(self, rhs: Self) -> Self {
+        Self::from_u64((self.as_f64() > rhs.as_f64()) as u64)
+    }
+
+    /// Check if `self` is less than `rhs`, as IEEE-754 double precision floats.
+    ///
+    /// Returns a value of 1 for true, and 0 for false. Any comparison involving NaN is false.
+    pub fn less_float(self, rhs: Self) -> Self {
+        Self::from_u64((self.as_f64() < rhs.as_f64()) as u64)
+    }
+
+    /// Check if `self` is equal to `rhs`, as IEEE-754 double precision floats.
+    ///
+    /// Returns a value of 1 for true, and 0 for false. Any comparison involving NaN is false, and
+    /// positive and negative zero compare equal.
+    pub fn eq_float(self, rhs: Self) -> Self {
+        Self::from_u64((self.as_f64() == rhs.as_f64()) as u64)
+    }
+
     /// Compute the bitwise AND of two values.
     pub fn and(self, rhs: Self) -> Self {
         Self(self.0 & rhs.0)
@@ -254,6 +495,42 @@ impl Value {
         Self(self.0 ^ rhs.0)
     }
 
+    /// Test a single bit of a value.
+    ///
+    /// Bits are indexed from the least significant, starting at 0. Returns a value of 1 if the bit
+    /// of `self` at index `rhs` is set, and 0 otherwise.
+    ///
+    /// # Errors
+    ///
+    /// If `rhs` is 64 or greater, this function will return [`ValueError::BitIndexOutOfRange`].
+    pub fn bit_test(self, rhs: Self) -> Result<Self, ValueError> {
+        Ok(Self::from_u64(((self.0 & bit_mask(rhs)?) != 0) as u64))
+    }
+
+    /// Set a single bit of a value.
+    ///
+    /// Returns `self` with the bit at index `rhs` set to 1. Bits are indexed as for
+    /// [`Value::bit_test`].
+    ///
+    /// # Errors
+    ///
+    /// If `rhs` is 64 or greater, this function will return [`ValueError::BitIndexOutOfRange`].
+    pub fn bit_set(self, rhs: Self) -> Result<Self, ValueError> {
+        Ok(Self(self.0 | bit_mask(rhs)?))
+    }
+
+    /// Clear a single bit of a value.
+    ///
+    /// Returns `self` with the bit at index `rhs` set to 0. Bits are indexed as for
+    /// [`Value::bit_test`].
+    ///
+    /// # Errors
+    ///
+    /// If `rhs` is 64 or greater, this function will return [`ValueError::BitIndexOutOfRange`].
+    pub fn bit_clear(self, rhs: Self) -> Result<Self, ValueError> {
+        Ok(Self(self.0 & !bit_mask(rhs)?))
+    }
+
     /// Get the logical negation of a value.
     ///
     /// Returns a value of 1 if `self` is 0, and a value of 0 otherwise.
@@ -265,6 +542,39 @@ impl Value {
     pub fn inv(self) -> Self {
         Self::from_u64(!self.as_u64())
     }
+
+    /// Get the canonical boolean form of a value.
+    ///
+    /// Returns a value of 1 if `self` is nonzero, and a value of 0 otherwise.
+    pub fn to_bool(self) -> Self {
+        Self::from_u64((self.0 != 0) as u64)
+    }
+
+    /// Compute the logical AND of two values.
+    ///
+    /// Both operands are regarded as booleans, with any nonzero value being true. Returns a value
+    /// of 1 for true, and 0 for false. Note that this differs from [`Value::and`] for operands
+    /// that are not canonical booleans; e.g. the logical AND of 2 and 1 is 1, while their bitwise
+    /// AND is 0.
+    pub fn logical_and(self, rhs: Self) -> Self {
+        self.to_bool().and(rhs.to_bool())
+    }
+
+    /// Compute the logical OR of two values.
+    ///
+    /// Both operands are regarded as booleans, with any nonzero value being true. Returns a value
+    /// of 1 for true, and 0 for false.
+    pub fn logical_or(self, rhs: Self) -> Self {
+        self.to_bool().or(rhs.to_bool())
+    }
+}
+
+/// Get a mask with only the bit at the given index set.
+fn bit_mask(index: Value) -> Result<u64, ValueError> {
+    u32::try_from(index.as_u64())
+        .ok()
+        .and_then(|index| 1u64.checked_shl(index))
+        .ok_or(ValueError::BitIndexOutOfRange)
 }
 
 #[cfg(test)]
@@ -307,6 +617,57 @@ mod test {
         );
     }
 
+    #[test]
+    fn f64_bits() {
+        assert_eq!(Value::from_f64(3.125).as_f64(), 3.125);
+        assert_eq!(Value::from_f64(-0.0).as_u64(), 0x8000000000000000);
+        assert_eq!(Value::from_f64(1.0).as_u64(), 0x3ff0000000000000);
+        assert_eq!(Value::from_u64(0x4000000000000000).as_f64(), 2.0);
+
+        assert!(Value::from_f64(f64::NAN).as_f64().is_nan());
+        let payload_nan = f64::from_bits(0x7ff8_0000_dead_beef);
+        assert_eq!(
+            Value::from_f64(payload_nan).as_f64().to_bits(),
+            0x7ff8_0000_dead_beef
+        );
+    }
+
+    #[test]
+    fn float_arithmetic() {
+        let f = Value::from_f64;
+
+        assert_eq!(f(1.5).add_float(f(2.5)).as_f64(), 4.0);
+        assert_eq!(f(1.5).sub_float(f(2.5)).as_f64(), -1.0);
+        assert_eq!(f(1.5).mul_float(f(2.5)).as_f64(), 3.75);
+        assert_eq!(f(7.5).div_float(f(2.5)).as_f64(), 3.0);
+        assert_eq!(f(1.0).div_float(f(0.0)).as_f64(), f64::INFINITY);
+        assert_eq!(f(-1.0).div_float(f(0.0)).as_f64(), f64::NEG_INFINITY);
+        assert!(f(0.0).div_float(f(0.0)).as_f64().is_nan());
+    }
+
+    #[test]
+    fn float_comparisons() {
+        let f = Value::from_f64;
+        let t = Value::from_u64(1);
+        let z = Value::from_u64(0);
+
+        assert_eq!(f(1.5).add_float(f(2.5)).eq_float(f(4.0)), t);
+        assert_eq!(f(-1.0).greater_float(f(-2.0)), t);
+        assert_eq!(f(-1.0).less_float(f(-2.0)), z);
+        assert_eq!(f(0.0).eq_float(f(-0.0)), t);
+        assert_eq!(f(f64::INFINITY).greater_float(f(f64::MAX)), t);
+
+        let nan = f(f64::NAN);
+        for other in [nan, f(0.0), f(f64::INFINITY)] {
+            assert_eq!(nan.greater_float(other), z);
+            assert_eq!(nan.less_float(other), z);
+            assert_eq!(nan.eq_float(other), z);
+            assert_eq!(other.greater_float(nan), z);
+            assert_eq!(other.less_float(nan), z);
+            assert_eq!(other.eq_float(nan), z);
+        }
+    }
+
     #[test]
     fn bytes_conversions() {
         assert_eq!(
@@ -325,6 +686,73 @@ mod test {
         );
     }
 
+    #[test]
+    fn checked_bytes_conversions() {
+        assert_eq!(Value::try_from_slice(&[125]), Ok(Value::from_u8(125)));
+        assert_eq!(
+            Value::try_from_slice(&[0xff, 0xfe]),
+            Ok(Value::from_u16(0xfffe))
+        );
+        assert_eq!(
+            Value::try_from_slice(&[0xde, 0xad, 0xbe, 0xef]),
+            Ok(Value::from_u32(0xdeadbeef))
+        );
+        assert_eq!(
+            Value::try_from_slice(&[0x12, 0x34, 0x56, 0x78, 0x90, 0xab, 0xcd, 0xef]),
+            Ok(Value::from_u64(0x1234567890abcdef))
+        );
+
+        assert_eq!(
+            Value::try_from_slice_signed(&[0xff]),
+            Ok(Value::from_i8(-1))
+        );
+        assert_eq!(
+            Value::try_from_slice_signed(&[0xff, 0xfe]),
+            Ok(Value::from_i16(-2))
+        );
+        assert_eq!(
+            Value::try_from_slice_signed(&[0xff, 0xff, 0xff, 0xfd]),
+            Ok(Value::from_i32(-3))
+        );
+        assert_eq!(
+            Value::try_from_slice_signed(&[0x80, 0, 0, 0, 0, 0, 0, 0]),
+            Ok(Value::from_i64(i64::MIN))
+        );
+
+        for len in [0, 3, 5, 9] {
+            let bytes = vec![0xaa; len];
+            assert_eq!(Value::try_from_slice(&bytes), Err(InvalidLengthError));
+            assert_eq!(
+                Value::try_from_slice_signed(&bytes),
+                Err(InvalidLengthError)
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid value length")]
+    fn bytes_conversion_invalid_length() {
+        Value::from_slice(&[1, 2, 3]);
+    }
+
+    #[test]
+    fn byte_access() {
+        let val = Value::from_u64(0x1234567890abcdef);
+        assert_eq!(val.byte(0), 0x12);
+        assert_eq!(val.byte(3), 0x78);
+        assert_eq!(val.byte(7), 0xef);
+
+        assert_eq!(val.with_byte(0, 0xff).as_u64(), 0xff34567890abcdef);
+        assert_eq!(val.with_byte(5, 0x00).as_u64(), 0x123456789000cdef);
+        assert_eq!(val.with_byte(7, 0x01).as_u64(), 0x1234567890abcd01);
+    }
+
+    #[test]
+    #[should_panic]
+    fn byte_access_out_of_range() {
+        Value::from_u64(0).byte(8);
+    }
+
     #[test]
     fn addition() {
         assert_eq!(
@@ -357,6 +785,36 @@ mod test {
         );
     }
 
+    #[test]
+    fn carrying_add() {
+        let add = |a: u64, b: u64| {
+            let (sum, carry) = Value::from_u64(a).carrying_add(Value::from_u64(b));
+            (sum.as_u64(), carry)
+        };
+
+        assert_eq!(add(4, 6), (10, false));
+        assert_eq!(add(u64::MAX - 1, 1), (u64::MAX, false));
+        assert_eq!(add(u64::MAX, 0), (u64::MAX, false));
+        assert_eq!(add(u64::MAX, 1), (0, true));
+        assert_eq!(add(u64::MAX, u64::MAX), (u64::MAX - 1, true));
+        assert_eq!(add(1 << 63, 1 << 63), (0, true));
+    }
+
+    #[test]
+    fn borrowing_sub() {
+        let sub = |a: u64, b: u64| {
+            let (diff, borrow) = Value::from_u64(a).borrowing_sub(Value::from_u64(b));
+            (diff.as_u64(), borrow)
+        };
+
+        assert_eq!(sub(10, 4), (6, false));
+        assert_eq!(sub(5, 5), (0, false));
+        assert_eq!(sub(u64::MAX, u64::MAX), (0, false));
+        assert_eq!(sub(0, 1), (u64::MAX, true));
+        assert_eq!(sub(4, 10), (u64::MAX - 5, true));
+        assert_eq!(sub(0, u64::MAX), (1, true));
+    }
+
     #[test]
     fn multiplication() {
         assert_eq!(
@@ -391,9 +849,8 @@ mod test {
         assert_eq!(
             Value::from_u64(1526)
                 .div_unsigned(Value::from_u64(0))
-                .unwrap_err()
-                .kind,
-            ErrorKind::DivideByZero,
+                .unwrap_err(),
+            ValueError::DivideByZero,
         );
     }
 
@@ -413,9 +870,8 @@ mod test {
         assert_eq!(
             Value::from_i64(-162456)
                 .div_signed(Value::from_i64(0))
-                .unwrap_err()
-                .kind,
-            ErrorKind::DivideByZero
+                .unwrap_err(),
+            ValueError::DivideByZero
         );
     }
 
@@ -431,11 +887,273 @@ mod test {
         );
 
         assert_eq!(
-            Value::from_u64(1234)
-                .mod_(Value::from_u64(0))
-                .unwrap_err()
-                .kind,
-            ErrorKind::DivideByZero
+            Value::from_u64(1234).mod_(Value::from_u64(0)).unwrap_err(),
+            ValueError::DivideByZero
+        );
+    }
+
+    #[test]
+    fn logical_connectives() {
+        // Logical operators normalize their operands, unlike the bitwise operators
+        assert_eq!(
+            Value::from_u64(2).logical_and(Value::from_u64(1)),
+            Value::from_u64(1)
+        );
+        assert_eq!(
+            Value::from_u64(2).and(Value::from_u64(1)),
+            Value::from_u64(0)
+        );
+
+        assert_eq!(
+            Value::from_u64(0).logical_and(Value::from_i64(-1)),
+            Value::from_u64(0)
+        );
+        assert_eq!(
+            Value::from_u64(4).logical_or(Value::from_u64(8)),
+            Value::from_u64(1)
+        );
+        assert_eq!(
+            Value::from_u64(0).logical_or(Value::from_u64(0)),
+            Value::from_u64(0)
+        );
+    }
+
+    #[test]
+    fn pow() {
+        let pow = |a: u64, b: u64| Value::from_u64(a).pow(Value::from_u64(b)).as_u64();
+
+        assert_eq!(pow(2, 10), 1024);
+        assert_eq!(pow(0, 0), 1);
+        assert_eq!(pow(0, 5), 0);
+        assert_eq!(pow(7, 1), 7);
+        assert_eq!(pow(2, 63), 1 << 63);
+        assert_eq!(pow(2, 64), 0);
+        assert_eq!(pow(3, 41), 3_u64.wrapping_pow(41));
+        assert_eq!(pow(u64::MAX, 3), u64::MAX);
+
+        // Exponents beyond the range of u32
+        assert_eq!(pow(1, u64::MAX), 1);
+        assert_eq!(pow(2, 1 << 32), 0);
+        assert_eq!(pow(u64::MAX, u64::MAX), u64::MAX);
+        assert_eq!(pow(u64::MAX, 1 << 40), 1);
+        assert_eq!(
+            pow(3, (1 << 32) + 5),
+            3_u64
+                .wrapping_pow(1 << 31)
+                .wrapping_pow(2)
+                .wrapping_mul(243)
+        );
+    }
+
+    #[test]
+    fn modpow() {
+        let modpow = |a: u64, b: u64, m: u64| {
+            Value::from_u64(a)
+                .modpow(Value::from_u64(b), Value::from_u64(m))
+                .map(|v| v.as_u64())
+        };
+
+        assert_eq!(modpow(4, 13, 497), Ok(445));
+        assert_eq!(modpow(2, 10, 1000), Ok(24));
+        assert_eq!(modpow(0, 0, 7), Ok(1));
+        assert_eq!(modpow(0, 0, 1), Ok(0));
+        assert_eq!(modpow(12345, 678, 1), Ok(0));
+
+        // Large exponents and moduli, whose intermediate products overflow 64 bits
+        assert_eq!(
+            modpow(123456789, 987654321987654321, u64::MAX),
+            Ok(8008563388178576484)
+        );
+        assert_eq!(
+            modpow(u64::MAX, u64::MAX, (1 << 63) + 5),
+            Ok(3322427856246731656)
+        );
+
+        // Fermat's little theorem, with the largest 64-bit prime
+        let p = 18446744073709551557;
+        assert_eq!(modpow(5, p - 1, p), Ok(1));
+        assert_eq!(modpow(u64::MAX, p - 1, p), Ok(1));
+
+        assert_eq!(modpow(3, 4, 0), Err(ValueError::DivideByZero));
+        assert_eq!(modpow(0, 0, 0), Err(ValueError::DivideByZero));
+    }
+
+    #[test]
+    fn little_endian() {
+        let bytes = [0x12, 0x34];
+        assert_eq!(Value::from_slice(&bytes).as_u64(), 0x1234);
+        assert_eq!(Value::from_slice_le(&bytes).as_u64(), 0x3412);
+
+        let bytes = [0x01, 0x00, 0x00, 0x80];
+        assert_eq!(Value::from_slice_signed(&bytes).as_i64(), 0x01000080);
+        assert_eq!(
+            Value::from_slice_signed_le(&bytes).as_i64(),
+            i32::MIN as i64 + 1
+        );
+
+        // Single bytes have no byte order
+        assert_eq!(Value::from_slice_le(&[0xf0]), Value::from_slice(&[0xf0]));
+        assert_eq!(
+            Value::from_slice_signed_le(&[0xf0]),
+            Value::from_slice_signed(&[0xf0])
+        );
+
+        let val = Value::from_u64(0x0102030405060708);
+        assert_eq!(val.as_bytes(), [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(val.as_bytes_le(), [8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(Value::from_slice_le(&val.as_bytes_le()), val);
+    }
+
+    /// Operand pairs for checking operations against reference implementations: every pair of a
+    /// set of edge-case values, followed by pseudo-random pairs from a fixed-seed xorshift
+    /// generator.
+    fn operand_pairs() -> Vec<(u64, u64)> {
+        let edges = [
+            0,
+            1,
+            2,
+            0x7f,
+            0x80,
+            0xff,
+            i64::MAX as u64,
+            i64::MIN as u64,
+            u64::MAX - 1,
+            u64::MAX,
+        ];
+
+        let mut pairs: Vec<_> = edges
+            .iter()
+            .flat_map(|&a| edges.iter().map(move |&b| (a, b)))
+            .collect();
+
+        let mut state = 0x2545f4914f6cdd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        pairs.extend((0..1000).map(|_| (next(), next())));
+
+        pairs
+    }
+
+    #[test]
+    fn reference_arithmetic() {
+        for (a, b) in operand_pairs() {
+            let (va, vb) = (Value::from_u64(a), Value::from_u64(b));
+            let msg = format!("operands {a:#x}, {b:#x}");
+
+            assert_eq!(va.add(vb).as_u64(), a.wrapping_add(b), "{msg}");
+            assert_eq!(va.sub(vb).as_u64(), a.wrapping_sub(b), "{msg}");
+            assert_eq!(va.mul(vb).as_u64(), a.wrapping_mul(b), "{msg}");
+
+            match (a.checked_div(b), a.checked_rem(b)) {
+                (Some(quot), Some(rem)) => {
+                    assert_eq!(va.div_unsigned(vb).unwrap().as_u64(), quot, "{msg}");
+                    assert_eq!(va.mod_(vb).unwrap().as_u64(), rem, "{msg}");
+                }
+                _ => {
+                    let err = va.div_unsigned(vb).unwrap_err();
+                    assert_eq!(err, ValueError::DivideByZero, "{msg}");
+                    let err = va.mod_(vb).unwrap_err();
+                    assert_eq!(err, ValueError::DivideByZero, "{msg}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reference_comparisons() {
+        for (a, b) in operand_pairs() {
+            let (va, vb) = (Value::from_u64(a), Value::from_u64(b));
+            let (sa, sb) = (a as i64, b as i64);
+            let msg = format!("operands {a:#x}, {b:#x}");
+
+            assert_eq!(va.greater_unsigned(vb).as_u64(), (a > b) as u64, "{msg}");
+            assert_eq!(va.less_unsigned(vb).as_u64(), (a < b) as u64, "{msg}");
+            assert_eq!(
+                va.greater_or_eq_unsigned(vb).as_u64(),
+                (a >= b) as u64,
+                "{msg}"
+            );
+            assert_eq!(
+                va.less_or_eq_unsigned(vb).as_u64(),
+                (a <= b) as u64,
+                "{msg}"
+            );
+            assert_eq!(va.greater_signed(vb).as_u64(), (sa > sb) as u64, "{msg}");
+            assert_eq!(va.less_signed(vb).as_u64(), (sa < sb) as u64, "{msg}");
+            assert_eq!(
+                va.greater_or_eq_signed(vb).as_u64(),
+                (sa >= sb) as u64,
+                "{msg}"
+            );
+            assert_eq!(
+                va.less_or_eq_signed(vb).as_u64(),
+                (sa <= sb) as u64,
+                "{msg}"
+            );
+            assert_eq!(va.eq(vb).as_u64(), (a == b) as u64, "{msg}");
+            assert_eq!(va.ne(vb).as_u64(), (a != b) as u64, "{msg}");
+        }
+    }
+
+    #[test]
+    fn reference_bitwise() {
+        for (a, b) in operand_pairs() {
+            let (va, vb) = (Value::from_u64(a), Value::from_u64(b));
+            let msg = format!("operands {a:#x}, {b:#x}");
+
+            assert_eq!(va.and(vb).as_u64(), a & b, "{msg}");
+            assert_eq!(va.or(vb).as_u64(), a | b, "{msg}");
+            assert_eq!(va.xor(vb).as_u64(), a ^ b, "{msg}");
+            assert_eq!(va.inv().as_u64(), !a, "{msg}");
+            assert_eq!(va.not().as_u64(), (a == 0) as u64, "{msg}");
+        }
+    }
+
+    #[test]
+    fn bit_manipulation() {
+        let v = Value::from_u64(0b1010);
+        let bit = |n: u64| Value::from_u64(n);
+
+        assert_eq!(v.bit_test(bit(1)), Ok(Value::from_u64(1)));
+        assert_eq!(v.bit_test(bit(2)), Ok(Value::from_u64(0)));
+        assert_eq!(
+            Value::from_i64(-1).bit_test(bit(63)),
+            Ok(Value::from_u64(1))
+        );
+
+        assert_eq!(v.bit_set(bit(0)), Ok(Value::from_u64(0b1011)));
+        assert_eq!(v.bit_set(bit(1)), Ok(v));
+        assert_eq!(
+            Value::from_u64(0).bit_set(bit(63)),
+            Ok(Value::from_u64(1 << 63))
+        );
+
+        assert_eq!(v.bit_clear(bit(3)), Ok(Value::from_u64(0b0010)));
+        assert_eq!(v.bit_clear(bit(0)), Ok(v));
+
+        // Indices are not masked
+        for index in [64, 65, 1 << 32, u64::MAX] {
+            assert_eq!(v.bit_test(bit(index)), Err(ValueError::BitIndexOutOfRange));
+            assert_eq!(v.bit_set(bit(index)), Err(ValueError::BitIndexOutOfRange));
+            assert_eq!(v.bit_clear(bit(index)), Err(ValueError::BitIndexOutOfRange));
+        }
+    }
+
+    #[test]
+    fn debug() {
+        assert_eq!(format!("{:?}", Value::from_u64(255)), "Value(0xff = 255)");
+        assert_eq!(format!("{:?}", Value::from_u64(0)), "Value(0x0 = 0)");
+        assert_eq!(
+            format!("{:?}", Value::from_i64(-1)),
+            "Value(0xffffffffffffffff = 18446744073709551615)"
+        );
+        assert_eq!(
+            format!("{:?}", [Value::from_u64(1), Value::from_u64(16)]),
+            "[Value(0x1 = 1), Value(0x10 = 16)]"
         );
     }
 
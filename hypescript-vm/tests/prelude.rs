@@ -0,0 +1,22 @@
+use hypescript_bytecode::consts::*;
+use hypescript_vm::prelude::*;
+
+fn run(program: &[u8]) -> Result<ExecutionSummary> {
+    ExecutionContext::new(program).run()
+}
+
+// The prelude's `Result` must not prevent using `Result` with other error types
+fn parse(s: &str) -> Result<u64, std::num::ParseIntError> {
+    s.parse()
+}
+
+#[test]
+fn prelude() {
+    let summary = run(&[PUSH8, 2, PUSH8, 3, ADD]).unwrap();
+    assert_eq!(summary.stack, vec![Value::from_u64(5)]);
+
+    let err: Error = run(&[ADD]).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::StackUnderflow);
+
+    assert_eq!(parse("42"), Ok(42));
+}